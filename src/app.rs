@@ -1,16 +1,112 @@
-use crate::dice::DiceState;
+use crate::client::RemoteGame;
+use crate::config::Config;
+use crate::dice::{Dice, DiceState, DiceStateError, DieSpec, Roll};
+use crate::dice_stats::DiceHistory;
+use crate::game::{GameEvent, GameSnapshot, Player};
+use crate::help::help_lines;
+use crate::keymap::{Action, Keymap};
+use crate::probability::{odds_from, odds_keeping, Odds};
+use crate::profile::{GameRecord, PlayerProfile, ProfileStats};
+use crate::protocol::{ClientMessage, ServerMessage};
+use crate::score::{Category, ScoreError};
+use crate::selection::{parse_selection, SelectionError};
+use crate::theme::Theme;
+use crate::ui::{MenuItem, UiTarget};
 use crate::Score;
-use std::num::ParseIntError;
+use chrono::Utc;
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent};
+use std::io;
+use thiserror::Error;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub(crate) enum InputMode {
     Normal,
     Selecting,
+    Confirming,
+    Chatting,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum View {
+    Game,
+    DiceStats,
+    PlayerStats,
+    Help,
+}
+
+/// Number of ticks the dice tumble after being rolled before they show their real values.
+const ROLL_ANIMATION_TICKS: u8 = 3;
+
+/// Random faces shown on the dice that are still tumbling.
+pub(crate) struct DiceAnimation {
+    dice_ids: Vec<usize>,
+    faces: Vec<u32>,
+    ticks_left: u8,
+}
+
+impl DiceAnimation {
+    pub fn is_rolling(&self, die_id: usize) -> bool {
+        self.dice_ids.contains(&die_id)
+    }
+}
+
+/// Number of ticks a status message stays visible.
+const STATUS_MESSAGE_TICKS: u8 = 15;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Feedback shown in the status bar until it expires.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StatusMessage {
+    pub text: String,
+    pub severity: Severity,
+    ticks_left: u8,
+}
+
+/// Why an action the player asked for could not be carried out.
+#[derive(Debug, Error, PartialEq)]
+pub(crate) enum AppError {
+    #[error(transparent)]
+    Dice(#[from] DiceStateError),
+    #[error("{} has already been scored", .0.name())]
+    AlreadyScored(Category),
+    #[error("the Joker rules don't allow scoring this Yahtzee as {}", .0.name())]
+    JokerRule(Category),
+    #[error(transparent)]
+    Selection(#[from] SelectionError),
+    #[error("roll the dice first")]
+    NotRolled,
+    #[error("a game on a server can't be started over")]
+    RemoteNewGame,
+    #[error("chatting needs a game on a server")]
+    NotRemote,
+    #[error("spectators can't play")]
+    Spectating,
+    #[error("lost the connection to the server: {0}")]
+    Connection(String),
 }
 
 pub(crate) enum Event<I> {
     Input(I),
+    Mouse(MouseEvent),
+    Resize,
     Tick,
+    /// Boxed, as a whole game snapshot would make every other event as large.
+    Server(Box<ServerMessage>),
+    /// An event source failed and can't deliver any more events.
+    Error(io::Error),
+}
+
+/// Whether the game keeps running after an input was handled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Flow {
+    Continue,
+    Quit,
 }
 
 pub(crate) struct AppState {
@@ -18,20 +114,326 @@ pub(crate) struct AppState {
     pub input_mode: InputMode,
     pub dice_state: DiceState,
     pub score: Score,
+    pub view: View,
+    pub dice_history: Option<DiceHistory>,
+    pub dice_report: String,
+    pub player: Option<PlayerProfile>,
+    pub player_stats: Option<ProfileStats>,
+    pub highlighted_category: Option<Category>,
+    pub pending_category: Option<Category>,
+    pub config: Config,
+    pub animation: Option<DiceAnimation>,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    /// First line of the help overlay that is shown.
+    pub help_scroll: usize,
+    pub status: Option<StatusMessage>,
+    pub show_odds: bool,
+    /// The odds shown next to the dice, or `None` if the die has too many faces to work them out.
+    pub odds: Option<Vec<Odds>>,
+    /// The dice, held dice and rolls left `odds` were worked out for.
+    odds_input: Option<(Vec<u32>, Option<Vec<u32>>, usize)>,
+    /// Set when playing a game hosted by a server, which then rolls the dice and keeps the score.
+    pub remote: Option<RemoteGame>,
+    pub chat_input: String,
+    game_recorded: bool,
 }
 
 impl AppState {
-    pub fn new() -> Self {
+    pub fn new(die_spec: DieSpec) -> Self {
         Self {
             selection_input: String::new(),
             input_mode: InputMode::Normal,
-            dice_state: DiceState::new(),
+            dice_state: DiceState::new(die_spec),
             score: Score::new(),
+            view: View::Game,
+            dice_history: None,
+            dice_report: String::new(),
+            player: None,
+            player_stats: None,
+            highlighted_category: Some(Category::Aces),
+            pending_category: None,
+            config: Config::default(),
+            animation: None,
+            keymap: Keymap::default(),
+            theme: Theme::default(),
+            help_scroll: 0,
+            status: None,
+            show_odds: false,
+            odds: None,
+            odds_input: None,
+            remote: None,
+            chat_input: String::new(),
+            game_recorded: false,
         }
     }
 
+    pub fn with_dice_history(mut self, dice_history: DiceHistory) -> Self {
+        self.dice_history = Some(dice_history);
+        self
+    }
+
+    pub fn with_player(mut self, player: PlayerProfile) -> Self {
+        self.player = Some(player);
+        self
+    }
+
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_roller(mut self, roller: Box<dyn crate::dice::DiceRoller>) -> Self {
+        self.dice_state = self.dice_state.with_roller(roller);
+        self
+    }
+
+    pub fn with_remote(mut self, remote: RemoteGame) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
     pub fn new_game(&mut self) {
-        *self = Self::new();
+        let previous = std::mem::replace(self, Self::new(self.dice_state.die_spec.clone()));
+        self.dice_history = previous.dice_history;
+        self.player = previous.player;
+        self.config = previous.config;
+        self.keymap = previous.keymap;
+        self.theme = previous.theme;
+        self.show_odds = previous.show_odds;
+        // Keeps the dice roller, which can't be recreated
+        self.dice_state = previous.dice_state;
+        self.dice_state.reset();
+    }
+
+    /// Handles a key press according to the current input mode.
+    pub fn handle_key(&mut self, event: &KeyEvent) -> Flow {
+        match self.input_mode {
+            InputMode::Normal => match self.keymap.action_for(event) {
+                _ if event.code == KeyCode::Esc && self.view != View::Game => {
+                    self.toggle_view(View::Game)
+                }
+                Some(Action::Quit) => return Flow::Quit,
+                Some(action) => self.perform(action),
+                None => {}
+            },
+            InputMode::Selecting => match event.code {
+                KeyCode::Enter => {
+                    let result = self.reroll_selected_dice();
+                    self.report(result);
+                }
+                KeyCode::Char(c) => {
+                    self.selection_input.push(c);
+                }
+                KeyCode::Backspace => {
+                    self.selection_input.pop();
+                }
+                KeyCode::Esc => self.cancel_selection_mode(),
+                _ => {}
+            },
+            InputMode::Confirming => match event.code {
+                KeyCode::Char('y') | KeyCode::Enter => {
+                    let result = self.confirm_scoring();
+                    self.report(result);
+                }
+                KeyCode::Char('n') | KeyCode::Esc => self.cancel_confirmation(),
+                _ => {}
+            },
+            InputMode::Chatting => match event.code {
+                KeyCode::Enter => {
+                    let result = self.send_chat();
+                    self.report(result);
+                }
+                KeyCode::Char(c) => {
+                    self.chat_input.push(c);
+                }
+                KeyCode::Backspace => {
+                    self.chat_input.pop();
+                }
+                KeyCode::Esc => self.cancel_chat(),
+                _ => {}
+            },
+        }
+        self.refresh_odds();
+        Flow::Continue
+    }
+
+    /// Handles a left click on a part of the UI.
+    pub fn handle_click(&mut self, target: UiTarget) -> Flow {
+        if target == UiTarget::Menu(MenuItem::Quit) {
+            return Flow::Quit;
+        }
+        let result = self.click(target);
+        self.report(result);
+        self.refresh_odds();
+        Flow::Continue
+    }
+
+    fn click(&mut self, target: UiTarget) -> Result<(), AppError> {
+        if matches!(self.input_mode, InputMode::Confirming | InputMode::Chatting) {
+            return Ok(());
+        }
+        match target {
+            // Only the view toggles and quitting work while another view covers the game
+            UiTarget::Menu(MenuItem::NewGame | MenuItem::RollDice)
+            | UiTarget::Die(_)
+            | UiTarget::Category(_)
+                if self.view != View::Game => {}
+            UiTarget::Menu(MenuItem::RollDice) | UiTarget::Die(_) | UiTarget::Category(_)
+                if self.is_spectating() =>
+            {
+                return Err(AppError::Spectating)
+            }
+            UiTarget::Menu(MenuItem::NewGame) if self.remote.is_some() => {
+                return Err(AppError::RemoteNewGame)
+            }
+            UiTarget::Menu(MenuItem::NewGame) => self.new_game(),
+            UiTarget::Menu(MenuItem::RollDice) => {
+                if self.dice_selected_for_reroll().is_empty() {
+                    self.cancel_selection_mode();
+                    return self.roll_all_dice();
+                }
+                return self.reroll_selected_dice();
+            }
+            UiTarget::Menu(MenuItem::DiceStats) => self.toggle_view(View::DiceStats),
+            UiTarget::Menu(MenuItem::PlayerStats) => self.toggle_view(View::PlayerStats),
+            UiTarget::Menu(MenuItem::Help) => self.toggle_view(View::Help),
+            UiTarget::Menu(MenuItem::Quit) => {}
+            UiTarget::Die(die_id) => return self.toggle_die_selection(die_id),
+            UiTarget::Category(category) => {
+                self.cancel_selection_mode();
+                return self.score_category(category);
+            }
+        }
+        Ok(())
+    }
+
+    /// Carries out a key bound action and reports a failure in the status bar. Quitting is left
+    /// to the caller, and game actions are ignored while one of the other views is shown.
+    pub fn perform(&mut self, action: Action) {
+        let result = match action {
+            Action::Quit => Ok(()),
+            Action::DiceStats => {
+                self.toggle_view(View::DiceStats);
+                Ok(())
+            }
+            Action::PlayerStats => {
+                self.toggle_view(View::PlayerStats);
+                Ok(())
+            }
+            Action::Help => {
+                self.toggle_view(View::Help);
+                Ok(())
+            }
+            Action::HighlightPrevious if self.view == View::Help => {
+                self.help_scroll = self.help_scroll.saturating_sub(1);
+                Ok(())
+            }
+            Action::HighlightNext if self.view == View::Help => {
+                let last_line = help_lines(&self.dice_state, &self.keymap).len() - 1;
+                self.help_scroll = (self.help_scroll + 1).min(last_line);
+                Ok(())
+            }
+            _ if self.view != View::Game => Ok(()),
+            Action::ToggleOdds => {
+                self.show_odds = !self.show_odds;
+                Ok(())
+            }
+            Action::RollDice
+            | Action::SelectDice
+            | Action::ScoreHighlighted
+            | Action::Score(_)
+            | Action::Chat
+                if self.is_spectating() =>
+            {
+                Err(AppError::Spectating)
+            }
+            Action::Chat => self.start_chat(),
+            Action::NewGame if self.remote.is_some() => Err(AppError::RemoteNewGame),
+            Action::NewGame => {
+                self.new_game();
+                Ok(())
+            }
+            Action::RollDice => self.roll_all_dice(),
+            Action::SelectDice => self.select_dice_to_reroll(),
+            Action::HighlightPrevious => {
+                self.highlight_previous_category();
+                Ok(())
+            }
+            Action::HighlightNext => {
+                self.highlight_next_category();
+                Ok(())
+            }
+            Action::ScoreHighlighted => self.score_highlighted_category(),
+            Action::Score(category) => self.score_category(category),
+        };
+        self.report(result);
+    }
+
+    /// Shows why an action failed in the status bar.
+    pub fn report(&mut self, result: Result<(), AppError>) {
+        if let Err(error) = result {
+            self.set_status(Severity::Error, error.to_string());
+        }
+    }
+
+    pub fn set_status(&mut self, severity: Severity, text: String) {
+        self.status = Some(StatusMessage {
+            text,
+            severity,
+            ticks_left: STATUS_MESSAGE_TICKS,
+        });
+    }
+
+    /// Switches to the given view, or back to the game if it is already shown.
+    pub fn toggle_view(&mut self, view: View) {
+        if self.view == view {
+            self.view = View::Game;
+            return;
+        }
+        match view {
+            View::Game => {}
+            View::Help => self.help_scroll = 0,
+            View::DiceStats => {
+                self.dice_report = match &self.dice_history {
+                    Some(history) => match history.report(&self.dice_state.die_spec) {
+                        Ok(report) => report.to_string(),
+                        Err(error) => format!("Could not read the dice history: {}", error),
+                    },
+                    None => "Dice history is not available.".to_string(),
+                };
+            }
+            View::PlayerStats => {
+                self.player_stats = self
+                    .player
+                    .as_ref()
+                    .map(|player| ProfileStats::new(&player.profile));
+            }
+        }
+        self.view = view;
+    }
+
+    fn record_roll(&mut self, roll: &Roll) {
+        if let Some(history) = &self.dice_history {
+            // Statistics are best effort and must never interrupt a game
+            if let Err(error) = history.record(&self.dice_state.die_spec, roll) {
+                self.set_status(
+                    Severity::Warning,
+                    format!("Could not save the dice history: {}", error),
+                );
+            }
+        }
     }
 
     pub fn reset(&mut self) {
@@ -40,14 +442,170 @@ impl AppState {
         self.input_mode = InputMode::Normal;
     }
 
-    pub fn roll_all_dice(&mut self) {
-        self.dice_state.roll_all_dice()
+    fn finish_turn(&mut self) {
+        self.reset();
+        if self
+            .highlighted_category
+            .is_some_and(|category| self.score.get(category).is_some())
+        {
+            self.highlight_next_category();
+        }
+        if self.score.is_complete() && !self.game_recorded {
+            self.game_recorded = true;
+            self.set_status(
+                Severity::Info,
+                format!("Game over - final score {}", self.score.total_score()),
+            );
+            if let Some(player) = &mut self.player {
+                // Like the dice history, a failing write must not end the game
+                if let Err(error) = player.record_game(GameRecord::new(&self.score, Utc::now())) {
+                    self.set_status(
+                        Severity::Warning,
+                        format!("Could not save the game to your profile: {}", error),
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn roll_all_dice(&mut self) -> Result<(), AppError> {
+        if let Some(remote) = &self.remote {
+            // Before the game starts, rolling tells the table the player is ready
+            if remote.current.is_none() && !remote.over {
+                return self.send(&[ClientMessage::Ready]);
+            }
+            let mut messages = vec![];
+            if !self.dice_state.dice.0.is_empty() {
+                messages.push(ClientMessage::Hold {
+                    held: vec![false; self.dice_state.number_of_dice],
+                });
+            }
+            messages.push(ClientMessage::Roll);
+            return self.send(&messages);
+        }
+        let roll = self.dice_state.roll_all_dice()?;
+        self.record_roll(&roll);
+        self.start_animation((0..roll.rolled.len()).collect());
+        Ok(())
+    }
+
+    fn start_animation(&mut self, dice_ids: Vec<usize>) {
+        self.animation = Some(DiceAnimation {
+            faces: Dice::roll_dice(&self.dice_state.die_spec, dice_ids.len()).0,
+            dice_ids,
+            ticks_left: ROLL_ANIMATION_TICKS,
+        });
+    }
+
+    pub fn on_tick(&mut self) {
+        if let Some(status) = &mut self.status {
+            status.ticks_left = status.ticks_left.saturating_sub(1);
+            if status.ticks_left == 0 {
+                self.status = None;
+            }
+        }
+        if let Some(animation) = &mut self.animation {
+            animation.ticks_left = animation.ticks_left.saturating_sub(1);
+            if animation.ticks_left == 0 {
+                self.animation = None;
+            } else {
+                animation.faces =
+                    Dice::roll_dice(&self.dice_state.die_spec, animation.dice_ids.len()).0;
+            }
+        }
+    }
+
+    /// The dice as they should be drawn, with tumbling faces for dice that are still rolling.
+    pub fn displayed_dice(&self) -> Vec<u32> {
+        let mut dice = self.dice_state.dice.0.clone();
+        if let Some(animation) = &self.animation {
+            for (id, face) in animation.dice_ids.iter().zip(&animation.faces) {
+                if let Some(die) = dice.get_mut(*id) {
+                    *die = *face;
+                }
+            }
+        }
+        dice
+    }
+
+    /// Flips a die between being held and being rerolled, starting a selection if needed.
+    pub fn toggle_die_selection(&mut self, die_id: usize) -> Result<(), AppError> {
+        if die_id >= self.dice_state.dice.0.len() {
+            return Ok(());
+        }
+        self.select_dice_to_reroll()?;
+        let mut selected = self.dice_selected_for_reroll();
+        match selected.iter().position(|id| *id == die_id) {
+            Some(position) => {
+                selected.remove(position);
+            }
+            None => selected.push(die_id),
+        }
+        selected.sort_unstable();
+        selected.dedup();
+        self.selection_input = selected
+            .iter()
+            .map(|id| (id + 1).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        Ok(())
+    }
+
+    /// Works the odds out again if the panel is shown and the dice, the held dice or the rolls
+    /// left changed since the last time. The dice held while selecting are kept for the next roll,
+    /// otherwise the best dice to keep are assumed.
+    fn refresh_odds(&mut self) {
+        if !self.show_odds {
+            return;
+        }
+        let dice = self.dice_state.dice.0.clone();
+        let held = match self.input_mode {
+            InputMode::Selecting => {
+                let selected = self.dice_selected_for_reroll();
+                Some(
+                    dice.iter()
+                        .enumerate()
+                        .filter(|(id, _)| !selected.contains(id))
+                        .map(|(_, face)| *face)
+                        .collect(),
+                )
+            }
+            _ if dice.is_empty() => Some(vec![]),
+            _ => None,
+        };
+        let rolls_left = self
+            .dice_state
+            .max_number_of_rolls
+            .saturating_sub(self.dice_state.number_of_rolls);
+        let input = (dice, held, rolls_left);
+        if self.odds_input.as_ref() == Some(&input) {
+            return;
+        }
+        let (dice, held, rolls_left) = &input;
+        let die_spec = &self.dice_state.die_spec;
+        let number_of_dice = self.dice_state.number_of_dice;
+        self.odds = match held {
+            Some(held) => odds_keeping(die_spec, number_of_dice, held, *rolls_left),
+            None => odds_from(die_spec, number_of_dice, dice, *rolls_left),
+        };
+        self.odds_input = Some(input);
+    }
+
+    /// Zero based ids of the dice currently typed into the selection, or none while it doesn't
+    /// parse (yet).
+    pub fn dice_selected_for_reroll(&self) -> Vec<usize> {
+        if !matches!(self.input_mode, InputMode::Selecting) {
+            return vec![];
+        }
+        parse_selection(&self.selection_input, &self.dice_state.dice.0).unwrap_or_default()
     }
 
-    pub fn select_dice_to_reroll(&mut self) {
-        if !self.dice_state.dice.0.is_empty() {
-            self.input_mode = InputMode::Selecting;
+    pub fn select_dice_to_reroll(&mut self) -> Result<(), AppError> {
+        if self.dice_state.dice.0.is_empty() {
+            return Err(AppError::NotRolled);
         }
+        self.input_mode = InputMode::Selecting;
+        Ok(())
     }
 
     pub fn cancel_selection_mode(&mut self) {
@@ -55,172 +613,603 @@ impl AppState {
         self.input_mode = InputMode::Normal;
     }
 
-    pub fn reroll_selected_dice(&mut self) {
-        if let Ok(one_indexed) = parse_selection_input_to_dice_indices(
-            self.selection_input.drain(..).collect::<String>().as_str(),
-        ) {
-            // TODO protect against overflow (0-1)
-            let dice_ids_to_reroll = one_indexed.into_iter().map(|v| v - 1).collect();
-            if self
-                .dice_state
-                .reroll_selected_dice(dice_ids_to_reroll)
-                .is_err()
-            {
-                self.cancel_selection_mode()
+    pub fn reroll_selected_dice(&mut self) -> Result<(), AppError> {
+        let input: String = self.selection_input.drain(..).collect();
+        let dice_ids_to_reroll = parse_selection(&input, &self.dice_state.dice.0)?;
+        if self.remote.is_some() {
+            let held = (0..self.dice_state.number_of_dice)
+                .map(|id| !dice_ids_to_reroll.contains(&id))
+                .collect();
+            return self.send(&[ClientMessage::Hold { held }, ClientMessage::Roll]);
+        }
+        match self
+            .dice_state
+            .reroll_selected_dice(dice_ids_to_reroll.clone())
+        {
+            Ok(roll) => {
+                self.record_roll(&roll);
+                self.start_animation(dice_ids_to_reroll);
+                Ok(())
+            }
+            Err(error) => {
+                self.cancel_selection_mode();
+                Err(error.into())
             }
         }
     }
 
-    pub fn set_aces(&mut self) {
-        if self
-            .score
-            .upper_section
-            .set_aces(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    /// Scores `category`, unless it would be scratched or is much worse than the alternatives,
+    /// in which case the player has to confirm first.
+    pub fn score_category(&mut self, category: Category) -> Result<(), AppError> {
+        if self.needs_confirmation(category) {
+            self.pending_category = Some(category);
+            self.input_mode = InputMode::Confirming;
+            Ok(())
+        } else {
+            self.commit_category(category)
+        }
     }
 
-    pub fn set_twos(&mut self) {
-        if self
-            .score
-            .upper_section
-            .set_twos(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    pub fn confirm_scoring(&mut self) -> Result<(), AppError> {
+        match self.pending_category.take() {
+            Some(category) => {
+                self.input_mode = InputMode::Normal;
+                self.commit_category(category)
+            }
+            None => Ok(()),
+        }
     }
 
-    pub fn set_threes(&mut self) {
-        if self
-            .score
-            .upper_section
-            .set_threes(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    pub fn cancel_confirmation(&mut self) {
+        self.pending_category = None;
+        self.input_mode = InputMode::Normal;
     }
 
-    pub fn set_fours(&mut self) {
-        if self
-            .score
-            .upper_section
-            .set_fours(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    /// The category waiting for confirmation and the points it would score.
+    pub fn pending_confirmation(&self) -> Option<(Category, u32)> {
+        let category = self.pending_category?;
+        let points = self.potential(category)?;
+        Some((category, points))
     }
 
-    pub fn set_fives(&mut self) {
-        if self
-            .score
-            .upper_section
-            .set_fives(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    fn potential(&self, category: Category) -> Option<u32> {
+        self.score
+            .potential(category, &self.dice_state.dice, &self.dice_state.die_spec)
     }
 
-    pub fn set_sixes(&mut self) {
-        if self
-            .score
-            .upper_section
-            .set_sixes(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
+    fn needs_confirmation(&self, category: Category) -> bool {
+        if !self.config.confirm_scratch {
+            return false;
+        }
+        let points = match self.potential(category) {
+            Some(points) => points,
+            None => return false,
         };
+        if points == 0 {
+            return true;
+        }
+        let best_points = Category::ALL
+            .iter()
+            .filter_map(|category| self.potential(*category))
+            .max()
+            .unwrap_or_default();
+        f64::from(points) < self.config.confirm_below_best_ratio * f64::from(best_points)
     }
 
-    pub fn set_three_of_a_kind(&mut self) {
-        if self
-            .score
-            .lower_section
-            .set_three_of_a_kind(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    fn commit_category(&mut self, category: Category) -> Result<(), AppError> {
+        if self.remote.is_some() {
+            // The scorecard is updated once the server reports the points
+            return self.send(&[ClientMessage::Score { category }]);
+        }
+        self.score
+            .set(category, &self.dice_state.dice, &self.dice_state.die_spec)
+            .map_err(|error| match error {
+                ScoreError::AlreadySet => AppError::AlreadyScored(category),
+                ScoreError::JokerRule => AppError::JokerRule(category),
+            })?;
+        let points = self.score.get(category).unwrap_or_default();
+        self.set_status(
+            Severity::Info,
+            format!("Scored {} for {}", points, category.name()),
+        );
+        self.finish_turn();
+        Ok(())
     }
 
-    pub fn set_four_of_a_kind(&mut self) {
-        if self
-            .score
-            .lower_section
-            .set_four_of_a_kind(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    pub fn score_highlighted_category(&mut self) -> Result<(), AppError> {
+        match self.highlighted_category {
+            Some(category) => self.score_category(category),
+            None => Ok(()),
+        }
     }
 
-    pub fn set_full_house(&mut self) {
-        if self
-            .score
-            .lower_section
-            .set_full_house(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    pub fn highlight_next_category(&mut self) {
+        self.highlighted_category = self.next_open_category(1);
     }
 
-    pub fn set_small_straight(&mut self) {
-        if self
-            .score
-            .lower_section
-            .set_small_straight(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    pub fn highlight_previous_category(&mut self) {
+        self.highlighted_category = self.next_open_category(Category::ALL.len() - 1);
     }
 
-    pub fn set_large_straight(&mut self) {
-        if self
-            .score
-            .lower_section
-            .set_large_straight(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
-        };
+    /// Walks the scorecard from the highlighted category in steps of `step` (wrapping around) and
+    /// returns the first category that hasn't been scored yet.
+    fn next_open_category(&self, step: usize) -> Option<Category> {
+        let categories = Category::ALL.len();
+        let start = self
+            .highlighted_category
+            .and_then(|highlighted| Category::ALL.iter().position(|c| *c == highlighted))
+            .unwrap_or(categories - step % categories);
+        (1..=categories)
+            .map(|offset| Category::ALL[(start + offset * step) % categories])
+            .find(|category| self.score.get(*category).is_none())
     }
 
-    pub fn set_yahtzee(&mut self) {
-        if self
-            .score
-            .lower_section
-            .set_yahtzee(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
+    fn send(&mut self, messages: &[ClientMessage]) -> Result<(), AppError> {
+        if let Some(remote) = &mut self.remote {
+            for message in messages {
+                remote
+                    .send(message)
+                    .map_err(|error| AppError::Connection(error.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn start_chat(&mut self) -> Result<(), AppError> {
+        if self.remote.is_none() {
+            return Err(AppError::NotRemote);
+        }
+        self.cancel_selection_mode();
+        self.input_mode = InputMode::Chatting;
+        Ok(())
+    }
+
+    pub fn cancel_chat(&mut self) {
+        self.chat_input = String::new();
+        self.input_mode = InputMode::Normal;
+    }
+
+    fn send_chat(&mut self) -> Result<(), AppError> {
+        let text: String = self.chat_input.drain(..).collect();
+        self.input_mode = InputMode::Normal;
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        self.send(&[ClientMessage::Chat { text }])
+    }
+
+    /// Mirrors what the server reports about the game played on it.
+    pub fn apply_server_message(&mut self, message: ServerMessage) {
+        let remote = match &mut self.remote {
+            Some(remote) => remote,
+            None => return,
         };
+        match message {
+            ServerMessage::Welcome {
+                table,
+                seat,
+                token,
+                game,
+            } => {
+                let score = match game.players.get(seat) {
+                    Some(player) => player.score.clone(),
+                    None => {
+                        let text = format!("The server seated us at unknown seat {}", seat);
+                        return self.set_status(Severity::Error, text);
+                    }
+                };
+                remote.seat = Some(seat);
+                remote.token = Some(token);
+                self.score = score;
+                let text = match game.current {
+                    None if !game.over => {
+                        format!("Joined table {} - roll when you're ready", table)
+                    }
+                    _ => format!("Joined table {}", table),
+                };
+                self.mirror_game(table, game);
+                self.set_status(Severity::Info, text);
+            }
+            ServerMessage::Watching { table, game } => {
+                remote.spectating = true;
+                self.mirror_game(table, game);
+                self.set_status(Severity::Info, format!("Watching table {}", table));
+            }
+            ServerMessage::Games { .. } => {}
+            ServerMessage::Event(event) => self.apply_game_event(event),
+            ServerMessage::Chat { from, text } => {
+                self.set_status(Severity::Info, format!("{}: {}", from, text))
+            }
+            ServerMessage::Error { message } => self.set_status(Severity::Error, message),
+        }
+        self.refresh_odds();
     }
 
-    pub fn set_chance(&mut self) {
-        if self
-            .score
-            .lower_section
-            .set_chance(&self.dice_state.dice)
-            .is_ok()
-        {
-            self.reset();
+    fn mirror_game(&mut self, table: usize, game: GameSnapshot) {
+        if let Some(remote) = &mut self.remote {
+            remote.table = Some(table);
+            remote.players = game.players;
+            remote.current = game.current;
+            remote.over = game.over;
+        }
+        self.dice_state.die_spec = game.die_spec;
+        self.dice_state.dice = Dice(game.dice);
+        self.dice_state.number_of_rolls = self
+            .dice_state
+            .max_number_of_rolls
+            .saturating_sub(game.rolls_left);
+    }
+
+    pub fn is_spectating(&self) -> bool {
+        self.remote.as_ref().is_some_and(|remote| remote.spectating)
+    }
+
+    fn apply_game_event(&mut self, event: GameEvent) {
+        let remote = match &mut self.remote {
+            Some(remote) => remote,
+            None => return,
         };
+        let mine = |seat| remote.seat == Some(seat);
+        match event {
+            GameEvent::PlayerJoined { seat, name } => {
+                let text = format!("{} joined", name);
+                let number_of_players = remote.players.len();
+                match remote.players.get_mut(seat) {
+                    Some(player) => {
+                        player.name = name;
+                        player.present = true;
+                    }
+                    None if seat == number_of_players => remote.players.push(Player {
+                        name,
+                        score: Score::new(),
+                        present: true,
+                        ready: false,
+                    }),
+                    None => return self.unknown_seat(seat),
+                }
+                self.set_status(Severity::Info, text);
+            }
+            GameEvent::PlayerLeft { seat } => {
+                if let Some(player) = remote.players.get_mut(seat) {
+                    player.present = false;
+                    let text = format!("{} left", player.name);
+                    self.set_status(Severity::Warning, text);
+                }
+            }
+            GameEvent::Ready { seat } => {
+                if let Some(player) = remote.players.get_mut(seat) {
+                    player.ready = true;
+                    let text = format!("{} is ready", player.name);
+                    self.set_status(Severity::Info, text);
+                }
+            }
+            GameEvent::TurnStarted { seat } => {
+                let text = match (mine(seat), remote.players.get(seat)) {
+                    (true, _) => "Your turn".to_string(),
+                    (false, Some(player)) => format!("{}'s turn", player.name),
+                    (false, None) => return self.unknown_seat(seat),
+                };
+                remote.current = Some(seat);
+                self.dice_state.reset();
+                if self.input_mode != InputMode::Chatting {
+                    self.cancel_selection_mode();
+                    self.pending_category = None;
+                }
+                self.set_status(Severity::Info, text);
+            }
+            GameEvent::Held { .. } => {}
+            GameEvent::Rolled {
+                dice,
+                rolled,
+                rolls_left,
+                ..
+            } => {
+                self.dice_state.dice = Dice(dice);
+                self.dice_state.number_of_rolls = self
+                    .dice_state
+                    .max_number_of_rolls
+                    .saturating_sub(rolls_left);
+                self.start_animation(rolled);
+            }
+            GameEvent::Scored {
+                seat,
+                category,
+                points,
+                ..
+            } => {
+                let mine = mine(seat);
+                let player = match remote.players.get_mut(seat) {
+                    Some(player) => player,
+                    None => return self.unknown_seat(seat),
+                };
+                // The dice are still the ones that were scored
+                let _ =
+                    player
+                        .score
+                        .set(category, &self.dice_state.dice, &self.dice_state.die_spec);
+                if mine {
+                    self.score = player.score.clone();
+                    self.set_status(
+                        Severity::Info,
+                        format!("Scored {} for {}", points, category.name()),
+                    );
+                    self.finish_turn();
+                } else {
+                    let text = format!("{} scored {} for {}", player.name, points, category.name());
+                    self.set_status(Severity::Info, text);
+                }
+            }
+            GameEvent::GameOver { scores } => {
+                remote.over = true;
+                remote.current = None;
+                let best = scores.iter().copied().max().unwrap_or_default();
+                let winners: Vec<&str> = remote
+                    .players
+                    .iter()
+                    .zip(&scores)
+                    .filter(|(_, score)| **score == best)
+                    .map(|(player, _)| player.name.as_str())
+                    .collect();
+                let text = format!("Game over - {} won with {}", winners.join(" and "), best);
+                self.dice_state.reset();
+                self.set_status(Severity::Info, text);
+            }
+        }
+    }
+
+    /// Reports an event for a seat the game doesn't have, which only a confused server sends.
+    fn unknown_seat(&mut self, seat: usize) {
+        self.set_status(
+            Severity::Error,
+            format!("The server reported unknown seat {}", seat),
+        );
     }
 }
 
-fn parse_selection_input_to_dice_indices(
-    selection_input: &str,
-) -> Result<Vec<usize>, ParseIntError> {
-    selection_input
-        .split(',')
-        .into_iter()
-        .map(|s| s.parse::<usize>())
-        .collect()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_highlight_skips_filled_categories_and_wraps() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.score.upper_section.twos = Some(4);
+        app_state.score.lower_section.chance = Some(20);
+        assert_eq!(app_state.highlighted_category, Some(Category::Aces));
+
+        app_state.highlight_next_category();
+        assert_eq!(app_state.highlighted_category, Some(Category::Threes));
+        app_state.highlight_previous_category();
+        assert_eq!(app_state.highlighted_category, Some(Category::Aces));
+        app_state.highlight_previous_category();
+        assert_eq!(app_state.highlighted_category, Some(Category::Yahtzee));
+        app_state.highlight_next_category();
+        assert_eq!(app_state.highlighted_category, Some(Category::Aces));
+    }
+
+    #[test]
+    fn test_scoring_highlighted_category_moves_highlight() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.dice_state.dice = Dice(vec![1, 1, 2, 3, 4]);
+        app_state.score_highlighted_category().unwrap();
+        assert_eq!(app_state.score.upper_section.aces, Some(2));
+        assert_eq!(app_state.highlighted_category, Some(Category::Twos));
+    }
+
+    #[test]
+    fn test_highlight_is_cleared_when_scorecard_is_full() {
+        let mut app_state = AppState::new(DieSpec::default()).with_config(Config {
+            confirm_scratch: false,
+            ..Config::default()
+        });
+        for category in Category::ALL {
+            app_state.score_category(category).unwrap();
+        }
+        assert!(app_state.score.is_complete());
+        assert_eq!(app_state.highlighted_category, None);
+    }
+
+    #[test]
+    fn test_scratching_requires_confirmation() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.dice_state.dice = Dice(vec![1, 2, 3, 4, 6]);
+        app_state.score_category(Category::FullHouse).unwrap();
+        assert!(matches!(app_state.input_mode, InputMode::Confirming));
+        assert_eq!(
+            app_state.pending_confirmation(),
+            Some((Category::FullHouse, 0))
+        );
+        assert_eq!(app_state.score.lower_section.full_house, None);
+
+        app_state.cancel_confirmation();
+        assert!(matches!(app_state.input_mode, InputMode::Normal));
+        assert_eq!(app_state.score.lower_section.full_house, None);
+
+        app_state.score_category(Category::FullHouse).unwrap();
+        app_state.confirm_scoring().unwrap();
+        assert!(matches!(app_state.input_mode, InputMode::Normal));
+        assert_eq!(app_state.score.lower_section.full_house, Some(0));
+    }
+
+    #[test]
+    fn test_scoring_points_needs_no_confirmation_by_default() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.dice_state.dice = Dice(vec![1, 2, 3, 4, 6]);
+        app_state.score_category(Category::Aces).unwrap();
+        assert_eq!(app_state.score.upper_section.aces, Some(1));
+    }
+
+    #[test]
+    fn test_low_score_relative_to_best_requires_confirmation() {
+        let mut app_state = AppState::new(DieSpec::default()).with_config(Config {
+            confirm_below_best_ratio: 0.5,
+            ..Config::default()
+        });
+        app_state.dice_state.dice = Dice(vec![1, 2, 3, 4, 6]);
+        app_state.score_category(Category::Aces).unwrap();
+        assert_eq!(app_state.pending_confirmation(), Some((Category::Aces, 1)));
+        app_state.cancel_confirmation();
+        app_state.score_category(Category::SmallStraight).unwrap();
+        assert_eq!(app_state.score.lower_section.small_straight, Some(30));
+    }
+
+    #[test]
+    fn test_confirmation_can_be_disabled() {
+        let mut app_state = AppState::new(DieSpec::default()).with_config(Config {
+            confirm_scratch: false,
+            ..Config::default()
+        });
+        app_state.dice_state.dice = Dice(vec![1, 2, 3, 4, 6]);
+        app_state.score_category(Category::Yahtzee).unwrap();
+        assert!(matches!(app_state.input_mode, InputMode::Normal));
+        assert_eq!(app_state.score.lower_section.yahtzee, Some(0));
+    }
+
+    #[test]
+    fn test_rolled_dice_tumble_before_settling() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.roll_all_dice().unwrap();
+        let animation = app_state.animation.as_ref().unwrap();
+        assert_eq!(animation.dice_ids, vec![0, 1, 2, 3, 4]);
+        assert_eq!(app_state.displayed_dice().len(), 5);
+
+        for _ in 0..ROLL_ANIMATION_TICKS {
+            app_state.on_tick();
+        }
+        assert!(app_state.animation.is_none());
+        assert_eq!(app_state.displayed_dice(), app_state.dice_state.dice.0);
+    }
+
+    #[test]
+    fn test_only_rerolled_dice_tumble() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.roll_all_dice().unwrap();
+        let kept = app_state.dice_state.dice.0.clone();
+        app_state.select_dice_to_reroll().unwrap();
+        app_state.selection_input = "2,4".to_string();
+        assert_eq!(app_state.dice_selected_for_reroll(), vec![1, 3]);
+        app_state.reroll_selected_dice().unwrap();
+
+        assert_eq!(app_state.animation.as_ref().unwrap().dice_ids, vec![1, 3]);
+        let displayed = app_state.displayed_dice();
+        assert_eq!(
+            (displayed[0], displayed[2], displayed[4]),
+            (kept[0], kept[2], kept[4])
+        );
+    }
+
+    #[test]
+    fn test_toggling_dice_edits_selection() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.toggle_die_selection(0).unwrap();
+        assert!(matches!(app_state.input_mode, InputMode::Normal));
+
+        app_state.dice_state.dice = Dice(vec![1, 2, 3, 4, 5]);
+        app_state.toggle_die_selection(3).unwrap();
+        app_state.toggle_die_selection(1).unwrap();
+        assert!(matches!(app_state.input_mode, InputMode::Selecting));
+        assert_eq!(app_state.selection_input, "2,4");
+
+        app_state.toggle_die_selection(3).unwrap();
+        app_state.toggle_die_selection(7).unwrap();
+        assert_eq!(app_state.selection_input, "2");
+    }
+
+    #[test]
+    fn test_help_overlay_scrolls_instead_of_moving_highlight() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.perform(Action::Help);
+        assert_eq!(app_state.view, View::Help);
+
+        app_state.perform(Action::HighlightPrevious);
+        assert_eq!(app_state.help_scroll, 0);
+        for _ in 0..1000 {
+            app_state.perform(Action::HighlightNext);
+        }
+        let last_line = help_lines(&app_state.dice_state, &app_state.keymap).len() - 1;
+        assert_eq!(app_state.help_scroll, last_line);
+        assert_eq!(app_state.highlighted_category, Some(Category::Aces));
+
+        app_state.perform(Action::Help);
+        assert_eq!(app_state.view, View::Game);
+        app_state.perform(Action::Help);
+        assert_eq!(app_state.help_scroll, 0);
+    }
+
+    #[test]
+    fn test_clicks_on_the_game_are_ignored_behind_other_views() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.perform(Action::Help);
+        app_state.handle_click(UiTarget::Menu(MenuItem::RollDice));
+        app_state.handle_click(UiTarget::Category(Category::Chance));
+        assert!(app_state.dice_state.dice.0.is_empty());
+        assert_eq!(app_state.score.get(Category::Chance), None);
+
+        app_state.handle_click(UiTarget::Menu(MenuItem::DiceStats));
+        assert_eq!(app_state.view, View::DiceStats);
+        app_state.handle_click(UiTarget::Menu(MenuItem::DiceStats));
+        app_state.handle_click(UiTarget::Menu(MenuItem::RollDice));
+        assert!(!app_state.dice_state.dice.0.is_empty());
+    }
+
+    #[test]
+    fn test_failed_actions_are_reported_in_status_bar() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.perform(Action::SelectDice);
+        assert_eq!(
+            app_state.status.as_ref().map(|status| status.severity),
+            Some(Severity::Error)
+        );
+
+        for _ in 0..3 {
+            app_state.perform(Action::RollDice);
+        }
+        assert_eq!(
+            app_state.roll_all_dice(),
+            Err(AppError::Dice(DiceStateError::MaxRollsReached))
+        );
+        app_state.perform(Action::RollDice);
+        assert_eq!(
+            app_state.status.as_ref().map(|status| status.text.as_str()),
+            Some("no rolls left this turn, pick a category to score")
+        );
+    }
+
+    #[rstest]
+    #[case("0", SelectionError::NoSuchDie { die: 0, number_of_dice: 5 }.into())]
+    #[case("1,x", SelectionError::InvalidTerm("x".to_string()).into())]
+    #[case("6", SelectionError::NoSuchDie { die: 6, number_of_dice: 5 }.into())]
+    fn test_invalid_selections_are_errors(#[case] input: &str, #[case] expected: AppError) {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.roll_all_dice().unwrap();
+        app_state.select_dice_to_reroll().unwrap();
+        app_state.selection_input = input.to_string();
+        assert_eq!(app_state.reroll_selected_dice(), Err(expected));
+        assert_eq!(app_state.dice_state.number_of_rolls, 1);
+    }
+
+    #[test]
+    fn test_scoring_shows_points_until_message_expires() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.dice_state.dice = Dice(vec![2, 2, 3, 3, 3]);
+        app_state.score_category(Category::FullHouse).unwrap();
+        assert_eq!(
+            app_state.status,
+            Some(StatusMessage {
+                text: "Scored 25 for Full house".to_string(),
+                severity: Severity::Info,
+                ticks_left: STATUS_MESSAGE_TICKS,
+            })
+        );
+        assert_eq!(
+            app_state.score_category(Category::FullHouse),
+            Err(AppError::AlreadyScored(Category::FullHouse))
+        );
+
+        for _ in 0..STATUS_MESSAGE_TICKS {
+            app_state.on_tick();
+        }
+        assert_eq!(app_state.status, None);
+    }
 }
@@ -0,0 +1,423 @@
+use crate::dice::{Dice, DiceStateError, DieSpec};
+use crate::game::{Game, GameError};
+use crate::score::{Category, Score};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// What a bot gets to see when it's its move.
+pub(crate) struct TurnView<'a> {
+    pub die_spec: &'a DieSpec,
+    pub dice: &'a [u32],
+    pub rolls_left: usize,
+    pub score: &'a Score,
+}
+
+/// Written as `{"hold":[true,false,true,false,false]}` or `{"score":"Chance"}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Decision {
+    /// Keeps the dice flagged `true` and rolls the others.
+    Hold(Vec<bool>),
+    Score(Category),
+}
+
+impl Decision {
+    /// Whether the game would accept the decision on this turn.
+    pub fn check(&self, turn: &TurnView) -> Result<(), GameError> {
+        match self {
+            Decision::Hold(_) if turn.rolls_left == 0 => {
+                Err(DiceStateError::MaxRollsReached.into())
+            }
+            Decision::Hold(held) if held.len() != turn.dice.len() => {
+                Err(GameError::WrongHoldCount {
+                    expected: turn.dice.len(),
+                    actual: held.len(),
+                })
+            }
+            Decision::Hold(held) if held.iter().all(|held| *held) => Err(GameError::NothingToRoll),
+            // Left to the scorecard, which knows the Joker rules
+            Decision::Score(category) => turn
+                .score
+                .clone()
+                .set(*category, &Dice(turn.dice.to_vec()), turn.die_spec)
+                .map_err(|error| GameError::from_score(error, *category)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A computer player. It's asked for a decision after every roll, and has to score once no rolls
+/// are left.
+pub(crate) trait Bot: Send {
+    fn decide(&mut self, turn: &TurnView) -> Decision;
+
+    /// Called before a game starts with the bot at `seat`.
+    fn new_game(&mut self, _seat: usize, _players: usize, _die_spec: &DieSpec) {}
+
+    /// Called with everyone's final score once a game is over.
+    fn game_over(&mut self, _scores: &[u32]) {}
+
+    /// How many of the bot's decisions had to be replaced because it got them wrong.
+    fn faults(&self) -> usize {
+        0
+    }
+}
+
+/// Goes for as many of a kind as possible and scores whatever is worth the most.
+pub(crate) struct GreedyBot;
+
+impl Bot for GreedyBot {
+    fn decide(&mut self, turn: &TurnView) -> Decision {
+        let (best, points) = best_category(turn);
+        let made = matches!(
+            best,
+            Category::Yahtzee | Category::LargeStraight | Category::FullHouse
+        ) && points > 0;
+        if turn.rolls_left == 0 || made {
+            return Decision::Score(best);
+        }
+        // The most common face, the higher one on a tie
+        let kept = turn
+            .dice
+            .iter()
+            .max_by_key(|face| (turn.dice.iter().filter(|die| die == face).count(), **face))
+            .copied();
+        let held: Vec<bool> = turn.dice.iter().map(|die| Some(*die) == kept).collect();
+        if held.iter().all(|held| *held) {
+            return Decision::Score(best);
+        }
+        Decision::Hold(held)
+    }
+}
+
+/// Chases the upper section bonus: keeps the most common face it can still score up there, and
+/// settles for whatever is worth the most once that doesn't work out.
+pub(crate) struct UpperBot;
+
+impl Bot for UpperBot {
+    fn decide(&mut self, turn: &TurnView) -> Decision {
+        let count = |face: u32| turn.dice.iter().filter(|die| **die == face).count();
+        let target = Category::ALL[..6]
+            .iter()
+            .filter(|category| turn.score.get(**category).is_none())
+            .filter_map(|category| Some((*category, category.face()?)))
+            .filter(|(_, face)| count(*face) > 0)
+            .max_by_key(|(_, face)| (count(*face), *face));
+        let (category, face) = match target {
+            Some(target) => target,
+            None => return GreedyBot.decide(turn),
+        };
+        if turn.rolls_left == 0 || count(face) == turn.dice.len() {
+            return match count(face) >= 3 {
+                true => Decision::Score(category),
+                false => Decision::Score(best_category(turn).0),
+            };
+        }
+        Decision::Hold(turn.dice.iter().map(|die| *die == face).collect())
+    }
+}
+
+/// Keeps and scores whatever it happens to pick, as a baseline for the other strategies.
+pub(crate) struct RandomBot {
+    rng: StdRng,
+}
+
+impl RandomBot {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Bot for RandomBot {
+    fn decide(&mut self, turn: &TurnView) -> Decision {
+        if turn.rolls_left > 0 && self.rng.gen_bool(0.5) {
+            let mut held: Vec<bool> = turn.dice.iter().map(|_| self.rng.gen()).collect();
+            let rerolled = self.rng.gen_range(0..held.len());
+            held[rerolled] = false;
+            return Decision::Hold(held);
+        }
+        let dice = Dice(turn.dice.to_vec());
+        let open: Vec<_> = Category::ALL
+            .iter()
+            .filter(|category| {
+                turn.score
+                    .potential(**category, &dice, turn.die_spec)
+                    .is_some()
+            })
+            .collect();
+        match open.choose(&mut self.rng) {
+            Some(category) => Decision::Score(**category),
+            None => Decision::Score(Category::Chance),
+        }
+    }
+}
+
+/// The bots that come with the game.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Strategy {
+    Greedy,
+    Upper,
+    Random,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 3] = [Strategy::Greedy, Strategy::Upper, Strategy::Random];
+
+    /// A bot playing the strategy. Only the random one makes use of `seed`.
+    pub fn bot(&self, seed: u64) -> Box<dyn Bot> {
+        match self {
+            Strategy::Greedy => Box::new(GreedyBot),
+            Strategy::Upper => Box::new(UpperBot),
+            Strategy::Random => Box::new(RandomBot::new(seed)),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Strategy::Greedy => "greedy",
+            Strategy::Upper => "upper",
+            Strategy::Random => "random",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("unknown strategy '{0}', expected one of greedy, upper or random")]
+pub(crate) struct UnknownStrategy(String);
+
+impl FromStr for Strategy {
+    type Err = UnknownStrategy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strategy::ALL
+            .iter()
+            .find(|strategy| strategy.to_string() == s)
+            .copied()
+            .ok_or_else(|| UnknownStrategy(s.to_string()))
+    }
+}
+
+/// Takes the next step of `seat`'s turn: the first roll, or whatever `bot` decides after that. A
+/// decision the game doesn't accept scores the best category instead, so a confused bot can't
+/// stall the game.
+pub(crate) fn play_step(game: &mut Game, seat: usize, bot: &mut dyn Bot) -> Result<(), GameError> {
+    let snapshot = game.snapshot();
+    if snapshot.dice.is_empty() {
+        return game.roll(seat);
+    }
+    let turn = TurnView {
+        die_spec: &snapshot.die_spec,
+        dice: &snapshot.dice,
+        rolls_left: snapshot.rolls_left,
+        score: &snapshot.players[seat].score,
+    };
+    let result = match bot.decide(&turn) {
+        Decision::Hold(held) => game.hold(seat, held).and_then(|_| game.roll(seat)),
+        Decision::Score(category) => game.score(seat, category),
+    };
+    result.or_else(|_| game.score(seat, best_category(&turn).0))
+}
+
+/// The open category worth the most with the dice as they are, the first one on a tie.
+pub(crate) fn best_category(turn: &TurnView) -> (Category, u32) {
+    let dice = Dice(turn.dice.to_vec());
+    Category::ALL
+        .iter()
+        .filter_map(|category| {
+            let points = turn.score.potential(*category, &dice, turn.die_spec)?;
+            Some((*category, points))
+        })
+        .rev()
+        .max_by_key(|(_, points)| *points)
+        .unwrap_or((Category::Chance, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decide(dice: &[u32], rolls_left: usize, score: &Score) -> Decision {
+        GreedyBot.decide(&TurnView {
+            die_spec: &DieSpec::default(),
+            dice,
+            rolls_left,
+            score,
+        })
+    }
+
+    #[test]
+    fn test_greedy_bot_keeps_the_most_common_face() {
+        let score = Score::new();
+        assert_eq!(
+            decide(&[2, 5, 5, 1, 2], 2, &score),
+            Decision::Hold(vec![false, true, true, false, false])
+        );
+        assert_eq!(
+            decide(&[6, 6, 6, 6, 6], 2, &score),
+            Decision::Score(Category::Yahtzee)
+        );
+    }
+
+    #[test]
+    fn test_greedy_bot_scores_the_most_points_without_rolls_left() {
+        let mut score = Score::new();
+        assert_eq!(
+            decide(&[3, 3, 3, 4, 4], 0, &score),
+            Decision::Score(Category::FullHouse)
+        );
+        score.lower_section.full_house = Some(25);
+        assert_eq!(
+            decide(&[3, 3, 3, 4, 4], 0, &score),
+            Decision::Score(Category::ThreeOfAKind)
+        );
+    }
+
+    #[test]
+    fn test_upper_bot_goes_for_the_bonus() {
+        let mut score = Score::new();
+        let decide = |dice: &[u32], rolls_left, score: &Score| {
+            UpperBot.decide(&TurnView {
+                die_spec: &DieSpec::default(),
+                dice,
+                rolls_left,
+                score,
+            })
+        };
+        assert_eq!(
+            decide(&[4, 4, 1, 6, 6], 2, &score),
+            Decision::Hold(vec![false, false, false, true, true])
+        );
+        score.upper_section.sixes = Some(18);
+        assert_eq!(
+            decide(&[4, 4, 1, 6, 6], 2, &score),
+            Decision::Hold(vec![true, true, false, false, false])
+        );
+        assert_eq!(
+            decide(&[4, 4, 4, 6, 6], 0, &score),
+            Decision::Score(Category::Fours)
+        );
+    }
+
+    #[test]
+    fn test_random_bots_only_make_legal_decisions() {
+        let die_spec = DieSpec::default();
+        let mut score = Score::new();
+        score.lower_section.chance = Some(20);
+        let mut bot = RandomBot::new(1);
+        for rolls_left in [0, 1, 2].repeat(20) {
+            let turn = TurnView {
+                die_spec: &die_spec,
+                dice: &[1, 2, 3, 4, 5],
+                rolls_left,
+                score: &score,
+            };
+            assert_eq!(bot.decide(&turn).check(&turn), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_strategies_parse_by_name() {
+        for strategy in Strategy::ALL {
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+        }
+        assert_eq!(
+            "lucky".parse::<Strategy>(),
+            Err(UnknownStrategy("lucky".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_decisions_are_json() {
+        let hold = Decision::Hold(vec![true, false, true, false, false]);
+        let json = serde_json::to_string(&hold).unwrap();
+        assert_eq!(json, r#"{"hold":[true,false,true,false,false]}"#);
+        assert_eq!(
+            serde_json::from_str::<Decision>(r#"{"score":"Chance"}"#).unwrap(),
+            Decision::Score(Category::Chance)
+        );
+    }
+
+    #[test]
+    fn test_illegal_decisions_are_caught() {
+        let mut score = Score::new();
+        score.lower_section.chance = Some(20);
+        let die_spec = DieSpec::default();
+        let turn = |rolls_left| TurnView {
+            die_spec: &die_spec,
+            dice: &[1, 2, 3, 4, 5],
+            rolls_left,
+            score: &score,
+        };
+        assert_eq!(Decision::Hold(vec![false; 5]).check(&turn(1)), Ok(()));
+        assert_eq!(
+            Decision::Hold(vec![false; 5]).check(&turn(0)),
+            Err(GameError::Dice(DiceStateError::MaxRollsReached))
+        );
+        assert_eq!(
+            Decision::Hold(vec![false; 3]).check(&turn(1)),
+            Err(GameError::WrongHoldCount {
+                expected: 5,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            Decision::Hold(vec![true; 5]).check(&turn(1)),
+            Err(GameError::NothingToRoll)
+        );
+        assert_eq!(
+            Decision::Score(Category::Chance).check(&turn(0)),
+            Err(GameError::AlreadyScored(Category::Chance))
+        );
+        assert_eq!(Decision::Score(Category::Aces).check(&turn(0)), Ok(()));
+
+        // A second Yahtzee has to go in the upper box of its face
+        score.lower_section.yahtzee = Some(50);
+        let joker = TurnView {
+            die_spec: &die_spec,
+            dice: &[3; 5],
+            rolls_left: 0,
+            score: &score,
+        };
+        assert_eq!(
+            Decision::Score(Category::FullHouse).check(&joker),
+            Err(GameError::JokerRule(Category::FullHouse))
+        );
+        assert_eq!(Decision::Score(Category::Threes).check(&joker), Ok(()));
+    }
+
+    #[test]
+    fn test_ties_go_to_the_first_category() {
+        let score = Score::new();
+        assert_eq!(
+            best_category(&TurnView {
+                die_spec: &DieSpec::default(),
+                dice: &[1, 3, 3, 5, 6],
+                rolls_left: 0,
+                score: &score,
+            }),
+            (Category::Chance, 18)
+        );
+        let mut score = Score::new();
+        score.lower_section.chance = Some(18);
+        score.upper_section.sixes = Some(18);
+        assert_eq!(
+            best_category(&TurnView {
+                die_spec: &DieSpec::default(),
+                dice: &[1, 2, 2, 4, 6],
+                rolls_left: 0,
+                score: &score,
+            }),
+            (Category::Twos, 4)
+        );
+    }
+}
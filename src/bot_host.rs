@@ -0,0 +1,338 @@
+//! Bots written in any language, run as child processes that talk JSON lines over stdin and
+//! stdout. The host writes one message per line, tagged with its `type`:
+//!
+//! ```text
+//! {"type":"new_game","seat":1,"players":2,"die":"d6"}
+//! {"type":"decide","dice":[2,2,5,6,2],"rolls_left":2,"scorecard":{...},"open":["Aces",...]}
+//! {"type":"rejected","message":"Chance has already been scored"}
+//! {"type":"game_over","scores":[212,187]}
+//! ```
+//!
+//! Only `decide` needs an answer, a single line with either the dice to keep for the next roll
+//! or the category to score:
+//!
+//! ```text
+//! {"hold":[true,true,false,false,true]}
+//! {"score":"FullHouse"}
+//! ```
+//!
+//! The scorecard has the same shape as in a saved game, with `null` for open categories. An
+//! answer that is late, can't be read or breaks the rules is a fault: the bot is told why with
+//! `rejected`, and the host scores the open category worth the least for it instead. Bots should ignore
+//! message types they don't know, and can log to stderr.
+
+use crate::bot::{Bot, Decision, TurnView};
+use crate::dice::{Dice, DieSpec};
+use crate::game::GameError;
+use crate::protocol::write_message;
+use crate::score::{Category, Score};
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
+
+/// After this many timeouts in a row the bot is taken to be stuck and isn't waited for anymore.
+const MAX_TIMEOUTS_IN_A_ROW: usize = 3;
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum HostMessage<'a> {
+    NewGame {
+        seat: usize,
+        players: usize,
+        die: &'a DieSpec,
+    },
+    Decide {
+        dice: &'a [u32],
+        rolls_left: usize,
+        scorecard: &'a Score,
+        open: Vec<Category>,
+    },
+    Rejected {
+        message: String,
+    },
+    GameOver {
+        scores: &'a [u32],
+    },
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum BotError {
+    #[error("no answer within {} ms", .0.as_millis())]
+    Timeout(Duration),
+    #[error("the bot isn't running")]
+    Exited,
+    #[error("invalid answer: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error(transparent)]
+    Illegal(#[from] GameError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A bot running as a child process.
+pub(crate) struct ExternalBot {
+    child: Child,
+    /// Lines for the bot's stdin. They are written on a thread of their own, so a bot that
+    /// doesn't read can't hold up the host. Dropping it closes stdin, which tells a well-behaved
+    /// bot to exit.
+    lines: Option<Sender<Vec<u8>>>,
+    answers: Receiver<String>,
+    timeout: Duration,
+    timeouts_in_a_row: usize,
+    faults: usize,
+}
+
+impl ExternalBot {
+    /// Starts `command` with the shell, so it can have arguments, e.g. `python3 bots/greedy.py`.
+    pub fn spawn(command: &str, timeout: Duration) -> io::Result<Self> {
+        let mut child = shell(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::new(ErrorKind::BrokenPipe, "no stdout"))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::new(ErrorKind::BrokenPipe, "no stdin"))?;
+        let (lines, to_write) = mpsc::channel::<Vec<u8>>();
+        thread::spawn(move || {
+            for line in to_write {
+                if stdin.write_all(&line).and_then(|_| stdin.flush()).is_err() {
+                    return;
+                }
+            }
+        });
+        let (sender, answers) = mpsc::channel();
+        // Reads on its own thread so waiting for an answer can time out
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => return,
+                };
+                if !line.trim().is_empty() && sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Self {
+            lines: Some(lines),
+            child,
+            answers,
+            timeout,
+            timeouts_in_a_row: 0,
+            faults: 0,
+        })
+    }
+
+    /// Whether the bot stopped answering, in which case nothing is sent to it anymore either.
+    fn is_stuck(&self) -> bool {
+        self.timeouts_in_a_row >= MAX_TIMEOUTS_IN_A_ROW
+    }
+
+    fn send(&mut self, message: &HostMessage) -> Result<(), BotError> {
+        if self.is_stuck() {
+            return Err(BotError::Timeout(self.timeout));
+        }
+        let mut line = vec![];
+        write_message(&mut line, message)?;
+        match &self.lines {
+            Some(lines) if lines.send(line).is_ok() => Ok(()),
+            _ => Err(BotError::Exited),
+        }
+    }
+
+    fn ask(&mut self, turn: &TurnView) -> Result<Decision, BotError> {
+        if self.is_stuck() {
+            return Err(BotError::Timeout(self.timeout));
+        }
+        // Answers that came in too late for an earlier question
+        while self.answers.try_recv().is_ok() {}
+        self.send(&HostMessage::Decide {
+            dice: turn.dice,
+            rolls_left: turn.rolls_left,
+            scorecard: turn.score,
+            open: Category::ALL
+                .iter()
+                .copied()
+                .filter(|category| turn.score.get(*category).is_none())
+                .collect(),
+        })?;
+        let answer = match self.answers.recv_timeout(self.timeout) {
+            Ok(answer) => answer,
+            Err(RecvTimeoutError::Timeout) => {
+                self.timeouts_in_a_row += 1;
+                return Err(BotError::Timeout(self.timeout));
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(BotError::Exited),
+        };
+        self.timeouts_in_a_row = 0;
+        let decision: Decision = serde_json::from_str(&answer)?;
+        decision.check(turn)?;
+        Ok(decision)
+    }
+}
+
+impl Bot for ExternalBot {
+    fn decide(&mut self, turn: &TurnView) -> Decision {
+        match self.ask(turn) {
+            Ok(decision) => decision,
+            Err(error) => {
+                self.faults += 1;
+                // The bot may well not be listening anymore
+                let _ = self.send(&HostMessage::Rejected {
+                    message: error.to_string(),
+                });
+                Decision::Score(worst_category(turn))
+            }
+        }
+    }
+
+    fn new_game(&mut self, seat: usize, players: usize, die_spec: &DieSpec) {
+        let _ = self.send(&HostMessage::NewGame {
+            seat,
+            players,
+            die: die_spec,
+        });
+    }
+
+    fn game_over(&mut self, scores: &[u32]) {
+        let _ = self.send(&HostMessage::GameOver { scores });
+    }
+
+    fn faults(&self) -> usize {
+        self.faults
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        self.lines = None;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// What a faulty bot gets to score, so that getting things wrong never pays off.
+fn worst_category(turn: &TurnView) -> Category {
+    let dice = Dice(turn.dice.to_vec());
+    Category::ALL
+        .iter()
+        .copied()
+        .filter_map(|category| {
+            let points = turn.score.potential(category, &dice, turn.die_spec)?;
+            Some((category, points))
+        })
+        .min_by_key(|(_, points)| *points)
+        .map_or(Category::Chance, |(category, _)| category)
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A bot that gives `answer` to every question.
+    fn answering(answer: &str) -> String {
+        format!(
+            r#"while read -r line; do case "$line" in *'"decide"'*) echo '{}';; esac; done"#,
+            answer
+        )
+    }
+
+    /// Asks `bot` what to do with a full house, which scores nothing as aces.
+    fn decide(bot: &mut ExternalBot, rolls_left: usize, score: &Score) -> Decision {
+        bot.decide(&TurnView {
+            die_spec: &DieSpec::default(),
+            dice: &[3, 3, 3, 4, 4],
+            rolls_left,
+            score,
+        })
+    }
+
+    #[test]
+    fn test_external_bots_answer_in_json() {
+        let mut bot = ExternalBot::spawn(
+            &answering(r#"{"hold":[true,true,true,false,false]}"#),
+            TIMEOUT,
+        )
+        .unwrap();
+        let score = Score::new();
+        bot.new_game(0, 2, &DieSpec::default());
+        assert_eq!(
+            decide(&mut bot, 2, &score),
+            Decision::Hold(vec![true, true, true, false, false])
+        );
+        assert_eq!(decide(&mut bot, 1, &score), decide(&mut bot, 1, &score));
+        assert_eq!(bot.faults(), 0);
+    }
+
+    #[test]
+    fn test_illegal_answers_are_replaced() {
+        let mut bot = ExternalBot::spawn(&answering(r#"{"score":"Chance"}"#), TIMEOUT).unwrap();
+        let mut score = Score::new();
+        assert_eq!(
+            decide(&mut bot, 0, &score),
+            Decision::Score(Category::Chance)
+        );
+        score.lower_section.chance = Some(17);
+        assert_eq!(decide(&mut bot, 0, &score), Decision::Score(Category::Aces));
+        assert_eq!(bot.faults(), 1);
+
+        let mut bot = ExternalBot::spawn(&answering("pass"), TIMEOUT).unwrap();
+        assert_eq!(decide(&mut bot, 1, &score), Decision::Score(Category::Aces));
+        assert_eq!(bot.faults(), 1);
+    }
+
+    #[test]
+    fn test_slow_and_missing_bots_are_faults() {
+        let mut bot = ExternalBot::spawn("sleep 5", Duration::from_millis(20)).unwrap();
+        let score = Score::new();
+        for _ in 0..MAX_TIMEOUTS_IN_A_ROW + 2 {
+            assert_eq!(decide(&mut bot, 2, &score), Decision::Score(Category::Aces));
+        }
+        assert_eq!(bot.faults(), MAX_TIMEOUTS_IN_A_ROW + 2);
+
+        let mut bot = ExternalBot::spawn("exit 0", TIMEOUT).unwrap();
+        assert_eq!(decide(&mut bot, 2, &score), Decision::Score(Category::Aces));
+        assert_eq!(bot.faults(), 1);
+    }
+
+    #[test]
+    fn test_bots_that_dont_read_hold_up_nothing() {
+        let mut bot = ExternalBot::spawn("exec sleep 100", Duration::from_millis(20)).unwrap();
+        let score = Score::new();
+        // Far more than fits into the pipe
+        for _ in 0..10_000 {
+            bot.new_game(0, 2, &DieSpec::default());
+        }
+        for _ in 0..1000 {
+            assert_eq!(decide(&mut bot, 2, &score), Decision::Score(Category::Aces));
+            bot.game_over(&[0, 0]);
+        }
+        assert_eq!(bot.faults(), 1000);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::bot::{Strategy, UnknownStrategy};
+use crate::dice::{DieSpec, DieSpecError};
+use crate::protocol::{DEFAULT_PORT, DEFAULT_WS_PORT};
+use crate::theme::{ThemeName, UnknownTheme};
+use crate::tournament::ReportFormat;
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub(crate) enum CliError {
+    #[error("missing value for '{0}'")]
+    MissingValue(String),
+    #[error("unknown argument '{0}'")]
+    UnknownArgument(String),
+    #[error("invalid value '{value}' for '{arg}'")]
+    InvalidValue { arg: String, value: String },
+    #[error(transparent)]
+    DieSpec(#[from] DieSpecError),
+    #[error(transparent)]
+    Theme(#[from] UnknownTheme),
+    #[error(transparent)]
+    Strategy(#[from] UnknownStrategy),
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Command {
+    Play,
+    DiceStats,
+    /// Hosts a game for others to join.
+    Serve,
+    /// Serves the REST API, if built with the `http` feature.
+    ServeHttp,
+    /// Plays a game hosted at the given address.
+    Join(String),
+    /// Follows a game hosted at the given address without playing.
+    Watch(String),
+    /// Lists the games on the server at the given address.
+    Games(String),
+    /// Plays external bots against each other.
+    BotHost,
+    /// Plays built-in strategies, and any external bots, against each other.
+    Tournament,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Options {
+    pub command: Command,
+    pub die_spec: DieSpec,
+    pub player: String,
+    pub config: Option<PathBuf>,
+    pub theme: Option<ThemeName>,
+    /// Where `serve` or `serve-http` listens, each has its own default.
+    pub addr: Option<String>,
+    /// Where `serve` also lets browsers in over WebSocket, on `DEFAULT_WS_PORT` if the address
+    /// has no port.
+    pub ws_addr: Option<String>,
+    /// How many players a served or created game waits for, or a tournament game seats.
+    pub seats: usize,
+    /// The table to `join` or `watch`, any fitting one if `None`.
+    pub table: Option<usize>,
+    /// Whether `join` opens a new table instead.
+    pub create: bool,
+    /// Whether `join` fills the free seats with bots.
+    pub bots: bool,
+    /// Gets back a seat after the connection dropped.
+    pub token: Option<String>,
+    /// The commands starting external bots for `bot-host` and `tournament`.
+    pub bot_commands: Vec<String>,
+    /// The built-in strategies playing in a `tournament`.
+    pub strategies: Vec<Strategy>,
+    /// How many games every table of a tournament plays.
+    pub games: usize,
+    /// Picked at random if `None`.
+    pub seed: Option<u64>,
+    /// How long a bot gets to answer.
+    pub bot_timeout: Duration,
+    pub report_format: ReportFormat,
+}
+
+impl Options {
+    /// Parses the command line arguments, without the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut options = Self {
+            command: Command::Play,
+            die_spec: DieSpec::default(),
+            player: std::env::var("USER").unwrap_or_else(|_| "player".to_string()),
+            config: None,
+            theme: None,
+            addr: None,
+            ws_addr: None,
+            seats: 2,
+            table: None,
+            create: false,
+            bots: false,
+            token: None,
+            bot_commands: vec![],
+            strategies: vec![],
+            games: 100,
+            seed: None,
+            bot_timeout: Duration::from_secs(1),
+            report_format: ReportFormat::Text,
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--die" => {
+                    let value = args.next().ok_or(CliError::MissingValue(arg))?;
+                    options.die_spec = value.parse()?;
+                }
+                "--player" => {
+                    options.player = args.next().ok_or(CliError::MissingValue(arg))?;
+                }
+                "--config" => {
+                    let value = args.next().ok_or(CliError::MissingValue(arg))?;
+                    options.config = Some(PathBuf::from(value));
+                }
+                "--theme" => {
+                    let value = args.next().ok_or(CliError::MissingValue(arg))?;
+                    options.theme = Some(value.parse()?);
+                }
+                "--addr" => {
+                    options.addr = Some(args.next().ok_or(CliError::MissingValue(arg))?);
+                }
+                "--ws-addr" => {
+                    let addr = args.next().ok_or(CliError::MissingValue(arg))?;
+                    options.ws_addr = Some(with_default_port(addr, DEFAULT_WS_PORT));
+                }
+                "--seats" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+                    options.seats = match value.parse() {
+                        Ok(seats) if seats > 0 => seats,
+                        _ => return Err(CliError::InvalidValue { arg, value }),
+                    };
+                }
+                "--table" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+                    match value.parse() {
+                        Ok(table) => options.table = Some(table),
+                        Err(_) => return Err(CliError::InvalidValue { arg, value }),
+                    }
+                }
+                "--create" => options.create = true,
+                "--bots" => options.bots = true,
+                "--token" => {
+                    options.token = Some(args.next().ok_or(CliError::MissingValue(arg))?);
+                }
+                "--bot" => {
+                    let command = args.next().ok_or(CliError::MissingValue(arg))?;
+                    options.bot_commands.push(command);
+                }
+                "--strategies" => {
+                    let value = args.next().ok_or(CliError::MissingValue(arg))?;
+                    for name in value.split(',') {
+                        options.strategies.push(name.trim().parse()?);
+                    }
+                }
+                "--format" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+                    match value.parse() {
+                        Ok(format) => options.report_format = format,
+                        Err(_) => return Err(CliError::InvalidValue { arg, value }),
+                    }
+                }
+                "--games" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+                    options.games = match value.parse() {
+                        Ok(games) if games > 0 => games,
+                        _ => return Err(CliError::InvalidValue { arg, value }),
+                    };
+                }
+                "--seed" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+                    match value.parse() {
+                        Ok(seed) => options.seed = Some(seed),
+                        Err(_) => return Err(CliError::InvalidValue { arg, value }),
+                    }
+                }
+                "--timeout" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+                    match value.parse() {
+                        Ok(millis) => options.bot_timeout = Duration::from_millis(millis),
+                        Err(_) => return Err(CliError::InvalidValue { arg, value }),
+                    }
+                }
+                "dice-stats" if options.command == Command::Play => {
+                    options.command = Command::DiceStats;
+                }
+                "serve" if options.command == Command::Play => {
+                    options.command = Command::Serve;
+                }
+                "serve-http" if options.command == Command::Play => {
+                    options.command = Command::ServeHttp;
+                }
+                "join" if options.command == Command::Play => {
+                    let addr = args.next().ok_or(CliError::MissingValue(arg))?;
+                    options.command = Command::Join(with_default_port(addr, DEFAULT_PORT));
+                }
+                "watch" if options.command == Command::Play => {
+                    let addr = args.next().ok_or(CliError::MissingValue(arg))?;
+                    options.command = Command::Watch(with_default_port(addr, DEFAULT_PORT));
+                }
+                "games" if options.command == Command::Play => {
+                    let addr = args.next().ok_or(CliError::MissingValue(arg))?;
+                    options.command = Command::Games(with_default_port(addr, DEFAULT_PORT));
+                }
+                "bot-host" if options.command == Command::Play => {
+                    options.command = Command::BotHost;
+                }
+                "tournament" if options.command == Command::Play => {
+                    options.command = Command::Tournament;
+                }
+                _ => return Err(CliError::UnknownArgument(arg)),
+            }
+        }
+        if options.command == Command::BotHost && options.bot_commands.is_empty() {
+            return Err(CliError::MissingValue("--bot".to_string()));
+        }
+        if options.command == Command::Tournament && options.strategies.is_empty() {
+            options.strategies = Strategy::ALL.to_vec();
+        }
+        Ok(options)
+    }
+}
+
+/// Lets users type just the host name when the default `port` will do.
+fn with_default_port(addr: String, port: u16) -> String {
+    if addr.contains(':') {
+        addr
+    } else {
+        format!("{}:{}", addr, port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_defaults_to_six_sided_die() {
+        let options = Options::parse(args(&[])).unwrap();
+        assert_eq!(options.command, Command::Play);
+        assert_eq!(options.die_spec, DieSpec::default());
+    }
+
+    #[test]
+    fn test_parses_die() {
+        let options = Options::parse(args(&["--die", "d8"])).unwrap();
+        assert_eq!(options.die_spec, DieSpec::new(8).unwrap());
+    }
+
+    #[test]
+    fn test_parses_player() {
+        let options = Options::parse(args(&["--player", "ada"])).unwrap();
+        assert_eq!(options.player, "ada");
+    }
+
+    #[test]
+    fn test_parses_config_path() {
+        let options = Options::parse(args(&["--config", "/tmp/yahtzee.json"])).unwrap();
+        assert_eq!(options.config, Some(PathBuf::from("/tmp/yahtzee.json")));
+    }
+
+    #[test]
+    fn test_parses_theme() {
+        let options = Options::parse(args(&["--theme", "light"])).unwrap();
+        assert_eq!(options.theme, Some(ThemeName::Light));
+        assert!(matches!(
+            Options::parse(args(&["--theme", "pink"])),
+            Err(CliError::Theme(_))
+        ));
+    }
+
+    #[test]
+    fn test_parses_dice_stats_command() {
+        let options = Options::parse(args(&["dice-stats", "--die", "d20"])).unwrap();
+        assert_eq!(options.command, Command::DiceStats);
+        assert_eq!(options.die_spec, DieSpec::new(20).unwrap());
+    }
+
+    #[test]
+    fn test_parses_bot_host_command() {
+        let options = Options::parse(args(&[
+            "bot-host",
+            "--bot",
+            "python3 greedy.py",
+            "--bot",
+            "./chance",
+            "--games",
+            "20",
+            "--seed",
+            "7",
+            "--timeout",
+            "250",
+        ]))
+        .unwrap();
+        assert_eq!(options.command, Command::BotHost);
+        assert_eq!(options.bot_commands, ["python3 greedy.py", "./chance"]);
+        assert_eq!(options.games, 20);
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.bot_timeout, Duration::from_millis(250));
+
+        assert_eq!(
+            Options::parse(args(&["bot-host"])),
+            Err(CliError::MissingValue("--bot".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parses_tournament_command() {
+        let options = Options::parse(args(&["tournament"])).unwrap();
+        assert_eq!(options.command, Command::Tournament);
+        assert_eq!(options.strategies, Strategy::ALL);
+        assert_eq!(options.seats, 2);
+
+        let options = Options::parse(args(&[
+            "tournament",
+            "--strategies",
+            "greedy, random",
+            "--seats",
+            "3",
+            "--format",
+            "csv",
+        ]))
+        .unwrap();
+        assert_eq!(options.strategies, [Strategy::Greedy, Strategy::Random]);
+        assert_eq!(options.seats, 3);
+        assert_eq!(options.report_format, ReportFormat::Csv);
+
+        assert!(matches!(
+            Options::parse(args(&["tournament", "--strategies", "greedy,lucky"])),
+            Err(CliError::Strategy(_))
+        ));
+        assert_eq!(
+            Options::parse(args(&["tournament", "--format", "xml"])),
+            Err(CliError::InvalidValue {
+                arg: "--format".to_string(),
+                value: "xml".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parses_network_commands() {
+        let options = Options::parse(args(&["serve", "--seats", "4", "--addr", ":9000"])).unwrap();
+        assert_eq!(options.command, Command::Serve);
+        assert_eq!(options.seats, 4);
+        assert_eq!(options.addr.as_deref(), Some(":9000"));
+        assert_eq!(options.ws_addr, None);
+        let options = Options::parse(args(&["serve", "--ws-addr", "0.0.0.0:9001"])).unwrap();
+        assert_eq!(options.ws_addr.as_deref(), Some("0.0.0.0:9001"));
+        let options = Options::parse(args(&["serve", "--ws-addr", "0.0.0.0"])).unwrap();
+        assert_eq!(options.ws_addr.as_deref(), Some("0.0.0.0:7879"));
+        let options = Options::parse(args(&["serve-http"])).unwrap();
+        assert_eq!(options.command, Command::ServeHttp);
+        assert_eq!(options.addr, None);
+
+        let options = Options::parse(args(&["join", "office-pc"])).unwrap();
+        assert_eq!(options.command, Command::Join("office-pc:7878".to_string()));
+        let options = Options::parse(args(&["join", "10.0.0.2:9000"])).unwrap();
+        assert_eq!(options.command, Command::Join("10.0.0.2:9000".to_string()));
+        let options = Options::parse(args(&["games", "office-pc"])).unwrap();
+        assert_eq!(
+            options.command,
+            Command::Games("office-pc:7878".to_string())
+        );
+
+        let options =
+            Options::parse(args(&["join", "pc", "--create", "--bots", "--seats", "3"])).unwrap();
+        assert!(options.create && options.bots);
+        assert_eq!(options.seats, 3);
+        let options = Options::parse(args(&["join", "pc", "--table", "2"])).unwrap();
+        assert_eq!(options.table, Some(2));
+        let options = Options::parse(args(&["watch", "pc", "--table", "3"])).unwrap();
+        assert_eq!(options.command, Command::Watch("pc:7878".to_string()));
+        assert_eq!(options.table, Some(3));
+        let options = Options::parse(args(&["join", "pc", "--token", "a1b2"])).unwrap();
+        assert_eq!(options.token.as_deref(), Some("a1b2"));
+
+        assert_eq!(
+            Options::parse(args(&["serve", "--seats", "0"])),
+            Err(CliError::InvalidValue {
+                arg: "--seats".to_string(),
+                value: "0".to_string()
+            })
+        );
+        assert_eq!(
+            Options::parse(args(&["join"])),
+            Err(CliError::MissingValue("join".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_arguments() {
+        assert_eq!(
+            Options::parse(args(&["--die"])),
+            Err(CliError::MissingValue("--die".to_string()))
+        );
+        assert_eq!(
+            Options::parse(args(&["--dice"])),
+            Err(CliError::UnknownArgument("--dice".to_string()))
+        );
+    }
+}
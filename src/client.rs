@@ -0,0 +1,362 @@
+use crate::app::Event;
+use crate::events::{EventSource, Events};
+use crate::game::Player;
+use crate::protocol::{
+    line_too_long, parse_message, read_message, write_message, ClientMessage, ServerMessage,
+    TableSummary, MAX_LINE,
+};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// The connection to a game hosted by a server, and what the server last told about the table.
+/// The dice and the player's own scorecard are mirrored in the usual places of `AppState`.
+pub(crate) struct RemoteGame {
+    stream: TcpStream,
+    /// `None` until the server welcomed the player, like `table` and `token`.
+    pub seat: Option<usize>,
+    pub table: Option<usize>,
+    pub token: Option<String>,
+    pub players: Vec<Player>,
+    pub current: Option<usize>,
+    pub over: bool,
+    /// Whether the player only watches the game.
+    pub spectating: bool,
+}
+
+impl RemoteGame {
+    /// Connects to the server at `addr` and sends `greeting`, which should get the player a
+    /// seat.
+    pub fn connect<A: ToSocketAddrs>(addr: A, greeting: &[ClientMessage]) -> io::Result<Self> {
+        let mut remote_game = Self {
+            stream: TcpStream::connect(addr)?,
+            seat: None,
+            table: None,
+            token: None,
+            players: vec![],
+            current: None,
+            over: false,
+            spectating: false,
+        };
+        for message in greeting {
+            remote_game.send(message)?;
+        }
+        Ok(remote_game)
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) -> io::Result<()> {
+        write_message(&mut self.stream, message)
+    }
+
+    /// Passes on every message from the server as an `Event::Server`. Reading times out every
+    /// `poll_timeout` to check whether to stop, and the connection closing ends the source with
+    /// an `Event::Error`.
+    pub fn spawn_reader(&self, events: &mut Events, poll_timeout: Duration) -> io::Result<()> {
+        let stream = self.stream.try_clone()?;
+        stream.set_read_timeout(Some(poll_timeout))?;
+        events.spawn(move |source| read_messages(stream, &source));
+        Ok(())
+    }
+}
+
+/// Asks the server at `addr` for its tables.
+pub(crate) fn list_games<A: ToSocketAddrs>(addr: A) -> io::Result<Vec<TableSummary>> {
+    let mut stream = TcpStream::connect(addr)?;
+    write_message(&mut stream, &ClientMessage::ListGames)?;
+    let mut reader = BufReader::new(stream);
+    match read_message(&mut reader) {
+        Ok(Some(ServerMessage::Games { games })) => Ok(games),
+        Ok(Some(ServerMessage::Error { message })) => Err(io::Error::other(message)),
+        Ok(_) => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "the server didn't list its games",
+        )),
+        Err(error) => Err(io::Error::new(ErrorKind::InvalidData, error)),
+    }
+}
+
+/// Works like `read_message`, but keeps a partly read line across the timeouts.
+fn read_messages(stream: TcpStream, source: &EventSource) {
+    let mut reader = BufReader::new(stream);
+    let mut line = vec![];
+    // Whether the rest of a line longer than `MAX_LINE` is still to be thrown away
+    let mut skipping = false;
+    while !source.is_shut_down() {
+        let limit = (MAX_LINE + 1 - line.len()) as u64;
+        let read = match reader.by_ref().take(limit).read_until(b'\n', &mut line) {
+            Ok(0) => Err(io::Error::new(
+                ErrorKind::ConnectionAborted,
+                "the server closed the connection",
+            )),
+            Ok(_) if line.last() != Some(&b'\n') && line.len() > MAX_LINE => {
+                line.clear();
+                let reported = std::mem::replace(&mut skipping, true);
+                Ok((!reported).then(|| Err(line_too_long())))
+            }
+            // The rest of the line comes after the timeout
+            Ok(_) if line.last() != Some(&b'\n') => Ok(None),
+            Ok(_) if skipping => {
+                skipping = false;
+                line.clear();
+                Ok(None)
+            }
+            Ok(_) => {
+                let message = parse_message::<ServerMessage>(&line).transpose();
+                line.clear();
+                Ok(message)
+            }
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        };
+        let event = match read {
+            Ok(None) => continue,
+            Ok(Some(Ok(message))) => Event::Server(Box::new(message)),
+            // One garbled message doesn't end the game
+            Ok(Some(Err(error))) => Event::Server(Box::new(ServerMessage::Error {
+                message: format!("Ignored a message from the server: {}", error),
+            })),
+            Err(error) => {
+                source.send(Event::Error(error));
+                return;
+            }
+        };
+        if !source.send(event) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppState;
+    use crate::dice::DieSpec;
+    use crate::game::{Game, GameEvent};
+    use crate::score::Category;
+    use crate::server::tests::{join, start_server};
+    use crate::ui::render_app;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+    use tui::backend::TestBackend;
+    use tui::Terminal;
+
+    fn press(app_state: &mut AppState, keys: &str) {
+        for c in keys.chars() {
+            app_state.handle_key(&KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+    }
+
+    /// Applies messages from the server until `done` holds.
+    fn receive_until<F: Fn(&AppState) -> bool>(events: &Events, app_state: &mut AppState, done: F) {
+        while !done(app_state) {
+            match events.next().unwrap() {
+                Event::Server(message) => app_state.apply_server_message(*message),
+                Event::Error(error) => panic!("{}", error),
+                _ => {}
+            }
+        }
+    }
+
+    fn status(app_state: &AppState) -> &str {
+        app_state.status.as_ref().map_or("", |status| &status.text)
+    }
+
+    #[test]
+    fn test_playing_a_turn_on_a_server() {
+        let addr = start_server(vec![2, 2, 2, 5, 6, 2, 2]);
+        let join_any = ClientMessage::Join {
+            name: "ada".to_string(),
+            table: None,
+        };
+        let remote = RemoteGame::connect(addr, &[join_any]).unwrap();
+        let mut events = Events::new();
+        remote
+            .spawn_reader(&mut events, Duration::from_millis(10))
+            .unwrap();
+        let mut app_state = AppState::new(DieSpec::default()).with_remote(remote);
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "Joined table 1 - roll when you're ready"
+        });
+        assert!(app_state.remote.as_ref().unwrap().token.is_some());
+
+        let (mut bob, _) = join(addr, "bob");
+        bob.send(&ClientMessage::Ready);
+        press(&mut app_state, "r");
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "Your turn"
+        });
+
+        // The server rolls the dice, the client only shows them
+        press(&mut app_state, "r");
+        assert!(app_state.dice_state.dice.0.is_empty());
+        receive_until(&events, &mut app_state, |app_state| {
+            app_state.dice_state.dice.0 == [2, 2, 2, 5, 6]
+        });
+        press(&mut app_state, "e45");
+        app_state.handle_key(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        receive_until(&events, &mut app_state, |app_state| {
+            app_state.dice_state.dice.0 == [2; 5]
+        });
+        assert_eq!(app_state.dice_state.number_of_rolls, 2);
+
+        app_state.handle_key(&KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        press(&mut app_state, "y");
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "bob's turn"
+        });
+        assert_eq!(app_state.score.lower_section.yahtzee, Some(50));
+        assert!(app_state.dice_state.dice.0.is_empty());
+
+        press(&mut app_state, "r");
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "it's not your turn"
+        });
+
+        press(&mut app_state, "mgg");
+        app_state.handle_key(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            bob.receive_until(|message| matches!(message, ServerMessage::Chat { .. })),
+            ServerMessage::Chat {
+                from: "ada".to_string(),
+                text: "gg".to_string()
+            }
+        );
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "ada: gg"
+        });
+
+        press(&mut app_state, "n");
+        assert_eq!(
+            status(&app_state),
+            "a game on a server can't be started over"
+        );
+
+        drop(bob);
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "bob left"
+        });
+    }
+
+    #[test]
+    fn test_broken_lines_from_the_server_are_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let remote = RemoteGame::connect(listener.local_addr().unwrap(), &[]).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        let mut events = Events::new();
+        remote
+            .spawn_reader(&mut events, Duration::from_millis(10))
+            .unwrap();
+        let mut app_state = AppState::new(DieSpec::default()).with_remote(remote);
+
+        // Split across several timeouts of the reader
+        server.write_all(br#"{"type":"chat","#).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        server.write_all(br#""from":"bob","text":"hi"}"#).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        server.write_all(b"\n").unwrap();
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "bob: hi"
+        });
+
+        server.write_all(b"not json\n").unwrap();
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state).starts_with("Ignored a message from the server: invalid message")
+        });
+
+        server.write_all(&vec![b' '; MAX_LINE + 10]).unwrap();
+        server
+            .write_all(b"\n{\"type\":\"error\",\"message\":\"still here\"}\n")
+            .unwrap();
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state).ends_with("longer than 1048576 bytes")
+        });
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "still here"
+        });
+    }
+
+    #[test]
+    fn test_unknown_seats_from_the_server_are_reported() {
+        let addr = start_server(vec![]);
+        let remote = RemoteGame::connect(addr, &[]).unwrap();
+        let mut app_state = AppState::new(DieSpec::default()).with_remote(remote);
+        let game = Game::new(DieSpec::default(), 2).snapshot();
+        app_state.apply_server_message(ServerMessage::Welcome {
+            table: 1,
+            seat: 3,
+            token: "token".to_string(),
+            game,
+        });
+        assert_eq!(status(&app_state), "The server seated us at unknown seat 3");
+        for event in [
+            GameEvent::PlayerJoined {
+                seat: 2,
+                name: "ada".to_string(),
+            },
+            GameEvent::TurnStarted { seat: 2 },
+            GameEvent::Scored {
+                seat: 2,
+                category: Category::Chance,
+                points: 20,
+                total: 20,
+            },
+        ] {
+            app_state.apply_server_message(ServerMessage::Event(event));
+            assert_eq!(status(&app_state), "The server reported unknown seat 2");
+        }
+        assert!(app_state.remote.as_ref().unwrap().players.is_empty());
+    }
+
+    #[test]
+    fn test_watching_a_game_on_a_server() {
+        let addr = start_server(vec![2, 2, 2, 5, 6]);
+        let (mut ada, _) = join(addr, "ada");
+        let (mut bob, _) = join(addr, "bob");
+        ada.send(&ClientMessage::Ready);
+        bob.send(&ClientMessage::Ready);
+        ada.receive_until(|message| {
+            matches!(message, ServerMessage::Event(GameEvent::TurnStarted { .. }))
+        });
+
+        let remote = RemoteGame::connect(addr, &[ClientMessage::Watch { table: None }]).unwrap();
+        let mut events = Events::new();
+        remote
+            .spawn_reader(&mut events, Duration::from_millis(10))
+            .unwrap();
+        let mut app_state = AppState::new(DieSpec::default()).with_remote(remote);
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "Watching table 1"
+        });
+
+        ada.send(&ClientMessage::Roll);
+        ada.send(&ClientMessage::Score {
+            category: Category::Twos,
+        });
+        receive_until(&events, &mut app_state, |app_state| {
+            status(app_state) == "bob's turn"
+        });
+        let remote = app_state.remote.as_ref().unwrap();
+        assert_eq!(remote.players[0].score.upper_section.twos, Some(6));
+
+        press(&mut app_state, "r");
+        assert_eq!(status(&app_state), "spectators can't play");
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 40)).unwrap();
+        let frame = terminal
+            .draw(|rect| {
+                render_app(&app_state, rect);
+            })
+            .unwrap();
+        let screen: String = frame
+            .buffer
+            .content()
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect();
+        assert!(screen.contains("Scorecards"));
+    }
+}
@@ -0,0 +1,66 @@
+use crate::keymap::KeymapConfig;
+use crate::storage::read_json;
+use crate::theme::ThemeName;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+pub(crate) const CONFIG_FILE: &str = "config.json";
+
+/// User settings read from `config.json`. Missing fields fall back to their defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Config {
+    /// Ask for confirmation before a category is scored for zero points.
+    pub confirm_scratch: bool,
+    /// Also ask when a category would score less than this fraction of the best open category,
+    /// e.g. `0.5` asks before taking 10 points when 25 are available elsewhere.
+    pub confirm_below_best_ratio: f64,
+    pub keymap: KeymapConfig,
+    /// Overrides `NO_COLOR`, but not the `--theme` flag.
+    pub theme: Option<ThemeName>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            confirm_scratch: true,
+            confirm_below_best_ratio: 0.0,
+            keymap: KeymapConfig::default(),
+            theme: None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from `path`, using the defaults if the file doesn't exist.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(read_json(path)?.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::temp_path;
+
+    #[test]
+    fn test_missing_config_uses_defaults() {
+        let config = Config::load(&temp_path(CONFIG_FILE)).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_partial_config_keeps_defaults() {
+        let config: Config = serde_json::from_str(r#"{"confirm_below_best_ratio": 0.5}"#).unwrap();
+        assert!(config.confirm_scratch);
+        assert_eq!(config.confirm_below_best_ratio, 0.5);
+        assert_eq!(config.theme, None);
+    }
+
+    #[test]
+    fn test_theme_is_read_from_config() {
+        let config: Config = serde_json::from_str(r#"{"theme": "high-contrast"}"#).unwrap();
+        assert_eq!(config.theme, Some(ThemeName::HighContrast));
+    }
+}
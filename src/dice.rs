@@ -1,18 +1,247 @@
-use rand::distributions::Uniform;
-use rand::Rng;
+use rand::distributions::{Uniform, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
 
-fn throw_n_dice(die_range: Uniform<u32>, n_dice: usize) -> Vec<u32> {
-    let mut rng = rand::thread_rng();
-    (&mut rng).sample_iter(die_range).take(n_dice).collect()
+/// Dice come from untrusted places like the network, so their size is capped.
+const MAX_FACES: usize = 1000;
+/// Keeps every score, summed over many games even, far from overflowing.
+const MAX_FACE_VALUE: u32 = 1_000_000;
+
+fn throw_n_dice(die_spec: &DieSpec, n_dice: usize) -> Vec<u32> {
+    throw_n_dice_with(&mut rand::thread_rng(), die_spec, n_dice)
+}
+
+fn throw_n_dice_with<R: Rng>(mut rng: R, die_spec: &DieSpec, n_dice: usize) -> Vec<u32> {
+    let face_indices: Vec<usize> = match die_spec.weights() {
+        Some(weights) => {
+            // Weights are validated when the spec is built, nonzero and with a sum that fits
+            let distribution = WeightedIndex::new(weights).expect("valid die weights");
+            (&mut rng).sample_iter(distribution).take(n_dice).collect()
+        }
+        None => {
+            let distribution = Uniform::new(0, die_spec.faces.len());
+            (&mut rng).sample_iter(distribution).take(n_dice).collect()
+        }
+    };
+    face_indices
+        .into_iter()
+        .map(|index| die_spec.faces[index])
+        .collect()
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub(crate) enum DieSpecError {
+    #[error("a die needs at least two faces")]
+    TooFewFaces,
+    #[error("a die can have at most {} faces", MAX_FACES)]
+    TooManyFaces,
+    #[error("face {0} is larger than {}", MAX_FACE_VALUE)]
+    FaceTooLarge(u32),
+    #[error("face {0} appears more than once")]
+    DuplicateFace(u32),
+    #[error("expected {expected} weights but got {actual}")]
+    WeightCountMismatch { expected: usize, actual: usize },
+    #[error("at least one face must have a non-zero weight")]
+    AllWeightsZero,
+    #[error("the weights add up to more than {}", u32::MAX)]
+    WeightsTooLarge,
+    #[error("invalid die definition '{0}'")]
+    Invalid(String),
+}
+
+/// Describes a single die: the values printed on its faces and, optionally, how likely each face
+/// is to come up. Faces are kept in ascending order, which is also the order used for straights.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct DieSpec {
+    faces: Vec<u32>,
+    weights: Option<Vec<u32>>,
+}
+
+impl DieSpec {
+    /// A standard die with faces numbered `1..=number_of_faces`, e.g. `DieSpec::new(8)` for a d8.
+    pub fn new(number_of_faces: u32) -> Result<Self, DieSpecError> {
+        if number_of_faces as usize > MAX_FACES {
+            return Err(DieSpecError::TooManyFaces);
+        }
+        Self::with_faces((1..=number_of_faces).collect())
+    }
+
+    pub fn with_faces(mut faces: Vec<u32>) -> Result<Self, DieSpecError> {
+        if faces.len() < 2 {
+            return Err(DieSpecError::TooFewFaces);
+        }
+        if faces.len() > MAX_FACES {
+            return Err(DieSpecError::TooManyFaces);
+        }
+        if let Some(face) = faces.iter().find(|face| **face > MAX_FACE_VALUE) {
+            return Err(DieSpecError::FaceTooLarge(*face));
+        }
+        faces.sort_unstable();
+        if let Some(window) = faces.windows(2).find(|window| window[0] == window[1]) {
+            return Err(DieSpecError::DuplicateFace(window[0]));
+        }
+        Ok(Self {
+            faces,
+            weights: None,
+        })
+    }
+
+    /// Builds a die from `(face, weight)` pairs. A face with weight 2 comes up twice as often as
+    /// a face with weight 1.
+    pub fn with_weighted_faces(mut weighted_faces: Vec<(u32, u32)>) -> Result<Self, DieSpecError> {
+        weighted_faces.sort_unstable_by_key(|(face, _)| *face);
+        let (faces, weights): (Vec<u32>, Vec<u32>) = weighted_faces.into_iter().unzip();
+        Self::with_faces(faces)?.with_weights(weights)
+    }
+
+    /// Attaches weights to the faces, in ascending face order.
+    pub fn with_weights(mut self, weights: Vec<u32>) -> Result<Self, DieSpecError> {
+        if weights.len() != self.faces.len() {
+            return Err(DieSpecError::WeightCountMismatch {
+                expected: self.faces.len(),
+                actual: weights.len(),
+            });
+        }
+        let total = weights
+            .iter()
+            .try_fold(0u32, |total, weight| total.checked_add(*weight))
+            .ok_or(DieSpecError::WeightsTooLarge)?;
+        if total == 0 {
+            return Err(DieSpecError::AllWeightsZero);
+        }
+        self.weights = Some(weights);
+        Ok(self)
+    }
+
+    pub fn faces(&self) -> &[u32] {
+        self.faces.as_slice()
+    }
+
+    pub fn weights(&self) -> Option<&[u32]> {
+        self.weights.as_deref()
+    }
+
+    /// Probability of each face coming up, in face order.
+    pub fn probabilities(&self) -> Vec<f64> {
+        match self.weights() {
+            Some(weights) => {
+                let total: u32 = weights.iter().sum();
+                weights
+                    .iter()
+                    .map(|weight| f64::from(*weight) / f64::from(total))
+                    .collect()
+            }
+            None => vec![1.0 / self.faces.len() as f64; self.faces.len()],
+        }
+    }
+}
+
+impl Default for DieSpec {
+    fn default() -> Self {
+        Self {
+            faces: (1..=6).collect(),
+            weights: None,
+        }
+    }
+}
+
+/// Formats the spec in the same notation accepted by `FromStr`.
+impl fmt::Display for DieSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let is_standard = self.faces.iter().copied().eq(1..=self.faces.len() as u32);
+        match self.weights() {
+            None if is_standard => write!(f, "d{}", self.faces.len()),
+            None => {
+                let faces: Vec<String> = self.faces.iter().map(u32::to_string).collect();
+                write!(f, "{}", faces.join(","))
+            }
+            Some(weights) => {
+                let weighted_faces: Vec<String> = self
+                    .faces
+                    .iter()
+                    .zip(weights)
+                    .map(|(face, weight)| format!("{}:{}", face, weight))
+                    .collect();
+                write!(f, "{}", weighted_faces.join(","))
+            }
+        }
+    }
+}
+
+impl From<DieSpec> for String {
+    fn from(die_spec: DieSpec) -> Self {
+        die_spec.to_string()
+    }
+}
+
+impl TryFrom<String> for DieSpec {
+    type Error = DieSpecError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Parses `d8`/`D20` for standard dice, `2,4,6,8` for custom faces and `1:1,2:1,6:3` for
+/// weighted faces.
+impl FromStr for DieSpec {
+    type Err = DieSpecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || DieSpecError::Invalid(s.to_string());
+
+        if let Some(number_of_faces) = s.strip_prefix('d').or_else(|| s.strip_prefix('D')) {
+            return Self::new(number_of_faces.parse().map_err(|_| invalid())?);
+        }
+
+        if s.contains(':') {
+            let weighted_faces = s
+                .split(',')
+                .map(|entry| {
+                    let (face, weight) = entry.split_once(':').ok_or_else(invalid)?;
+                    Ok((
+                        face.trim().parse().map_err(|_| invalid())?,
+                        weight.trim().parse().map_err(|_| invalid())?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, DieSpecError>>()?;
+            return Self::with_weighted_faces(weighted_faces);
+        }
+
+        let faces = s
+            .split(',')
+            .map(|face| face.trim().parse().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, DieSpecError>>()?;
+        Self::with_faces(faces)
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct Dice(pub(crate) Vec<u32>);
 
+/// A single throw within a turn: the dice that were kept and the values that were rolled.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Roll {
+    pub roll_number: usize,
+    pub kept: Vec<u32>,
+    pub rolled: Vec<u32>,
+}
+
+#[derive(Debug, Error, PartialEq)]
 pub(crate) enum DiceStateError {
+    #[error("no rolls left this turn, pick a category to score")]
     MaxRollsReached,
-    WrongDiceIds,
+    #[error("only dice 1 to {0} can be rerolled")]
+    WrongDiceIds(usize),
 }
 
 impl Dice {
@@ -20,29 +249,98 @@ impl Dice {
         Self(vec![])
     }
 
-    pub fn roll_dice(n_dice: usize) -> Self {
-        let die_range = Uniform::new_inclusive(1, 6);
-        Self(throw_n_dice(die_range, n_dice))
+    pub fn roll_dice(die_spec: &DieSpec, n_dice: usize) -> Self {
+        Self(throw_n_dice(die_spec, n_dice))
+    }
+}
+
+/// Decides which faces come up when dice are rolled.
+pub(crate) trait DiceRoller: Send {
+    fn roll(&mut self, die_spec: &DieSpec, n_dice: usize) -> Vec<u32>;
+}
+
+pub(crate) struct RandomRoller;
+
+impl DiceRoller for RandomRoller {
+    fn roll(&mut self, die_spec: &DieSpec, n_dice: usize) -> Vec<u32> {
+        throw_n_dice(die_spec, n_dice)
+    }
+}
+
+/// Rolls the same faces for the same seed, so a game can be played again with the same dice.
+pub(crate) struct SeededRoller {
+    rng: StdRng,
+}
+
+impl SeededRoller {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl DiceRoller for SeededRoller {
+    fn roll(&mut self, die_spec: &DieSpec, n_dice: usize) -> Vec<u32> {
+        throw_n_dice_with(&mut self.rng, die_spec, n_dice)
+    }
+}
+
+/// Hands out predetermined faces in order, so tests can play through a known game.
+#[cfg(test)]
+pub(crate) struct ScriptedRoller {
+    faces: VecDeque<u32>,
+}
+
+#[cfg(test)]
+impl ScriptedRoller {
+    pub fn new<I: IntoIterator<Item = u32>>(faces: I) -> Self {
+        Self {
+            faces: faces.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl DiceRoller for ScriptedRoller {
+    fn roll(&mut self, _die_spec: &DieSpec, n_dice: usize) -> Vec<u32> {
+        assert!(self.faces.len() >= n_dice, "the scripted dice ran out");
+        self.faces.drain(..n_dice).collect()
     }
 }
 
 pub(crate) struct DiceState {
     pub dice: Dice,
-    number_of_dice: usize,
+    pub(crate) die_spec: DieSpec,
+    pub(crate) number_of_dice: usize,
     pub(crate) max_number_of_rolls: usize,
     pub(crate) number_of_rolls: usize,
+    roller: Box<dyn DiceRoller>,
 }
 
 impl DiceState {
-    pub fn new() -> Self {
+    pub fn new(die_spec: DieSpec) -> Self {
         Self {
             dice: Dice::new(),
+            die_spec,
             number_of_dice: 5,
             number_of_rolls: 0,
             max_number_of_rolls: 3,
+            roller: Box::new(RandomRoller),
         }
     }
 
+    #[cfg(test)]
+    pub fn with_roller(mut self, roller: Box<dyn DiceRoller>) -> Self {
+        self.roller = roller;
+        self
+    }
+
+    /// Trades the roller for `roller`.
+    pub fn swap_roller(&mut self, roller: &mut Box<dyn DiceRoller>) {
+        std::mem::swap(&mut self.roller, roller);
+    }
+
     pub fn reset(&mut self) {
         self.dice = Dice::new();
         self.number_of_rolls = 0;
@@ -52,18 +350,24 @@ impl DiceState {
         self.number_of_rolls >= self.max_number_of_rolls
     }
 
-    pub fn roll_all_dice(&mut self) {
-        if !self.reached_max_rolls() {
-            self.number_of_rolls += 1;
-            self.dice = Dice::roll_dice(self.number_of_dice);
+    pub fn roll_all_dice(&mut self) -> Result<Roll, DiceStateError> {
+        if self.reached_max_rolls() {
+            return Err(DiceStateError::MaxRollsReached);
         }
+        self.number_of_rolls += 1;
+        self.dice = Dice(self.roller.roll(&self.die_spec, self.number_of_dice));
+        Ok(Roll {
+            roll_number: self.number_of_rolls,
+            kept: vec![],
+            rolled: self.dice.0.clone(),
+        })
     }
 
     pub fn reroll_selected_dice(
         &mut self,
         dice_ids_to_reroll: Vec<usize>,
-    ) -> Result<(), DiceStateError> {
-        let allowed_dice_ids: HashSet<usize> = (0..self.number_of_dice).into_iter().collect();
+    ) -> Result<Roll, DiceStateError> {
+        let allowed_dice_ids: HashSet<usize> = (0..self.number_of_dice).collect();
         let is_selection_subset = dice_ids_to_reroll
             .iter()
             .copied()
@@ -71,15 +375,28 @@ impl DiceState {
             .is_subset(&allowed_dice_ids);
 
         if !is_selection_subset {
-            return Err(DiceStateError::WrongDiceIds);
+            return Err(DiceStateError::WrongDiceIds(self.number_of_dice));
         }
 
         if self.reached_max_rolls() {
             return Err(DiceStateError::MaxRollsReached);
         }
 
-        let new_dice = Dice::roll_dice(dice_ids_to_reroll.len());
+        let new_dice = Dice(self.roller.roll(&self.die_spec, dice_ids_to_reroll.len()));
         self.number_of_rolls += 1;
+        let kept = self
+            .dice
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !dice_ids_to_reroll.contains(index))
+            .map(|(_, value)| *value)
+            .collect();
+        let roll = Roll {
+            roll_number: self.number_of_rolls,
+            kept,
+            rolled: new_dice.0.clone(),
+        };
 
         // Replace the selected dice with new ones, retaining the original order
         for (index, new_value) in dice_ids_to_reroll.iter().zip(new_dice.0) {
@@ -88,6 +405,99 @@ impl DiceState {
             // Remove element at index and replace with last one
             self.dice.0.swap_remove(*index);
         }
-        Ok(())
+        Ok(roll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("d4", vec![1, 2, 3, 4])]
+    #[case("D8", vec![1, 2, 3, 4, 5, 6, 7, 8])]
+    #[case("d20", (1..=20).collect())]
+    #[case("2, 8, 4,6", vec![2, 4, 6, 8])]
+    fn test_die_spec_parses_faces(#[case] input: &str, #[case] expected_faces: Vec<u32>) {
+        let die_spec: DieSpec = input.parse().unwrap();
+        assert_eq!(die_spec.faces(), expected_faces.as_slice());
+        assert_eq!(die_spec.weights(), None);
+    }
+
+    #[test]
+    fn test_die_spec_parses_weighted_faces() {
+        let die_spec: DieSpec = "6:3,1:1,2:0".parse().unwrap();
+        assert_eq!(die_spec.faces(), &[1, 2, 6]);
+        assert_eq!(die_spec.weights(), Some([1, 0, 3].as_slice()));
+    }
+
+    #[rstest]
+    #[case("d1", DieSpecError::TooFewFaces)]
+    #[case("dx", DieSpecError::Invalid("dx".to_string()))]
+    #[case("1,2,2", DieSpecError::DuplicateFace(2))]
+    #[case("1:0,2:0", DieSpecError::AllWeightsZero)]
+    #[case("1:1,2", DieSpecError::Invalid("1:1,2".to_string()))]
+    #[case("d4294967295", DieSpecError::TooManyFaces)]
+    #[case("4000000000,4000000001", DieSpecError::FaceTooLarge(4000000000))]
+    #[case("1:4294967295,2:1", DieSpecError::WeightsTooLarge)]
+    fn test_die_spec_rejects_invalid_input(#[case] input: &str, #[case] expected: DieSpecError) {
+        assert_eq!(input.parse::<DieSpec>(), Err(expected));
+    }
+
+    #[rstest]
+    #[case("d6")]
+    #[case("d20")]
+    #[case("2,4,6,8")]
+    #[case("1:1,2:0,6:3")]
+    fn test_die_spec_display_round_trips(#[case] input: &str) {
+        let die_spec: DieSpec = input.parse().unwrap();
+        assert_eq!(die_spec.to_string(), input);
+        assert_eq!(die_spec.to_string().parse::<DieSpec>().unwrap(), die_spec);
+    }
+
+    #[test]
+    fn test_probabilities_follow_weights() {
+        assert_eq!(DieSpec::new(4).unwrap().probabilities(), vec![0.25; 4]);
+        let die_spec: DieSpec = "1:1,2:0,3:3".parse().unwrap();
+        assert_eq!(die_spec.probabilities(), vec![0.25, 0.0, 0.75]);
+    }
+
+    #[test]
+    fn test_with_weights_checks_length() {
+        assert_eq!(
+            DieSpec::default().with_weights(vec![1, 1]),
+            Err(DieSpecError::WeightCountMismatch {
+                expected: 6,
+                actual: 2
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("d8")]
+    #[case("d20")]
+    #[case("2,4,6,8")]
+    #[case("1:1,3:0,5:1")]
+    fn test_rolls_only_produce_possible_faces(#[case] input: &str) {
+        let die_spec: DieSpec = input.parse().unwrap();
+        let allowed_values: HashSet<u32> = die_spec
+            .faces()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| die_spec.weights().is_none_or(|w| w[*index] > 0))
+            .map(|(_, face)| *face)
+            .collect();
+        let dice = Dice::roll_dice(&die_spec, 1000);
+        assert_eq!(dice.0.len(), 1000);
+        assert!(dice.0.iter().all(|value| allowed_values.contains(value)));
+    }
+
+    #[test]
+    fn test_seeded_rolls_repeat() {
+        let die_spec = DieSpec::default();
+        let roll = |seed| SeededRoller::new(seed).roll(&die_spec, 20);
+        assert_eq!(roll(7), roll(7));
+        assert_ne!(roll(7), roll(8));
     }
 }
@@ -0,0 +1,450 @@
+use crate::dice::{DieSpec, Roll};
+use crate::storage::{append_json_line, read_json_lines, JsonLines};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub(crate) const DICE_HISTORY_FILE: &str = "dice_history.jsonl";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RollRecord {
+    pub die: String,
+    #[serde(flatten)]
+    pub roll: Roll,
+}
+
+/// Every roll ever made, stored as one JSON line per roll so that appending stays cheap.
+pub(crate) struct DiceHistory {
+    path: PathBuf,
+}
+
+impl DiceHistory {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn record(&self, die_spec: &DieSpec, roll: &Roll) -> io::Result<()> {
+        append_json_line(
+            &self.path,
+            &RollRecord {
+                die: die_spec.to_string(),
+                roll: roll.clone(),
+            },
+        )
+    }
+
+    /// All recorded rolls made with the given die, oldest first, and how many lines of the
+    /// history couldn't be read.
+    pub fn rolls(&self, die_spec: &DieSpec) -> io::Result<JsonLines<Roll>> {
+        let die = die_spec.to_string();
+        let records = read_json_lines::<RollRecord>(&self.path)?;
+        Ok(JsonLines {
+            values: records
+                .values
+                .into_iter()
+                .filter(|record| record.die == die)
+                .map(|record| record.roll)
+                .collect(),
+            skipped: records.skipped,
+        })
+    }
+
+    pub fn report(&self, die_spec: &DieSpec) -> io::Result<DiceReport> {
+        let rolls = self.rolls(die_spec)?;
+        Ok(DiceReport {
+            skipped_lines: rolls.skipped,
+            ..DiceReport::new(die_spec, &rolls.values)
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct FaceStats {
+    pub face: u32,
+    pub count: u64,
+    pub expected_probability: f64,
+    pub longest_streak: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct YahtzeeStats {
+    pub roll_number: usize,
+    pub rolls: u64,
+    pub yahtzees: u64,
+    pub expected_yahtzees: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct DiceReport {
+    pub die: String,
+    pub total_dice: u64,
+    pub faces: Vec<FaceStats>,
+    pub chi_square: f64,
+    pub degrees_of_freedom: usize,
+    pub p_value: f64,
+    pub yahtzees: Vec<YahtzeeStats>,
+    /// Lines of the history that couldn't be read and were left out.
+    pub skipped_lines: usize,
+}
+
+impl DiceReport {
+    pub fn new(die_spec: &DieSpec, rolls: &[Roll]) -> Self {
+        let probabilities = die_spec.probabilities();
+        let mut faces: Vec<FaceStats> = die_spec
+            .faces()
+            .iter()
+            .zip(&probabilities)
+            .map(|(face, probability)| FaceStats {
+                face: *face,
+                count: 0,
+                expected_probability: *probability,
+                longest_streak: 0,
+            })
+            .collect();
+
+        // Streaks run over the dice in the order they were thrown, across turns and sessions
+        let mut streak: Option<(u32, u64)> = None;
+        for value in rolls.iter().flat_map(|roll| roll.rolled.iter().copied()) {
+            let length = match streak {
+                Some((face, length)) if face == value => length + 1,
+                _ => 1,
+            };
+            streak = Some((value, length));
+            if let Some(stats) = faces.iter_mut().find(|stats| stats.face == value) {
+                stats.count += 1;
+                stats.longest_streak = stats.longest_streak.max(length);
+            }
+        }
+
+        let total_dice = faces.iter().map(|stats| stats.count).sum();
+        let (chi_square, degrees_of_freedom) = chi_square_statistic(&faces, total_dice);
+        let p_value = chi_square_p_value(chi_square, degrees_of_freedom);
+
+        let mut yahtzees: BTreeMap<usize, YahtzeeStats> = BTreeMap::new();
+        for roll in rolls {
+            let stats = yahtzees
+                .entry(roll.roll_number)
+                .or_insert_with(|| YahtzeeStats {
+                    roll_number: roll.roll_number,
+                    rolls: 0,
+                    yahtzees: 0,
+                    expected_yahtzees: 0.0,
+                });
+            stats.rolls += 1;
+            stats.expected_yahtzees += yahtzee_probability(die_spec, &probabilities, roll);
+            if is_yahtzee(roll) {
+                stats.yahtzees += 1;
+            }
+        }
+
+        Self {
+            die: die_spec.to_string(),
+            total_dice,
+            faces,
+            chi_square,
+            degrees_of_freedom,
+            p_value,
+            yahtzees: yahtzees.into_values().collect(),
+            skipped_lines: 0,
+        }
+    }
+}
+
+fn is_yahtzee(roll: &Roll) -> bool {
+    let mut values = roll.kept.iter().chain(&roll.rolled);
+    match values.next() {
+        Some(first) => values.all(|value| value == first),
+        None => false,
+    }
+}
+
+/// Probability that a roll completes a Yahtzee, given the dice that were kept.
+fn yahtzee_probability(die_spec: &DieSpec, probabilities: &[f64], roll: &Roll) -> f64 {
+    let rolled = roll.rolled.len() as i32;
+    match roll.kept.first() {
+        None => probabilities
+            .iter()
+            .map(|probability| probability.powi(rolled))
+            .sum(),
+        Some(first) if roll.kept.iter().all(|value| value == first) => die_spec
+            .faces()
+            .iter()
+            .position(|face| face == first)
+            .map_or(0.0, |index| probabilities[index].powi(rolled)),
+        Some(_) => 0.0,
+    }
+}
+
+/// Pearson's chi-square statistic of the observed face counts against the die's probabilities.
+/// Faces that can never come up don't contribute a degree of freedom.
+fn chi_square_statistic(faces: &[FaceStats], total_dice: u64) -> (f64, usize) {
+    let mut possible_faces = 0;
+    let mut chi_square = 0.0;
+    for stats in faces {
+        if stats.expected_probability > 0.0 {
+            possible_faces += 1;
+            let expected = stats.expected_probability * total_dice as f64;
+            if expected > 0.0 {
+                chi_square += (stats.count as f64 - expected).powi(2) / expected;
+            }
+        } else if stats.count > 0 {
+            return (f64::INFINITY, possible_faces.max(1) - 1);
+        }
+    }
+    (chi_square, possible_faces.max(1) - 1)
+}
+
+pub(crate) fn chi_square_p_value(chi_square: f64, degrees_of_freedom: usize) -> f64 {
+    if chi_square.is_infinite() {
+        return 0.0;
+    }
+    if degrees_of_freedom == 0 || chi_square <= 0.0 {
+        return 1.0;
+    }
+    regularized_upper_gamma(degrees_of_freedom as f64 / 2.0, chi_square / 2.0)
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    // Lanczos approximation, g = 7
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Q(a, x), using the series expansion for small `x` and a continued fraction otherwise.
+fn regularized_upper_gamma(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-14;
+    const MAX_ITERATIONS: usize = 1000;
+    let prefactor = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..MAX_ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        return (1.0 - sum * prefactor).clamp(0.0, 1.0);
+    }
+
+    // Modified Lentz's method
+    let tiny = f64::MIN_POSITIVE / EPSILON;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / tiny;
+    let mut d = 1.0 / b;
+    let mut fraction = d;
+    for i in 1..MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        fraction *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    (fraction * prefactor).clamp(0.0, 1.0)
+}
+
+impl fmt::Display for DiceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Die: {}    Dice rolled: {}", self.die, self.total_dice)?;
+        if self.skipped_lines > 0 {
+            writeln!(
+                f,
+                "Skipped {} unreadable lines of the history",
+                self.skipped_lines
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>6} {:>10} {:>10} {:>10} {:>8}",
+            "Face", "Count", "Observed", "Expected", "Streak"
+        )?;
+        for stats in &self.faces {
+            let observed = if self.total_dice > 0 {
+                stats.count as f64 / self.total_dice as f64
+            } else {
+                0.0
+            };
+            writeln!(
+                f,
+                "{:>6} {:>10} {:>9.2}% {:>9.2}% {:>8}",
+                stats.face,
+                stats.count,
+                observed * 100.0,
+                stats.expected_probability * 100.0,
+                stats.longest_streak
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Chi-square: {:.3} ({} degrees of freedom), p-value: {:.4}",
+            self.chi_square, self.degrees_of_freedom, self.p_value
+        )?;
+        if self.total_dice > 0 && self.p_value < 0.01 {
+            writeln!(f, "The rolls deviate significantly from a fair die.")?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>6} {:>10} {:>10} {:>10}",
+            "Roll", "Throws", "Yahtzees", "Expected"
+        )?;
+        for stats in &self.yahtzees {
+            writeln!(
+                f,
+                "{:>6} {:>10} {:>10} {:>10.4}",
+                stats.roll_number, stats.rolls, stats.yahtzees, stats.expected_yahtzees
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::temp_path;
+    use rstest::rstest;
+
+    fn roll(roll_number: usize, kept: Vec<u32>, rolled: Vec<u32>) -> Roll {
+        Roll {
+            roll_number,
+            kept,
+            rolled,
+        }
+    }
+
+    #[rstest]
+    #[case(3.841_458_820_694_124, 1, 0.05)]
+    #[case(11.070_497_693_516_351, 5, 0.05)]
+    #[case(15.086_272_469_388_987, 5, 0.01)]
+    #[case(1.0, 2, 0.606_530_659_712_633_4)]
+    #[case(0.0, 5, 1.0)]
+    #[case(4.0, 0, 1.0)]
+    fn test_chi_square_p_value(
+        #[case] chi_square: f64,
+        #[case] degrees_of_freedom: usize,
+        #[case] expected: f64,
+    ) {
+        let p_value = chi_square_p_value(chi_square, degrees_of_freedom);
+        assert!((p_value - expected).abs() < 1e-9, "{}", p_value);
+    }
+
+    #[test]
+    fn test_report_counts_faces_and_streaks() {
+        let rolls = vec![
+            roll(1, vec![], vec![1, 1, 2, 3, 4]),
+            roll(2, vec![1, 1], vec![4, 4, 6]),
+        ];
+        let report = DiceReport::new(&DieSpec::default(), &rolls);
+        let counts: Vec<u64> = report.faces.iter().map(|stats| stats.count).collect();
+        let streaks: Vec<u64> = report
+            .faces
+            .iter()
+            .map(|stats| stats.longest_streak)
+            .collect();
+        assert_eq!(report.total_dice, 8);
+        assert_eq!(counts, vec![2, 1, 1, 3, 0, 1]);
+        assert_eq!(streaks, vec![2, 1, 1, 3, 0, 1]);
+        assert_eq!(report.degrees_of_freedom, 5);
+    }
+
+    #[test]
+    fn test_report_uniform_counts_fit_perfectly() {
+        let rolls = vec![roll(1, vec![], vec![1, 2, 3, 4, 5, 6])];
+        let report = DiceReport::new(&DieSpec::default(), &rolls);
+        assert_eq!(report.chi_square, 0.0);
+        assert_eq!(report.p_value, 1.0);
+    }
+
+    #[test]
+    fn test_report_impossible_face_fails_fit() {
+        let die_spec: DieSpec = "1:1,2:0".parse().unwrap();
+        let report = DiceReport::new(&die_spec, &[roll(1, vec![], vec![1, 2])]);
+        assert_eq!(report.p_value, 0.0);
+    }
+
+    #[test]
+    fn test_report_yahtzees_per_roll_number() {
+        let rolls = vec![
+            roll(1, vec![], vec![3, 3, 3, 3, 3]),
+            roll(1, vec![], vec![1, 2, 3, 4, 5]),
+            roll(2, vec![2, 2, 2], vec![2, 2]),
+            roll(2, vec![1, 2], vec![5, 5, 5]),
+        ];
+        let report = DiceReport::new(&DieSpec::default(), &rolls);
+        assert_eq!(report.yahtzees.len(), 2);
+
+        let first = &report.yahtzees[0];
+        assert_eq!((first.roll_number, first.rolls, first.yahtzees), (1, 2, 1));
+        assert!((first.expected_yahtzees - 2.0 / 1296.0).abs() < 1e-12);
+
+        let second = &report.yahtzees[1];
+        assert_eq!(
+            (second.roll_number, second.rolls, second.yahtzees),
+            (2, 2, 1)
+        );
+        assert!((second.expected_yahtzees - 1.0 / 36.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_history_persists_rolls_per_die() {
+        let history = DiceHistory::new(temp_path(DICE_HISTORY_FILE));
+        let d6 = DieSpec::default();
+        let d8 = DieSpec::new(8).unwrap();
+        history.record(&d6, &roll(1, vec![], vec![1, 2])).unwrap();
+        history.record(&d8, &roll(1, vec![], vec![8, 7])).unwrap();
+        history.record(&d6, &roll(2, vec![1], vec![6])).unwrap();
+
+        assert_eq!(
+            history.rolls(&d6).unwrap().values,
+            vec![roll(1, vec![], vec![1, 2]), roll(2, vec![1], vec![6])]
+        );
+        assert_eq!(history.report(&d8).unwrap().total_dice, 2);
+
+        append_json_line(&history.path, &"half a rec").unwrap();
+        let report = history.report(&d8).unwrap();
+        assert_eq!((report.total_dice, report.skipped_lines), (2, 1));
+        assert!(report
+            .to_string()
+            .contains("Skipped 1 unreadable lines of the history"));
+        std::fs::remove_file(history.path).unwrap();
+    }
+}
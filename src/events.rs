@@ -0,0 +1,170 @@
+use crate::app::Event;
+use crossterm::event::{self, Event as CEvent, KeyEvent};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Handed to every event source so it can send events and find out when to stop.
+#[derive(Clone)]
+pub(crate) struct EventSource {
+    tx: Sender<Event<KeyEvent>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl EventSource {
+    /// Returns `false` once nobody is listening anymore, in which case the source should stop.
+    pub fn send(&self, event: Event<KeyEvent>) -> bool {
+        self.tx.send(event).is_ok()
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+}
+
+/// Multiplexes events from any number of source threads into a single stream. Dropping it stops
+/// the sources and waits for their threads to finish.
+pub(crate) struct Events {
+    rx: Receiver<Event<KeyEvent>>,
+    source: EventSource,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            rx,
+            source: EventSource {
+                tx,
+                shutdown: Arc::new(AtomicBool::new(false)),
+            },
+            threads: vec![],
+        }
+    }
+
+    /// Runs `source` on its own thread. It should return soon after `is_shut_down` turns true.
+    pub fn spawn<F>(&mut self, source: F)
+    where
+        F: FnOnce(EventSource) + Send + 'static,
+    {
+        let event_source = self.source.clone();
+        self.threads
+            .push(thread::spawn(move || source(event_source)));
+    }
+
+    /// Forwards key, mouse and resize events from the terminal. Waiting for input times out
+    /// every `poll_timeout` to check whether to stop. Errors reading the terminal end the source
+    /// and are passed on as `Event::Error`.
+    pub fn spawn_input(&mut self, poll_timeout: Duration) {
+        self.spawn(move |source| {
+            while !source.is_shut_down() {
+                let result = event::poll(poll_timeout).and_then(|ready| {
+                    if ready {
+                        event::read().map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                });
+                let event = match result {
+                    Ok(Some(CEvent::Key(key))) => Event::Input(key),
+                    Ok(Some(CEvent::Mouse(mouse))) => Event::Mouse(mouse),
+                    Ok(Some(CEvent::Resize(_, _))) => Event::Resize,
+                    Ok(None) => continue,
+                    Err(error) => {
+                        source.send(Event::Error(error));
+                        return;
+                    }
+                };
+                if !source.send(event) {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Sends an `Event::Tick` every `tick_rate`.
+    pub fn spawn_ticks(&mut self, tick_rate: Duration) {
+        self.spawn(move |source| {
+            while !source.is_shut_down() {
+                thread::sleep(tick_rate);
+                if !source.send(Event::Tick) {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Blocks until the next event from any source arrives.
+    pub fn next(&self) -> Result<Event<KeyEvent>, RecvError> {
+        self.rx.recv()
+    }
+
+    /// Stops all sources and waits for their threads to finish.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.source.shutdown.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            // A source that panicked has nothing left to clean up
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::time::Instant;
+
+    #[test]
+    fn test_events_from_all_sources_are_multiplexed() {
+        let mut events = Events::new();
+        events.spawn_ticks(Duration::from_millis(5));
+        events.spawn(|source| {
+            source.send(Event::Error(io::Error::other("boom")));
+        });
+
+        let mut ticks = 0;
+        let mut errors = 0;
+        while ticks < 2 || errors < 1 {
+            match events.next().unwrap() {
+                Event::Tick => ticks += 1,
+                Event::Error(error) => {
+                    assert_eq!(error.to_string(), "boom");
+                    errors += 1;
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_shutdown_stops_and_joins_sources() {
+        let mut events = Events::new();
+        let (done_tx, done_rx) = mpsc::channel();
+        events.spawn(move |source| {
+            while !source.is_shut_down() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            done_tx.send(()).unwrap();
+        });
+        events.spawn_ticks(Duration::from_millis(1));
+
+        let start = Instant::now();
+        events.shutdown();
+        assert!(done_rx.try_recv().is_ok());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
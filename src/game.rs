@@ -0,0 +1,635 @@
+use crate::dice::{DiceRoller, DiceState, DiceStateError, DieSpec};
+use crate::score::{Category, Score, ScoreError};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Something that happened in a game. Every change to a game is recorded as one, in order, so
+/// the events are all it takes to follow a game from elsewhere.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum GameEvent {
+    PlayerJoined {
+        seat: usize,
+        name: String,
+    },
+    PlayerLeft {
+        seat: usize,
+    },
+    Ready {
+        seat: usize,
+    },
+    TurnStarted {
+        seat: usize,
+    },
+    /// Which dice `seat` keeps for the next roll.
+    Held {
+        seat: usize,
+        held: Vec<bool>,
+    },
+    /// All dice after a roll, and the ids of the ones that were rolled.
+    Rolled {
+        seat: usize,
+        dice: Vec<u32>,
+        rolled: Vec<usize>,
+        rolls_left: usize,
+    },
+    /// `total` is the seat's score so far, bonus included.
+    Scored {
+        seat: usize,
+        category: Category,
+        points: u32,
+        total: u32,
+    },
+    /// The final total of every seat.
+    GameOver {
+        scores: Vec<u32>,
+    },
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub(crate) enum GameError {
+    #[error("all {0} seats are taken")]
+    Full(usize),
+    #[error("waiting for every seat to be taken and everyone to be ready")]
+    NotStarted,
+    #[error("the game has already started")]
+    Started,
+    #[error("seat {0} isn't free to rejoin")]
+    NotAbsent(usize),
+    #[error("the game is over")]
+    Over,
+    #[error("it's not your turn")]
+    NotYourTurn,
+    #[error("expected {expected} dice to hold, got {actual}")]
+    WrongHoldCount { expected: usize, actual: usize },
+    #[error("all dice are held, there is nothing to roll")]
+    NothingToRoll,
+    #[error("roll the dice first")]
+    NotRolled,
+    #[error(transparent)]
+    Dice(#[from] DiceStateError),
+    #[error("{} has already been scored", .0.name())]
+    AlreadyScored(Category),
+    #[error("the Joker rules don't allow scoring this Yahtzee as {}", .0.name())]
+    JokerRule(Category),
+}
+
+impl GameError {
+    pub fn from_score(error: ScoreError, category: Category) -> Self {
+        match error {
+            ScoreError::AlreadySet => GameError::AlreadyScored(category),
+            ScoreError::JokerRule => GameError::JokerRule(category),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Player {
+    pub name: String,
+    pub score: Score,
+    /// Whether the player is still at the table. The turns of players who left are skipped.
+    pub present: bool,
+    pub ready: bool,
+}
+
+/// Everything needed to show a game in progress.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct GameSnapshot {
+    pub die_spec: DieSpec,
+    pub number_of_seats: usize,
+    pub players: Vec<Player>,
+    pub current: Option<usize>,
+    pub dice: Vec<u32>,
+    pub held: Vec<bool>,
+    pub rolls_left: usize,
+    pub over: bool,
+    /// Every seat's score so far, bonus included.
+    pub totals: Vec<u32>,
+}
+
+/// A game for a fixed number of seats that starts once they are all taken and everyone is ready.
+/// Players take turns in seat order, and the game is over once everyone still at the table filled
+/// their scorecard. It pauses while nobody is at the table.
+pub(crate) struct Game {
+    number_of_seats: usize,
+    players: Vec<Player>,
+    dice_state: DiceState,
+    /// Every seat's own roller, if the seats don't share one. The roller of the seat whose turn
+    /// it is sits in `dice_state` meanwhile, with the shared one in its place.
+    seat_rollers: Vec<Box<dyn DiceRoller>>,
+    rolling_seat: Option<usize>,
+    held: Vec<bool>,
+    current: Option<usize>,
+    started: bool,
+    over: bool,
+    events: Vec<GameEvent>,
+}
+
+impl Game {
+    pub fn new(die_spec: DieSpec, number_of_seats: usize) -> Self {
+        Self {
+            number_of_seats,
+            players: vec![],
+            dice_state: DiceState::new(die_spec),
+            seat_rollers: vec![],
+            rolling_seat: None,
+            held: vec![],
+            current: None,
+            started: false,
+            over: false,
+            events: vec![],
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_roller(mut self, roller: Box<dyn DiceRoller>) -> Self {
+        self.dice_state = self.dice_state.with_roller(roller);
+        self
+    }
+
+    /// Gives every seat dice of its own, e.g. rolling the same faces for everyone so that only
+    /// the choices made with them differ.
+    pub fn with_roller_per_seat<F>(mut self, roller: F) -> Self
+    where
+        F: FnMut(usize) -> Box<dyn DiceRoller>,
+    {
+        self.seat_rollers = (0..self.number_of_seats).map(roller).collect();
+        self
+    }
+
+    /// Every event so far, oldest first.
+    pub fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            die_spec: self.dice_state.die_spec.clone(),
+            number_of_seats: self.number_of_seats,
+            players: self.players.clone(),
+            current: self.current,
+            dice: self.dice_state.dice.0.clone(),
+            held: self.held.clone(),
+            rolls_left: self.rolls_left(),
+            over: self.over,
+            totals: self
+                .players
+                .iter()
+                .map(|player| player.score.total_score())
+                .collect(),
+        }
+    }
+
+    pub fn number_of_seats(&self) -> usize {
+        self.number_of_seats
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Seats `name` and returns the seat. Until the game starts, a seat someone left can be taken
+    /// by anyone. After that it stays reserved for a `rejoin`.
+    pub fn join(&mut self, name: &str) -> Result<usize, GameError> {
+        if self.over {
+            return Err(GameError::Over);
+        }
+        if self.started {
+            return Err(GameError::Started);
+        }
+        let seat = match self.players.iter().position(|player| !player.present) {
+            Some(seat) => {
+                self.players[seat].name = name.to_string();
+                self.players[seat].present = true;
+                seat
+            }
+            None if self.players.len() < self.number_of_seats => {
+                self.players.push(Player {
+                    name: name.to_string(),
+                    score: Score::new(),
+                    present: true,
+                    ready: false,
+                });
+                self.players.len() - 1
+            }
+            None => return Err(GameError::Full(self.number_of_seats)),
+        };
+        self.events.push(GameEvent::PlayerJoined {
+            seat,
+            name: name.to_string(),
+        });
+        Ok(seat)
+    }
+
+    /// Takes back a seat that was left during the game, scorecard and all.
+    pub fn rejoin(&mut self, seat: usize) -> Result<(), GameError> {
+        let player = match self.players.get_mut(seat) {
+            Some(player) if !player.present => player,
+            _ => return Err(GameError::NotAbsent(seat)),
+        };
+        player.present = true;
+        self.events.push(GameEvent::PlayerJoined {
+            seat,
+            name: player.name.clone(),
+        });
+        // Resumes a game everyone had left
+        if self.started && !self.over && self.current.is_none() {
+            if self.players[seat].score.is_complete() {
+                self.next_turn();
+            } else {
+                self.start_turn(seat);
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks `seat` as ready, starting the game once all seats are taken by players who are.
+    pub fn ready(&mut self, seat: usize) -> Result<(), GameError> {
+        if self.started {
+            return Err(GameError::Started);
+        }
+        match self.players.get_mut(seat) {
+            Some(player) if !player.ready => player.ready = true,
+            _ => return Ok(()),
+        }
+        self.events.push(GameEvent::Ready { seat });
+        if self.players.len() == self.number_of_seats
+            && self
+                .players
+                .iter()
+                .all(|player| player.present && player.ready)
+        {
+            self.started = true;
+            self.start_turn(0);
+        }
+        Ok(())
+    }
+
+    pub fn leave(&mut self, seat: usize) {
+        match self.players.get_mut(seat) {
+            Some(player) if player.present => {
+                player.present = false;
+                player.ready &= self.started;
+            }
+            _ => return,
+        }
+        self.events.push(GameEvent::PlayerLeft { seat });
+        if self.current == Some(seat) && !self.over {
+            self.next_turn();
+        }
+    }
+
+    pub fn hold(&mut self, seat: usize, held: Vec<bool>) -> Result<(), GameError> {
+        self.check_turn(seat)?;
+        if self.dice_state.number_of_rolls == 0 {
+            return Err(GameError::NotRolled);
+        }
+        if held.len() != self.dice_state.number_of_dice {
+            return Err(GameError::WrongHoldCount {
+                expected: self.dice_state.number_of_dice,
+                actual: held.len(),
+            });
+        }
+        self.held = held.clone();
+        self.events.push(GameEvent::Held { seat, held });
+        Ok(())
+    }
+
+    /// Rolls every die that isn't held, which is all of them for the first roll of a turn.
+    pub fn roll(&mut self, seat: usize) -> Result<(), GameError> {
+        self.check_turn(seat)?;
+        let rolled: Vec<usize> = (0..self.dice_state.number_of_dice)
+            .filter(|id| !self.held.get(*id).copied().unwrap_or(false))
+            .collect();
+        if self.dice_state.number_of_rolls == 0 {
+            self.dice_state.roll_all_dice()?;
+        } else if rolled.is_empty() {
+            return Err(GameError::NothingToRoll);
+        } else {
+            self.dice_state.reroll_selected_dice(rolled.clone())?;
+        }
+        self.events.push(GameEvent::Rolled {
+            seat,
+            dice: self.dice_state.dice.0.clone(),
+            rolled,
+            rolls_left: self.rolls_left(),
+        });
+        Ok(())
+    }
+
+    pub fn score(&mut self, seat: usize, category: Category) -> Result<(), GameError> {
+        self.check_turn(seat)?;
+        if self.dice_state.number_of_rolls == 0 {
+            return Err(GameError::NotRolled);
+        }
+        let dice = &self.dice_state.dice;
+        let die_spec = &self.dice_state.die_spec;
+        let score = &mut self.players[seat].score;
+        score
+            .set(category, dice, die_spec)
+            .map_err(|error| GameError::from_score(error, category))?;
+        let points = score.get(category).unwrap_or_default();
+        let total = score.total_score();
+        self.events.push(GameEvent::Scored {
+            seat,
+            category,
+            points,
+            total,
+        });
+        self.next_turn();
+        Ok(())
+    }
+
+    fn rolls_left(&self) -> usize {
+        self.dice_state
+            .max_number_of_rolls
+            .saturating_sub(self.dice_state.number_of_rolls)
+    }
+
+    fn check_turn(&self, seat: usize) -> Result<(), GameError> {
+        if self.over {
+            return Err(GameError::Over);
+        }
+        match self.current {
+            None => Err(GameError::NotStarted),
+            Some(current) if current != seat => Err(GameError::NotYourTurn),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn start_turn(&mut self, seat: usize) {
+        if let Some(previous) = self.rolling_seat.take() {
+            self.dice_state
+                .swap_roller(&mut self.seat_rollers[previous]);
+        }
+        if let Some(roller) = self.seat_rollers.get_mut(seat) {
+            self.dice_state.swap_roller(roller);
+            self.rolling_seat = Some(seat);
+        }
+        self.current = Some(seat);
+        self.dice_state.reset();
+        self.held = vec![false; self.dice_state.number_of_dice];
+        self.events.push(GameEvent::TurnStarted { seat });
+    }
+
+    /// Passes the dice on to the next player at the table who still has categories to fill.
+    fn next_turn(&mut self) {
+        if self.players.iter().all(|player| !player.present) {
+            self.current = None;
+            self.dice_state.reset();
+            return;
+        }
+        let current = self.current.unwrap_or(0);
+        let number_of_players = self.players.len();
+        let next = (1..=number_of_players)
+            .map(|offset| (current + offset) % number_of_players)
+            .find(|seat| {
+                let player = &self.players[*seat];
+                player.present && !player.score.is_complete()
+            });
+        match next {
+            Some(seat) => self.start_turn(seat),
+            None => {
+                self.over = true;
+                self.current = None;
+                self.dice_state.reset();
+                self.events.push(GameEvent::GameOver {
+                    scores: self
+                        .players
+                        .iter()
+                        .map(|player| player.score.total_score())
+                        .collect(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::ScriptedRoller;
+
+    fn game(seats: usize, faces: Vec<u32>) -> Game {
+        Game::new(DieSpec::default(), seats).with_roller(Box::new(ScriptedRoller::new(faces)))
+    }
+
+    /// A started game with everyone at the table.
+    fn started_game(names: &[&str], faces: Vec<u32>) -> Game {
+        let mut game = game(names.len(), faces);
+        for name in names {
+            game.join(name).unwrap();
+        }
+        for seat in 0..names.len() {
+            game.ready(seat).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn test_game_starts_once_all_seats_are_taken_and_ready() {
+        let mut game = game(2, vec![]);
+        assert_eq!(game.join("ada"), Ok(0));
+        game.ready(0).unwrap();
+        assert_eq!(game.roll(0), Err(GameError::NotStarted));
+        assert_eq!(game.join("bob"), Ok(1));
+        assert_eq!(game.join("cy"), Err(GameError::Full(2)));
+        assert_eq!(game.roll(0), Err(GameError::NotStarted));
+        game.ready(1).unwrap();
+        assert_eq!(game.join("cy"), Err(GameError::Started));
+        assert_eq!(game.ready(1), Err(GameError::Started));
+        assert_eq!(
+            game.events(),
+            &[
+                GameEvent::PlayerJoined {
+                    seat: 0,
+                    name: "ada".to_string()
+                },
+                GameEvent::Ready { seat: 0 },
+                GameEvent::PlayerJoined {
+                    seat: 1,
+                    name: "bob".to_string()
+                },
+                GameEvent::Ready { seat: 1 },
+                GameEvent::TurnStarted { seat: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_seats_left_before_the_start_are_free_again() {
+        let mut game = game(2, vec![]);
+        game.join("ada").unwrap();
+        game.ready(0).unwrap();
+        game.leave(0);
+        assert_eq!(game.join("bob"), Ok(0));
+        assert!(!game.players()[0].ready);
+        game.join("cy").unwrap();
+        game.ready(1).unwrap();
+        assert_eq!(game.snapshot().current, None);
+        game.ready(0).unwrap();
+        assert_eq!(game.snapshot().current, Some(0));
+    }
+
+    #[test]
+    fn test_turns_follow_the_seats() {
+        let mut game = started_game(&["ada", "bob"], vec![1, 2, 3, 4, 5, 6, 6, 6, 6, 6, 1, 2, 2]);
+        assert_eq!(game.roll(1), Err(GameError::NotYourTurn));
+        assert_eq!(game.hold(0, vec![false; 5]), Err(GameError::NotRolled));
+
+        game.roll(0).unwrap();
+        game.hold(0, vec![true, true, true, true, false]).unwrap();
+        game.roll(0).unwrap();
+        assert_eq!(game.snapshot().dice, vec![1, 2, 3, 4, 6]);
+        game.score(0, Category::LargeStraight).unwrap();
+        assert_eq!(
+            game.players()[0].score.get(Category::LargeStraight),
+            Some(0)
+        );
+
+        assert_eq!(game.roll(0), Err(GameError::NotYourTurn));
+        game.roll(1).unwrap();
+        game.hold(1, vec![true, true, true, false, false]).unwrap();
+        game.roll(1).unwrap();
+        assert_eq!(
+            game.events().last(),
+            Some(&GameEvent::Rolled {
+                seat: 1,
+                dice: vec![6, 6, 6, 2, 2],
+                rolled: vec![3, 4],
+                rolls_left: 1,
+            })
+        );
+        game.score(1, Category::FullHouse).unwrap();
+        assert_eq!(game.players()[1].score.get(Category::FullHouse), Some(25));
+        assert_eq!(game.snapshot().current, Some(0));
+    }
+
+    #[test]
+    fn test_seats_can_roll_the_same_dice() {
+        let mut game = Game::new(DieSpec::default(), 2)
+            .with_roller_per_seat(|_| Box::new(ScriptedRoller::new(vec![3, 3, 3, 1, 2, 3, 3])));
+        for name in ["ada", "bob"] {
+            game.join(name).unwrap();
+        }
+        for seat in 0..2 {
+            game.ready(seat).unwrap();
+        }
+        game.roll(0).unwrap();
+        game.hold(0, vec![true, true, true, false, false]).unwrap();
+        game.roll(0).unwrap();
+        assert_eq!(game.snapshot().dice, vec![3; 5]);
+        game.score(0, Category::Yahtzee).unwrap();
+
+        // Bob's dice start over where Ada's did, however many Ada rolled
+        game.roll(1).unwrap();
+        assert_eq!(game.snapshot().dice, vec![3, 3, 3, 1, 2]);
+        game.score(1, Category::Threes).unwrap();
+        assert_eq!(game.snapshot().current, Some(0));
+    }
+
+    #[test]
+    fn test_invalid_moves_are_rejected() {
+        let mut game = started_game(&["ada"], vec![1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3]);
+        assert_eq!(game.score(0, Category::Chance), Err(GameError::NotRolled));
+        game.roll(0).unwrap();
+        assert_eq!(
+            game.hold(0, vec![true]),
+            Err(GameError::WrongHoldCount {
+                expected: 5,
+                actual: 1
+            })
+        );
+        game.hold(0, vec![true; 5]).unwrap();
+        assert_eq!(game.roll(0), Err(GameError::NothingToRoll));
+        game.hold(0, vec![false; 5]).unwrap();
+        game.roll(0).unwrap();
+        game.roll(0).unwrap();
+        assert_eq!(
+            game.roll(0),
+            Err(GameError::Dice(DiceStateError::MaxRollsReached))
+        );
+        game.score(0, Category::Threes).unwrap();
+        assert_eq!(game.snapshot().rolls_left, 3);
+    }
+
+    #[test]
+    fn test_game_is_over_once_every_scorecard_is_full() {
+        let mut game = started_game(&["ada", "bob"], vec![2; 5 * 26]);
+        for category in Category::ALL {
+            for seat in 0..2 {
+                game.roll(seat).unwrap();
+                game.score(seat, category).unwrap();
+            }
+        }
+        assert!(game.snapshot().over);
+        let total = game.players()[0].score.total_score();
+        assert_eq!(
+            game.events().last(),
+            Some(&GameEvent::GameOver {
+                scores: vec![total, total]
+            })
+        );
+        assert_eq!(game.roll(0), Err(GameError::Over));
+    }
+
+    #[test]
+    fn test_seats_left_are_skipped_until_rejoined() {
+        let mut game = started_game(&["ada", "bob", "cy"], vec![3; 25]);
+        game.roll(0).unwrap();
+        game.score(0, Category::Threes).unwrap();
+
+        // Leaving during your turn passes the dice on
+        game.leave(1);
+        assert_eq!(game.snapshot().current, Some(2));
+        game.roll(2).unwrap();
+        game.score(2, Category::Threes).unwrap();
+        assert_eq!(game.snapshot().current, Some(0));
+
+        assert_eq!(game.join("bea"), Err(GameError::Started));
+        game.rejoin(1).unwrap();
+        assert_eq!(game.rejoin(1), Err(GameError::NotAbsent(1)));
+        let snapshot = game.snapshot();
+        assert_eq!(snapshot.players[1].name, "bob");
+        assert!(snapshot.players[1].present);
+        game.roll(0).unwrap();
+        game.score(0, Category::Chance).unwrap();
+        assert_eq!(game.snapshot().current, Some(1));
+    }
+
+    #[test]
+    fn test_game_pauses_while_everyone_is_away() {
+        let mut game = started_game(&["ada", "bob"], vec![4; 5]);
+        game.leave(1);
+        game.leave(0);
+        assert_eq!(game.snapshot().current, None);
+        assert!(!game.snapshot().over);
+
+        game.rejoin(1).unwrap();
+        assert_eq!(game.snapshot().current, Some(1));
+        game.roll(1).unwrap();
+        game.score(1, Category::Fours).unwrap();
+        // Ada is still away, so it's Bob's turn again
+        assert_eq!(game.snapshot().current, Some(1));
+    }
+
+    #[test]
+    fn test_events_serialize_with_their_name() {
+        let event = GameEvent::Scored {
+            seat: 1,
+            category: Category::FullHouse,
+            points: 25,
+            total: 120,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"event":"scored","seat":1,"category":"FullHouse","points":25,"total":120}"#
+        );
+        assert_eq!(serde_json::from_str::<GameEvent>(&json).unwrap(), event);
+    }
+}
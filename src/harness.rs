@@ -0,0 +1,246 @@
+//! Plays the game from tests: key presses and clicks go through the same handlers as in the
+//! terminal, the dice come up as scripted, and every step is rendered into a `TestBackend` whose
+//! screen can be compared against a snapshot in `src/snapshots`.
+
+use crate::app::{AppState, Flow};
+use crate::dice::{DieSpec, ScriptedRoller};
+use crate::ui::{render_app, UiRegions};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fs;
+use std::path::PathBuf;
+use tui::backend::TestBackend;
+use tui::Terminal;
+
+const WIDTH: u16 = 80;
+const HEIGHT: u16 = 40;
+
+pub(crate) struct Harness {
+    pub app_state: AppState,
+    terminal: Terminal<TestBackend>,
+    regions: UiRegions,
+    quit: bool,
+}
+
+impl Harness {
+    /// Starts a game on a standard die in which the dice come up as `faces`, in order.
+    pub fn new<I: IntoIterator<Item = u32>>(faces: I) -> Self {
+        let app_state =
+            AppState::new(DieSpec::default()).with_roller(Box::new(ScriptedRoller::new(faces)));
+        Self::with_app_state(app_state, WIDTH, HEIGHT)
+    }
+
+    pub fn with_app_state(app_state: AppState, width: u16, height: u16) -> Self {
+        let mut harness = Self {
+            app_state,
+            terminal: Terminal::new(TestBackend::new(width, height)).unwrap(),
+            regions: UiRegions::default(),
+            quit: false,
+        };
+        harness.draw();
+        harness
+    }
+
+    pub fn press(&mut self, code: KeyCode) -> &mut Self {
+        assert!(!self.quit, "the game has already quit");
+        let flow = self
+            .app_state
+            .handle_key(&KeyEvent::new(code, KeyModifiers::NONE));
+        self.settle(flow)
+    }
+
+    /// Presses every character of `keys` in turn.
+    pub fn keys(&mut self, keys: &str) -> &mut Self {
+        for c in keys.chars() {
+            self.press(KeyCode::Char(c));
+        }
+        self
+    }
+
+    /// Clicks the cell at `column`, `row` of the last rendered screen.
+    pub fn click(&mut self, column: u16, row: u16) -> &mut Self {
+        assert!(!self.quit, "the game has already quit");
+        let flow = match self.regions.target_at(column, row) {
+            Some(target) => self.app_state.handle_click(target),
+            None => Flow::Continue,
+        };
+        self.settle(flow)
+    }
+
+    pub fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// The rendered screen, one line per row without trailing spaces.
+    pub fn screen(&self) -> String {
+        let buffer = self.terminal.backend().buffer();
+        let area = buffer.area;
+        let mut screen = String::new();
+        for y in 0..area.height {
+            let line: String = (0..area.width)
+                .map(|x| buffer.get(x, y).symbol.as_str())
+                .collect();
+            screen.push_str(line.trim_end());
+            screen.push('\n');
+        }
+        screen
+    }
+
+    /// Compares the screen with `src/snapshots/<name>.txt`. Snapshots are only ever written when
+    /// `UPDATE_SNAPSHOTS` is set, so a missing one fails like a wrong one.
+    pub fn assert_snapshot(&self, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src")
+            .join("snapshots")
+            .join(format!("{}.txt", name));
+        let screen = self.screen();
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &screen).unwrap();
+            return;
+        }
+        if !path.exists() {
+            panic!(
+                "snapshot {} is missing, run the tests with UPDATE_SNAPSHOTS=1 to write it\n\nactual:\n{}",
+                path.display(),
+                screen
+            );
+        }
+        let expected = fs::read_to_string(&path).unwrap();
+        assert!(
+            screen == expected,
+            "screen does not match snapshot {}\n\nexpected:\n{}\nactual:\n{}",
+            path.display(),
+            expected,
+            screen
+        );
+    }
+
+    /// Lets a roll animation run to its end, so every step is rendered with the final dice.
+    fn settle(&mut self, flow: Flow) -> &mut Self {
+        if flow == Flow::Quit {
+            self.quit = true;
+            return self;
+        }
+        while self.app_state.animation.is_some() {
+            self.app_state.on_tick();
+        }
+        self.draw();
+        self
+    }
+
+    fn draw(&mut self) {
+        let app_state = &self.app_state;
+        let regions = &mut self.regions;
+        self.terminal
+            .draw(|rect| *regions = render_app(app_state, rect))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::InputMode;
+    use crate::score::Category;
+
+    #[test]
+    fn test_roll_reroll_and_score() {
+        let mut harness = Harness::new([1, 3, 3, 5, 6, 3, 3, 3]);
+        harness.keys("r");
+        harness.assert_snapshot("first_roll");
+
+        harness.keys("e1,4,5").press(KeyCode::Enter);
+        assert_eq!(harness.app_state.dice_state.dice.0, vec![3, 3, 3, 3, 3]);
+        harness.assert_snapshot("rerolled_to_yahtzee");
+
+        // Selecting stays on for further rerolls until it's left
+        harness.press(KeyCode::Esc).keys("y");
+        assert_eq!(harness.app_state.score.get(Category::Yahtzee), Some(50));
+        harness.assert_snapshot("scored_yahtzee");
+    }
+
+    #[test]
+    fn test_scratching_a_category_asks_for_confirmation() {
+        let mut harness = Harness::new([1, 2, 3, 4, 6]);
+        harness.keys("ry");
+        assert_eq!(harness.app_state.input_mode, InputMode::Confirming);
+        harness.assert_snapshot("confirm_scratch");
+
+        harness.keys("n");
+        assert_eq!(harness.app_state.score.get(Category::Yahtzee), None);
+        harness.keys("yy");
+        assert_eq!(harness.app_state.score.get(Category::Yahtzee), Some(0));
+    }
+
+    #[test]
+    fn test_rolling_too_often_is_reported() {
+        let mut harness = Harness::new([1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3]);
+        harness.keys("rrrr");
+        assert_eq!(harness.app_state.dice_state.dice.0, vec![3, 3, 3, 3, 3]);
+        assert!(harness
+            .screen()
+            .contains("Error: no rolls left this turn, pick a category to score"));
+    }
+
+    #[test]
+    fn test_playing_a_whole_game() {
+        // One roll per turn, each scored in the category it fits best
+        let turns: [(&str, [u32; 5]); 13] = [
+            ("1", [1, 1, 1, 2, 3]),
+            ("2", [2, 2, 2, 4, 5]),
+            ("3", [3, 3, 3, 1, 2]),
+            ("4", [4, 4, 4, 1, 2]),
+            ("5", [5, 5, 5, 1, 2]),
+            ("6", [6, 6, 6, 1, 2]),
+            ("t", [6, 6, 6, 5, 4]),
+            ("f", [5, 5, 5, 5, 1]),
+            ("h", [2, 2, 3, 3, 3]),
+            ("s", [1, 2, 3, 4, 6]),
+            ("l", [2, 3, 4, 5, 6]),
+            ("y", [4, 4, 4, 4, 4]),
+            ("c", [6, 6, 5, 5, 4]),
+        ];
+        let mut harness = Harness::new(turns.iter().flat_map(|(_, faces)| *faces));
+        for (key, _) in turns {
+            harness.keys("r").keys(key);
+        }
+
+        assert!(harness.app_state.score.is_complete());
+        // 3 + 6 + 9 + 12 + 15 + 18 = 63 earns the upper bonus of 35
+        assert_eq!(
+            harness.app_state.score.total_score(),
+            63 + 35 + 27 + 21 + 25 + 30 + 40 + 50 + 26
+        );
+        harness.assert_snapshot("game_over");
+
+        harness.keys("n");
+        assert!(!harness.app_state.score.is_complete());
+        harness.keys("q");
+        assert!(harness.has_quit());
+    }
+
+    #[test]
+    fn test_odds_panel_follows_the_held_dice() {
+        let mut harness = Harness::new([3, 3, 5, 1, 6]);
+        harness.keys("o");
+        harness.assert_snapshot("odds_before_rolling");
+
+        // Keeping 3-3-5 for the last two rolls
+        harness.keys("r").keys("e4,5");
+        harness.assert_snapshot("odds_keeping_dice");
+        harness.press(KeyCode::Esc).keys("o");
+        assert!(!harness.screen().contains("Odds"));
+    }
+
+    #[test]
+    fn test_clicking_the_menu_and_dice() {
+        let mut harness = Harness::new([1, 2, 3, 4, 5, 6]);
+        // "r Roll dice" in the menu, then the first die
+        harness.click(17, 3).click(3, 6);
+        assert_eq!(harness.app_state.dice_selected_for_reroll(), vec![0]);
+        harness.click(17, 3);
+        assert_eq!(harness.app_state.dice_state.dice.0, vec![6, 2, 3, 4, 5]);
+        harness.click(70, 3);
+        assert!(harness.has_quit());
+    }
+}
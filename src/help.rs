@@ -0,0 +1,118 @@
+use crate::dice::DiceState;
+use crate::keymap::Keymap;
+use crate::score::{Category, UPPER_BONUS, UPPER_BONUS_THRESHOLD, YAHTZEE_BONUS};
+use crate::selection::SYNTAX;
+
+/// A line of the help overlay.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum HelpLine {
+    Heading(String),
+    /// A label, e.g. a category or key, and its explanation.
+    Entry(String, String),
+    Text(String),
+    Blank,
+}
+
+/// Builds the help text from the rules and key bindings actually in use, so it stays correct for
+/// custom dice and remapped keys.
+pub(crate) fn help_lines(dice_state: &DiceState, keymap: &Keymap) -> Vec<HelpLine> {
+    let die_spec = &dice_state.die_spec;
+    let category_entry =
+        |category: &Category| HelpLine::Entry(category.name().to_string(), category.rule(die_spec));
+    let (upper, lower) = Category::ALL.split_at(6);
+
+    let mut lines = vec![
+        HelpLine::Heading("Turns".to_string()),
+        HelpLine::Text(format!(
+            "Roll {} {} dice up to {} times, keeping any of them between rolls, then score one \
+             open category. The game ends once every category is filled.",
+            dice_state.number_of_dice, die_spec, dice_state.max_number_of_rolls
+        )),
+        HelpLine::Blank,
+        HelpLine::Heading("Choosing dice to reroll".to_string()),
+    ];
+    lines.extend(
+        SYNTAX
+            .iter()
+            .map(|(example, meaning)| HelpLine::Entry(example.to_string(), meaning.to_string())),
+    );
+    lines.push(HelpLine::Blank);
+    lines.push(HelpLine::Heading("Upper section".to_string()));
+    lines.extend(upper.iter().map(category_entry));
+    lines.push(HelpLine::Text(format!(
+        "Bonus: {} points once the upper section adds up to {} or more.",
+        UPPER_BONUS, UPPER_BONUS_THRESHOLD
+    )));
+    lines.push(HelpLine::Blank);
+    lines.push(HelpLine::Heading("Lower section".to_string()));
+    lines.extend(lower.iter().map(category_entry));
+    lines.push(HelpLine::Text(format!(
+        "Yahtzee bonus: every further Yahtzee earns {} points, unless the Yahtzee box was \
+         scratched.",
+        YAHTZEE_BONUS
+    )));
+    lines.push(HelpLine::Text(
+        "Joker rules: a further Yahtzee goes in the upper box of its face if that is open, \
+         otherwise in any lower box, where it counts as a full house or straight, and only \
+         then in any upper box."
+            .to_string(),
+    ));
+    lines.push(HelpLine::Blank);
+    lines.push(HelpLine::Heading("Key bindings".to_string()));
+    lines.extend(keymap.bindings().map(|(action, keys)| {
+        let keys = if keys.is_empty() {
+            "(none)".to_string()
+        } else {
+            keymap.describe_keys(action)
+        };
+        HelpLine::Entry(keys, action.description())
+    }));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::DieSpec;
+    use crate::keymap::{Action, KeymapConfig, Preset};
+
+    #[test]
+    fn test_help_follows_die_and_keymap() {
+        let keymap = Keymap::from_config(&KeymapConfig {
+            preset: Preset::Numpad,
+            ..KeymapConfig::default()
+        })
+        .unwrap();
+        let lines = help_lines(&DiceState::new("d8".parse().unwrap()), &keymap);
+
+        assert!(lines.contains(&HelpLine::Entry(
+            "Large straight".to_string(),
+            "40 points for 5 faces in a row (e.g. 1-2-3-4-5)".to_string()
+        )));
+        assert!(lines.contains(&HelpLine::Entry(
+            "+".to_string(),
+            Action::RollDice.description()
+        )));
+        assert!(lines.contains(&HelpLine::Entry(
+            "(none)".to_string(),
+            Action::Score(Category::Aces).description()
+        )));
+        assert!(
+            matches!(&lines[1], HelpLine::Text(text) if text.starts_with("Roll 5 d8 dice up to 3 times"))
+        );
+    }
+
+    #[test]
+    fn test_help_lists_every_category_and_binding() {
+        let keymap = Keymap::default();
+        let lines = help_lines(&DiceState::new(DieSpec::default()), &keymap);
+        let entries = lines
+            .iter()
+            .filter(|line| matches!(line, HelpLine::Entry(..)))
+            .count();
+        assert_eq!(
+            entries,
+            SYNTAX.len() + Category::ALL.len() + Action::ALL.len()
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! A REST API for the game engine, so other front ends don't have to know the rules. Games live
+//! in memory for as long as the server runs. Every request and response body is JSON:
+//!
+//! ```text
+//! GET    /games                      ids of the games being played
+//! POST   /games                      {"players":["ada","bob"],"die":"d6"}, `die` being optional
+//! GET    /games/{id}                 the state of a game
+//! DELETE /games/{id}
+//! POST   /games/{id}/roll            {"seat":0}
+//! POST   /games/{id}/hold            {"seat":0,"held":[true,true,false,false,false]}
+//! POST   /games/{id}/score           {"seat":0,"category":"FullHouse"}
+//! GET    /games/{id}/potential       what the dice would score in every open category
+//! ```
+//!
+//! `seat` defaults to the player whose turn it is. Moves answer with the new state of the game,
+//! and anything that goes wrong with `{"error":"..."}` and a fitting status code.
+
+use crate::dice::{Dice, DieSpec};
+use crate::game::{Game, GameError, GameSnapshot};
+use crate::protocol::MAX_SEATS;
+use crate::score::Category;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use thiserror::Error;
+use tiny_http::{Header, Method, Request, Response};
+
+/// Where the API listens unless told otherwise, only reachable from the same machine.
+pub(crate) const DEFAULT_HTTP_ADDR: &str = "127.0.0.1:8080";
+
+/// Far more than any request needs, and little enough to read into memory.
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Debug, Error)]
+enum ApiError {
+    #[error("not found")]
+    NotFound,
+    #[error("there is no game {0}")]
+    NoSuchGame(usize),
+    #[error("a game needs at least one player")]
+    NoPlayers,
+    #[error("a game has at most {} players", MAX_SEATS)]
+    TooManyPlayers,
+    #[error("the request body is larger than {} bytes", MAX_BODY)]
+    BodyTooLarge,
+    #[error("invalid request body: {0}")]
+    InvalidBody(#[from] serde_json::Error),
+    #[error(transparent)]
+    Game(#[from] GameError),
+    #[error("the request could not be handled")]
+    Internal,
+}
+
+impl ApiError {
+    fn status(&self) -> u16 {
+        match self {
+            ApiError::NotFound | ApiError::NoSuchGame(_) => 404,
+            ApiError::NoPlayers | ApiError::TooManyPlayers | ApiError::InvalidBody(_) => 400,
+            ApiError::BodyTooLarge => 413,
+            ApiError::Game(_) => 409,
+            ApiError::Internal => 500,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct NewGame {
+    players: Vec<String>,
+    #[serde(default)]
+    die: DieSpec,
+}
+
+#[derive(Deserialize)]
+struct Move {
+    #[serde(default)]
+    seat: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct Hold {
+    #[serde(default)]
+    seat: Option<usize>,
+    held: Vec<bool>,
+}
+
+#[derive(Deserialize)]
+struct ScoreMove {
+    #[serde(default)]
+    seat: Option<usize>,
+    category: Category,
+}
+
+#[derive(Serialize)]
+struct State {
+    id: usize,
+    #[serde(flatten)]
+    game: GameSnapshot,
+}
+
+#[derive(Serialize)]
+struct Potential {
+    seat: usize,
+    scores: BTreeMap<Category, u32>,
+}
+
+/// The games the API serves.
+#[derive(Default)]
+struct Games {
+    games: BTreeMap<usize, Game>,
+    next_id: usize,
+}
+
+impl Games {
+    /// Answers a request with its status code and JSON body.
+    fn handle(&mut self, method: &Method, url: &str, body: &str) -> (u16, String) {
+        match self.route(method, url, body) {
+            Ok((status, body)) => (status, body.to_string()),
+            Err(error) => error_response(&error),
+        }
+    }
+
+    fn route(
+        &mut self,
+        method: &Method,
+        url: &str,
+        body: &str,
+    ) -> Result<(u16, serde_json::Value), ApiError> {
+        let path = url.split('?').next().unwrap_or_default();
+        let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let id = match segments.get(1) {
+            Some(id) => Some(id.parse().map_err(|_| ApiError::NotFound)?),
+            None => None,
+        };
+        match (method, segments.as_slice(), id) {
+            (Method::Get, ["games"], _) => {
+                Ok((200, to_json(self.games.keys().collect::<Vec<_>>())))
+            }
+            (Method::Post, ["games"], _) => {
+                let new_game: NewGame = parse(body)?;
+                let id = self.create(new_game)?;
+                Ok((201, self.state(id)?))
+            }
+            (Method::Get, ["games", _], Some(id)) => Ok((200, self.state(id)?)),
+            (Method::Delete, ["games", _], Some(id)) => {
+                self.games.remove(&id).ok_or(ApiError::NoSuchGame(id))?;
+                Ok((200, serde_json::json!({})))
+            }
+            (Method::Post, ["games", _, "roll"], Some(id)) => {
+                let Move { seat } = parse(body)?;
+                let game = self.game(id)?;
+                game.roll(seat_or_current(game, seat)?)?;
+                Ok((200, self.state(id)?))
+            }
+            (Method::Post, ["games", _, "hold"], Some(id)) => {
+                let Hold { seat, held } = parse(body)?;
+                let game = self.game(id)?;
+                game.hold(seat_or_current(game, seat)?, held)?;
+                Ok((200, self.state(id)?))
+            }
+            (Method::Post, ["games", _, "score"], Some(id)) => {
+                let ScoreMove { seat, category } = parse(body)?;
+                let game = self.game(id)?;
+                game.score(seat_or_current(game, seat)?, category)?;
+                Ok((200, self.state(id)?))
+            }
+            (Method::Get, ["games", _, "potential"], Some(id)) => {
+                Ok((200, to_json(self.potential(id)?)))
+            }
+            _ => Err(ApiError::NotFound),
+        }
+    }
+
+    /// Seats the players in the order given and starts the game straight away.
+    fn create(&mut self, new_game: NewGame) -> Result<usize, ApiError> {
+        if new_game.players.is_empty() {
+            return Err(ApiError::NoPlayers);
+        }
+        if new_game.players.len() > MAX_SEATS {
+            return Err(ApiError::TooManyPlayers);
+        }
+        let mut game = Game::new(new_game.die, new_game.players.len());
+        for name in &new_game.players {
+            game.join(name)?;
+        }
+        for seat in 0..new_game.players.len() {
+            game.ready(seat)?;
+        }
+        self.next_id += 1;
+        self.games.insert(self.next_id, game);
+        Ok(self.next_id)
+    }
+
+    fn game(&mut self, id: usize) -> Result<&mut Game, ApiError> {
+        self.games.get_mut(&id).ok_or(ApiError::NoSuchGame(id))
+    }
+
+    fn state(&mut self, id: usize) -> Result<serde_json::Value, ApiError> {
+        let game = self.game(id)?.snapshot();
+        Ok(to_json(State { id, game }))
+    }
+
+    fn potential(&mut self, id: usize) -> Result<Potential, ApiError> {
+        let game = self.game(id)?.snapshot();
+        let seat = match game.current {
+            Some(seat) if !game.dice.is_empty() => seat,
+            Some(_) => return Err(GameError::NotRolled.into()),
+            None => return Err(GameError::Over.into()),
+        };
+        let score = &game.players[seat].score;
+        let dice = Dice(game.dice.clone());
+        let scores = Category::ALL
+            .iter()
+            .filter_map(|category| {
+                let points = score.potential(*category, &dice, &game.die_spec)?;
+                Some((*category, points))
+            })
+            .collect();
+        Ok(Potential { seat, scores })
+    }
+}
+
+fn seat_or_current(game: &Game, seat: Option<usize>) -> Result<usize, GameError> {
+    seat.or(game.snapshot().current).ok_or(GameError::Over)
+}
+
+/// An empty body stands for an empty object, so moves by the current player need none.
+fn parse<T: DeserializeOwned>(body: &str) -> Result<T, serde_json::Error> {
+    match body.trim() {
+        "" => serde_json::from_str("{}"),
+        body => serde_json::from_str(body),
+    }
+}
+
+fn error_response(error: &ApiError) -> (u16, String) {
+    let body = serde_json::json!({ "error": error.to_string() });
+    (error.status(), body.to_string())
+}
+
+/// Answers with a 500 if handling the request panicked, which must not stop the server.
+fn catch_panic<F: FnOnce() -> (u16, String)>(handle: F) -> (u16, String) {
+    panic::catch_unwind(AssertUnwindSafe(handle))
+        .unwrap_or_else(|_| error_response(&ApiError::Internal))
+}
+
+fn to_json<T: Serialize>(value: T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+pub(crate) struct HttpServer {
+    server: tiny_http::Server,
+    games: Games,
+}
+
+impl HttpServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let server = tiny_http::Server::http(addr).map_err(io::Error::other)?;
+        Ok(Self {
+            server,
+            games: Games::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Answers requests one at a time until receiving them fails.
+    pub fn run(mut self) -> io::Result<()> {
+        loop {
+            let request = self.server.recv()?;
+            // A client that went away only misses its own answer
+            let _ = self.respond(request);
+        }
+    }
+
+    fn respond(&mut self, mut request: Request) -> io::Result<()> {
+        let mut body = String::new();
+        let read = request
+            .as_reader()
+            .take(MAX_BODY as u64 + 1)
+            .read_to_string(&mut body);
+        let (status, body) = match read {
+            Ok(length) if length > MAX_BODY => error_response(&ApiError::BodyTooLarge),
+            Ok(_) => catch_panic(|| self.games.handle(request.method(), request.url(), &body)),
+            Err(error) => (
+                400,
+                serde_json::json!({ "error": error.to_string() }).to_string(),
+            ),
+        };
+        let content_type =
+            Header::from_bytes("Content-Type", "application/json").expect("a valid header");
+        request.respond(
+            Response::from_string(body)
+                .with_status_code(status)
+                .with_header(content_type),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::thread;
+
+    fn json(body: &str) -> serde_json::Value {
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn test_playing_a_game_through_the_api() {
+        let mut games = Games::default();
+        let (status, body) = games.handle(
+            &Method::Post,
+            "/games",
+            r#"{"players":["ada","bob"],"die":"d6"}"#,
+        );
+        assert_eq!(status, 201);
+        let state = json(&body);
+        assert_eq!(state["id"], 1);
+        assert_eq!(state["current"], 0);
+        assert_eq!(state["players"][1]["name"], "bob");
+
+        let (status, body) = games.handle(&Method::Get, "/games/1/potential", "");
+        assert_eq!(
+            (status, json(&body)["error"].clone()),
+            (409, "roll the dice first".into())
+        );
+
+        let (status, body) = games.handle(&Method::Post, "/games/1/roll", "");
+        assert_eq!(status, 200);
+        assert_eq!(json(&body)["dice"].as_array().unwrap().len(), 5);
+        assert_eq!(json(&body)["rolls_left"], 2);
+
+        let (status, body) = games.handle(&Method::Post, "/games/1/roll", r#"{"seat":1}"#);
+        assert_eq!(
+            (status, json(&body)["error"].clone()),
+            (409, "it's not your turn".into())
+        );
+
+        let (status, _) = games.handle(
+            &Method::Post,
+            "/games/1/hold",
+            r#"{"held":[true,true,false,false,false]}"#,
+        );
+        assert_eq!(status, 200);
+        let (_, body) = games.handle(&Method::Post, "/games/1/roll", "");
+        assert_eq!(json(&body)["rolls_left"], 1);
+
+        let (status, body) = games.handle(&Method::Get, "/games/1/potential", "");
+        assert_eq!(status, 200);
+        let potential = json(&body);
+        assert_eq!(potential["seat"], 0);
+        assert_eq!(potential["scores"].as_object().unwrap().len(), 13);
+        let chance = potential["scores"]["Chance"].clone();
+
+        let (status, body) = games.handle(
+            &Method::Post,
+            "/games/1/score",
+            r#"{"seat":0,"category":"Chance"}"#,
+        );
+        assert_eq!(status, 200);
+        let state = json(&body);
+        assert_eq!(
+            state["players"][0]["score"]["lower_section"]["chance"],
+            chance
+        );
+        assert_eq!(state["current"], 1);
+    }
+
+    #[test]
+    fn test_bad_requests_are_errors() {
+        let mut games = Games::default();
+        let error = |(status, body): (u16, String)| (status, json(&body)["error"].clone());
+        assert_eq!(
+            error(games.handle(&Method::Get, "/games/7", "")),
+            (404, "there is no game 7".into())
+        );
+        assert_eq!(
+            error(games.handle(&Method::Get, "/players", "")),
+            (404, "not found".into())
+        );
+        assert_eq!(
+            error(games.handle(&Method::Post, "/games", r#"{"players":[]}"#)),
+            (400, "a game needs at least one player".into())
+        );
+        assert_eq!(
+            error(games.handle(
+                &Method::Post,
+                "/games",
+                &format!(r#"{{"players":{:?}}}"#, vec!["ada"; MAX_SEATS + 1])
+            )),
+            (400, "a game has at most 8 players".into())
+        );
+        assert_eq!(games.handle(&Method::Post, "/games", "{").0, 400);
+        for die in ["d1", "d4294967295", "4000000000,4000000001"] {
+            let body = format!(r#"{{"players":["ada"],"die":"{}"}}"#, die);
+            assert_eq!(games.handle(&Method::Post, "/games", &body).0, 400);
+        }
+
+        games.handle(&Method::Post, "/games", r#"{"players":["ada"]}"#);
+        games.handle(&Method::Post, "/games/1/roll", "");
+        assert_eq!(
+            error(games.handle(&Method::Post, "/games/1/hold", r#"{"held":[true]}"#)),
+            (409, "expected 5 dice to hold, got 1".into())
+        );
+        assert_eq!(games.handle(&Method::Delete, "/games/1", "").0, 200);
+        assert_eq!(
+            games.handle(&Method::Get, "/games", ""),
+            (200, "[]".to_string())
+        );
+        assert_eq!(
+            error(catch_panic(|| panic!("a bug"))),
+            (500, "the request could not be handled".into())
+        );
+    }
+
+    #[test]
+    fn test_serving_on_localhost() {
+        let server = HttpServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let body = r#"{"players":["ada"]}"#;
+        write!(
+            stream,
+            "POST /games HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 201"));
+        assert!(response.contains("application/json"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(json(body)["players"][0]["name"], "ada");
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST /games HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            MAX_BODY + 1,
+            " ".repeat(MAX_BODY + 1)
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"));
+    }
+}
@@ -0,0 +1,524 @@
+use crate::score::Category;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Everything the player can trigger with a key while no text is being entered.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Action {
+    Quit,
+    NewGame,
+    RollDice,
+    SelectDice,
+    DiceStats,
+    PlayerStats,
+    Help,
+    ToggleOdds,
+    Chat,
+    HighlightPrevious,
+    HighlightNext,
+    ScoreHighlighted,
+    Score(Category),
+}
+
+impl Action {
+    pub const ALL: [Action; 25] = [
+        Action::Quit,
+        Action::NewGame,
+        Action::RollDice,
+        Action::SelectDice,
+        Action::DiceStats,
+        Action::PlayerStats,
+        Action::Help,
+        Action::ToggleOdds,
+        Action::Chat,
+        Action::HighlightPrevious,
+        Action::HighlightNext,
+        Action::ScoreHighlighted,
+        Action::Score(Category::Aces),
+        Action::Score(Category::Twos),
+        Action::Score(Category::Threes),
+        Action::Score(Category::Fours),
+        Action::Score(Category::Fives),
+        Action::Score(Category::Sixes),
+        Action::Score(Category::ThreeOfAKind),
+        Action::Score(Category::FourOfAKind),
+        Action::Score(Category::FullHouse),
+        Action::Score(Category::SmallStraight),
+        Action::Score(Category::LargeStraight),
+        Action::Score(Category::Yahtzee),
+        Action::Score(Category::Chance),
+    ];
+
+    /// The name used for the action in the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::NewGame => "new_game",
+            Action::RollDice => "roll_dice",
+            Action::SelectDice => "select_dice",
+            Action::DiceStats => "dice_stats",
+            Action::PlayerStats => "player_stats",
+            Action::Help => "help",
+            Action::ToggleOdds => "toggle_odds",
+            Action::Chat => "chat",
+            Action::HighlightPrevious => "highlight_previous",
+            Action::HighlightNext => "highlight_next",
+            Action::ScoreHighlighted => "score_highlighted",
+            Action::Score(Category::Aces) => "score_aces",
+            Action::Score(Category::Twos) => "score_twos",
+            Action::Score(Category::Threes) => "score_threes",
+            Action::Score(Category::Fours) => "score_fours",
+            Action::Score(Category::Fives) => "score_fives",
+            Action::Score(Category::Sixes) => "score_sixes",
+            Action::Score(Category::ThreeOfAKind) => "score_three_of_a_kind",
+            Action::Score(Category::FourOfAKind) => "score_four_of_a_kind",
+            Action::Score(Category::FullHouse) => "score_full_house",
+            Action::Score(Category::SmallStraight) => "score_small_straight",
+            Action::Score(Category::LargeStraight) => "score_large_straight",
+            Action::Score(Category::Yahtzee) => "score_yahtzee",
+            Action::Score(Category::Chance) => "score_chance",
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Action::Quit => "Quit".to_string(),
+            Action::NewGame => "New game".to_string(),
+            Action::RollDice => "Roll all dice".to_string(),
+            Action::SelectDice => "Select dice to reroll".to_string(),
+            Action::DiceStats => "Dice statistics".to_string(),
+            Action::PlayerStats => "Player statistics".to_string(),
+            Action::Help => "Help".to_string(),
+            Action::ToggleOdds => "Show or hide the odds next to the dice".to_string(),
+            Action::Chat => "Chat with the other players".to_string(),
+            Action::HighlightPrevious => "Highlight previous category".to_string(),
+            Action::HighlightNext => "Highlight next category".to_string(),
+            Action::ScoreHighlighted => "Score highlighted category".to_string(),
+            Action::Score(category) => format!("Score {}", category.name()),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = KeymapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .iter()
+            .find(|action| action.name() == s)
+            .copied()
+            .ok_or_else(|| KeymapError::UnknownAction(s.to_string()))
+    }
+}
+
+/// A key, optionally combined with Ctrl or Alt, written like `q`, `enter`, `f1` or `ctrl-n`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        // Shift is part of the character itself, e.g. `?` or `Q`
+        let modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        self.code == event.code && self.modifiers == modifiers
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = KeymapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || KeymapError::InvalidKey(s.to_string());
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key = s;
+        loop {
+            if let Some(rest) = key.strip_prefix("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                key = rest;
+            } else if let Some(rest) = key.strip_prefix("alt-") {
+                modifiers |= KeyModifiers::ALT;
+                key = rest;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_ascii_lowercase().as_str() {
+                "esc" => KeyCode::Esc,
+                "enter" => KeyCode::Enter,
+                "space" => KeyCode::Char(' '),
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "insert" => KeyCode::Insert,
+                "delete" => KeyCode::Delete,
+                other => {
+                    let number = other.strip_prefix('f').ok_or_else(invalid)?;
+                    KeyCode::F(number.parse().map_err(|_| invalid())?)
+                }
+            },
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::F(number) => write!(f, "f{}", number),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub(crate) enum KeymapError {
+    #[error("unknown action '{0}'")]
+    UnknownAction(String),
+    #[error("invalid key '{0}'")]
+    InvalidKey(String),
+    #[error("key '{key}' is bound to more than one action: {}", .actions.join(", "))]
+    Conflict { key: String, actions: Vec<String> },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Preset {
+    Default,
+    Vim,
+    Numpad,
+}
+
+impl Preset {
+    fn bindings(&self) -> Vec<(Action, Vec<&'static str>)> {
+        let mut bindings = vec![
+            (Action::Quit, vec!["q", "esc"]),
+            (Action::NewGame, vec!["n"]),
+            (Action::RollDice, vec!["r"]),
+            (Action::SelectDice, vec!["e"]),
+            (Action::DiceStats, vec!["d"]),
+            (Action::PlayerStats, vec!["p"]),
+            (Action::Help, vec!["?"]),
+            (Action::ToggleOdds, vec!["o"]),
+            (Action::Chat, vec!["m"]),
+            (Action::HighlightPrevious, vec!["up"]),
+            (Action::HighlightNext, vec!["down"]),
+            (Action::ScoreHighlighted, vec!["enter"]),
+            (Action::Score(Category::Aces), vec!["1"]),
+            (Action::Score(Category::Twos), vec!["2"]),
+            (Action::Score(Category::Threes), vec!["3"]),
+            (Action::Score(Category::Fours), vec!["4"]),
+            (Action::Score(Category::Fives), vec!["5"]),
+            (Action::Score(Category::Sixes), vec!["6"]),
+            (Action::Score(Category::ThreeOfAKind), vec!["t"]),
+            (Action::Score(Category::FourOfAKind), vec!["f"]),
+            (Action::Score(Category::FullHouse), vec!["h"]),
+            (Action::Score(Category::SmallStraight), vec!["s"]),
+            (Action::Score(Category::LargeStraight), vec!["l"]),
+            (Action::Score(Category::Yahtzee), vec!["y"]),
+            (Action::Score(Category::Chance), vec!["c"]),
+        ];
+        let mut rebind = |action: Action, keys: Vec<&'static str>| {
+            if let Some((_, bound_keys)) = bindings.iter_mut().find(|(a, _)| *a == action) {
+                *bound_keys = keys;
+            }
+        };
+        match self {
+            Preset::Default => {}
+            Preset::Vim => {
+                rebind(Action::HighlightPrevious, vec!["k", "up"]);
+                rebind(Action::HighlightNext, vec!["j", "down"]);
+                rebind(Action::ScoreHighlighted, vec!["enter", "space"]);
+            }
+            Preset::Numpad => {
+                // The digits steer the cursor, so the upper section is scored via the cursor
+                rebind(Action::HighlightPrevious, vec!["8", "up"]);
+                rebind(Action::HighlightNext, vec!["2", "down"]);
+                rebind(Action::ScoreHighlighted, vec!["5", "enter"]);
+                rebind(Action::RollDice, vec!["+"]);
+                rebind(Action::SelectDice, vec!["-"]);
+                rebind(Action::NewGame, vec!["*"]);
+                rebind(Action::Help, vec!["/"]);
+                for category in &Category::ALL[..6] {
+                    rebind(Action::Score(*category), vec![]);
+                }
+            }
+        }
+        bindings
+    }
+}
+
+/// The `keymap` section of the config file: a preset plus per action overrides, e.g.
+/// `{"preset": "vim", "bindings": {"roll_dice": ["space"]}}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct KeymapConfig {
+    pub preset: Preset,
+    pub bindings: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        Self {
+            preset: Preset::Default,
+            bindings: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Keymap {
+    bindings: Vec<(Action, Vec<KeyBinding>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_config(&KeymapConfig::default()).expect("the default keymap is valid")
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from a preset and overrides. Overriding an action replaces all of its
+    /// preset keys. Keys bound to more than one action are rejected.
+    pub fn from_config(config: &KeymapConfig) -> Result<Self, KeymapError> {
+        let mut bindings = config
+            .preset
+            .bindings()
+            .into_iter()
+            .map(|(action, keys)| Ok((action, parse_keys(keys)?)))
+            .collect::<Result<Vec<_>, KeymapError>>()?;
+
+        for (name, keys) in &config.bindings {
+            let action: Action = name.parse()?;
+            let keys = parse_keys(keys.iter().map(String::as_str))?;
+            if let Some((_, bound_keys)) = bindings.iter_mut().find(|(a, _)| *a == action) {
+                *bound_keys = keys;
+            }
+        }
+
+        let keymap = Self { bindings };
+        keymap.check_conflicts()?;
+        Ok(keymap)
+    }
+
+    fn check_conflicts(&self) -> Result<(), KeymapError> {
+        for (index, (action, keys)) in self.bindings.iter().enumerate() {
+            for key in keys {
+                let conflicting: Vec<&Action> = self.bindings[index + 1..]
+                    .iter()
+                    .filter(|(_, other_keys)| other_keys.contains(key))
+                    .map(|(other, _)| other)
+                    .collect();
+                if !conflicting.is_empty() {
+                    return Err(KeymapError::Conflict {
+                        key: key.to_string(),
+                        actions: std::iter::once(action)
+                            .chain(conflicting)
+                            .map(|action| action.name().to_string())
+                            .collect(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn action_for(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|key| key.matches(event)))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys_for(&self, action: Action) -> &[KeyBinding] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    /// All keys of an action joined for display, e.g. `q/esc`.
+    pub fn describe_keys(&self, action: Action) -> String {
+        self.keys_for(action)
+            .iter()
+            .map(KeyBinding::to_string)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item = (Action, &[KeyBinding])> {
+        self.bindings
+            .iter()
+            .map(|(action, keys)| (*action, keys.as_slice()))
+    }
+}
+
+fn parse_keys<'a, I: IntoIterator<Item = &'a str>>(
+    keys: I,
+) -> Result<Vec<KeyBinding>, KeymapError> {
+    keys.into_iter().map(str::parse).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[rstest]
+    #[case("q", KeyCode::Char('q'), KeyModifiers::NONE)]
+    #[case("?", KeyCode::Char('?'), KeyModifiers::NONE)]
+    #[case("enter", KeyCode::Enter, KeyModifiers::NONE)]
+    #[case("space", KeyCode::Char(' '), KeyModifiers::NONE)]
+    #[case("f5", KeyCode::F(5), KeyModifiers::NONE)]
+    #[case("ctrl-n", KeyCode::Char('n'), KeyModifiers::CONTROL)]
+    #[case("ctrl-alt-x", KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT)]
+    fn test_key_binding_round_trips(
+        #[case] input: &str,
+        #[case] code: KeyCode,
+        #[case] modifiers: KeyModifiers,
+    ) {
+        let binding: KeyBinding = input.parse().unwrap();
+        assert_eq!(binding, KeyBinding { code, modifiers });
+        assert_eq!(binding.to_string(), input);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("shift")]
+    #[case("fx")]
+    #[case("ctrl-")]
+    fn test_invalid_key_bindings(#[case] input: &str) {
+        assert_eq!(
+            input.parse::<KeyBinding>(),
+            Err(KeymapError::InvalidKey(input.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_shift_is_ignored_for_characters() {
+        let binding: KeyBinding = "?".parse().unwrap();
+        assert!(binding.matches(&KeyEvent::new(KeyCode::Char('?'), KeyModifiers::SHIFT)));
+        assert!(!binding.matches(&KeyEvent::new(KeyCode::Char('?'), KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action_for(&key(KeyCode::Esc)), Some(Action::Quit));
+        assert_eq!(
+            keymap.action_for(&key(KeyCode::Char('h'))),
+            Some(Action::Score(Category::FullHouse))
+        );
+        assert_eq!(keymap.action_for(&key(KeyCode::Char('x'))), None);
+        assert_eq!(keymap.describe_keys(Action::Quit), "q/esc");
+    }
+
+    #[rstest]
+    #[case(Preset::Vim, KeyCode::Char('j'), Some(Action::HighlightNext))]
+    #[case(
+        Preset::Vim,
+        KeyCode::Char('h'),
+        Some(Action::Score(Category::FullHouse))
+    )]
+    #[case(Preset::Numpad, KeyCode::Char('2'), Some(Action::HighlightNext))]
+    #[case(Preset::Numpad, KeyCode::Char('+'), Some(Action::RollDice))]
+    #[case(Preset::Numpad, KeyCode::Char('1'), None)]
+    fn test_presets(
+        #[case] preset: Preset,
+        #[case] code: KeyCode,
+        #[case] expected: Option<Action>,
+    ) {
+        let keymap = Keymap::from_config(&KeymapConfig {
+            preset,
+            bindings: BTreeMap::new(),
+        })
+        .unwrap();
+        assert_eq!(keymap.action_for(&key(code)), expected);
+    }
+
+    #[test]
+    fn test_overrides_replace_preset_keys() {
+        let config: KeymapConfig =
+            serde_json::from_str(r#"{"bindings": {"roll_dice": ["space", "ctrl-r"]}}"#).unwrap();
+        let keymap = Keymap::from_config(&config).unwrap();
+        assert_eq!(keymap.action_for(&key(KeyCode::Char('r'))), None);
+        assert_eq!(
+            keymap.action_for(&key(KeyCode::Char(' '))),
+            Some(Action::RollDice)
+        );
+        assert_eq!(
+            keymap.action_for(&KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL)),
+            Some(Action::RollDice)
+        );
+    }
+
+    #[test]
+    fn test_conflicting_bindings_are_rejected() {
+        let config: KeymapConfig =
+            serde_json::from_str(r#"{"bindings": {"new_game": ["c"]}}"#).unwrap();
+        let error = Keymap::from_config(&config).unwrap_err();
+        assert_eq!(
+            error,
+            KeymapError::Conflict {
+                key: "c".to_string(),
+                actions: vec!["new_game".to_string(), "score_chance".to_string()],
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "key 'c' is bound to more than one action: new_game, score_chance"
+        );
+    }
+
+    #[test]
+    fn test_unknown_action_is_rejected() {
+        let config: KeymapConfig =
+            serde_json::from_str(r#"{"bindings": {"cheat": ["x"]}}"#).unwrap();
+        assert_eq!(
+            Keymap::from_config(&config),
+            Err(KeymapError::UnknownAction("cheat".to_string()))
+        );
+    }
+}
@@ -1,108 +1,257 @@
 mod app;
+mod bot;
+mod bot_host;
+mod cli;
+mod client;
+mod config;
 mod dice;
+mod dice_stats;
+mod events;
+mod game;
+#[cfg(test)]
+mod harness;
+mod help;
+#[cfg(feature = "http")]
+mod http;
+mod keymap;
+mod probability;
+mod profile;
+mod protocol;
 mod score;
+mod selection;
+mod server;
+mod storage;
+mod terminal;
+mod theme;
+mod tournament;
 mod ui;
 
-use crate::app::{AppState, Event, InputMode};
+use crate::app::{AppState, Event, Flow, InputMode};
+use crate::bot::Bot;
+use crate::bot_host::ExternalBot;
+use crate::cli::{Command, Options};
+use crate::client::RemoteGame;
+use crate::config::{Config, CONFIG_FILE};
+use crate::dice_stats::{DiceHistory, DICE_HISTORY_FILE};
+use crate::events::Events;
+#[cfg(feature = "http")]
+use crate::http::HttpServer;
+use crate::keymap::Keymap;
+use crate::profile::{PlayerProfile, PROFILES_DIR};
+use crate::protocol::{ClientMessage, DEFAULT_PORT};
 use crate::score::Score;
-use crate::ui::render_app;
-use crossterm::event::{KeyCode, KeyEvent};
-use crossterm::terminal::disable_raw_mode;
-use crossterm::{
-    event::{self, Event as CEvent},
-    terminal::enable_raw_mode,
-};
-use std::io;
-use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, SendError};
-use std::thread;
-use std::time::{Duration, Instant};
-use tui::backend::{Backend, CrosstermBackend};
-use tui::Terminal;
+use crate::server::Server;
+use crate::terminal::TerminalGuard;
+use crate::theme::{Theme, ThemeName};
+use crate::tournament::Tournament;
+use crate::ui::{render_app, UiRegions};
+use crossterm::event::{MouseButton, MouseEventKind};
+use std::time::Duration;
 
-fn event_loop(tick_rate: Duration) -> Receiver<Event<KeyEvent>> {
-    let (tx, rx) = mpsc::channel();
-    thread::spawn(move || -> Result<(), SendError<Event<KeyEvent>>> {
-        let mut last_tick = Instant::now();
-        loop {
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
+const TICK_RATE: Duration = Duration::from_millis(200);
 
-            if event::poll(timeout).unwrap_or(false) {
-                if let Ok(CEvent::Key(key)) = event::read() {
-                    tx.send(Event::Input(key))?;
-                }
-            }
-
-            if last_tick.elapsed() >= tick_rate && tx.send(Event::Tick).is_ok() {
-                last_tick = Instant::now();
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    if options.command == Command::DiceStats {
+        let dice_history = DiceHistory::new(storage::data_file(DICE_HISTORY_FILE)?);
+        print!("{}", dice_history.report(&options.die_spec)?);
+        return Ok(());
+    }
+    if options.command == Command::Serve {
+        #[cfg(not(feature = "websocket"))]
+        if let Some(ws_addr) = &options.ws_addr {
+            let message = format!(
+                "--ws-addr {} needs a build with the websocket feature",
+                ws_addr
+            );
+            return Err(message.into());
+        }
+        let addr = match &options.addr {
+            Some(addr) => addr.clone(),
+            None => format!("0.0.0.0:{}", DEFAULT_PORT),
+        };
+        let server = Server::bind(&addr, options.die_spec, options.seats)?;
+        println!(
+            "Serving on {}, new tables are for {} players",
+            server.local_addr()?,
+            options.seats
+        );
+        #[cfg(feature = "websocket")]
+        if let Some(ws_addr) = &options.ws_addr {
+            println!(
+                "Browsers connect to ws://{}",
+                server.listen_websocket(ws_addr)?
+            );
+        }
+        server.run()?;
+        return Ok(());
+    }
+    if options.command == Command::ServeHttp {
+        #[cfg(feature = "http")]
+        {
+            let addr = options.addr.as_deref().unwrap_or(http::DEFAULT_HTTP_ADDR);
+            let server = HttpServer::bind(addr)?;
+            if let Some(addr) = server.local_addr() {
+                println!("Serving the API on http://{}", addr);
             }
+            server.run()?;
+            return Ok(());
         }
-    });
-    rx
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    enable_raw_mode()?;
-    let rx = event_loop(Duration::from_millis(200));
+        #[cfg(not(feature = "http"))]
+        return Err("serve-http needs a build with the http feature".into());
+    }
+    if matches!(options.command, Command::BotHost | Command::Tournament) {
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut entrants: Vec<(String, Box<dyn Bot>)> = vec![];
+        for (index, strategy) in options.strategies.iter().enumerate() {
+            let bot = strategy.bot(seed.wrapping_add(index as u64));
+            entrants.push((strategy.to_string(), bot));
+        }
+        for command in &options.bot_commands {
+            let bot = ExternalBot::spawn(command, options.bot_timeout)?;
+            entrants.push((command.clone(), Box::new(bot)));
+        }
+        // On stderr, so the standings can be piped elsewhere
+        eprintln!(
+            "Playing {} games per table with seed {}",
+            options.games, seed
+        );
+        let standings = Tournament::new(options.die_spec, options.games, seed)
+            .with_seats(options.seats)
+            .run(&mut entrants)?;
+        print!("{}", tournament::report(&standings, options.report_format));
+        return Ok(());
+    }
+    if let Command::Games(addr) = &options.command {
+        for game in client::list_games(addr.as_str())? {
+            let status = match (game.started, game.over) {
+                (_, true) => "over",
+                (true, false) => "playing",
+                (false, false) => "waiting",
+            };
+            println!(
+                "Table {}: {}, {}/{} players, {} - {}",
+                game.table,
+                game.die_spec,
+                game.players.len(),
+                game.number_of_seats,
+                status,
+                game.players.join(", ")
+            );
+        }
+        return Ok(());
+    }
+    let remote = match &options.command {
+        Command::Join(addr) => Some(RemoteGame::connect(addr.as_str(), &greeting(&options))?),
+        Command::Watch(addr) => Some(RemoteGame::connect(
+            addr.as_str(),
+            &[ClientMessage::Watch {
+                table: options.table,
+            }],
+        )?),
+        _ => None,
+    };
 
-    let stdout = io::stdout();
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    terminal.clear()?;
-    let mut app_state = AppState::new();
+    let config = match options
+        .config
+        .or_else(|| storage::config_dir().map(|dir| dir.join(CONFIG_FILE)))
+    {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
+    let keymap = Keymap::from_config(&config.keymap)?;
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let theme = Theme::new(ThemeName::choose(options.theme, config.theme, no_color));
+    let mut app_state = AppState::new(options.die_spec)
+        .with_config(config)
+        .with_keymap(keymap)
+        .with_theme(theme);
+    if let Ok(path) = storage::data_file(DICE_HISTORY_FILE) {
+        app_state = app_state.with_dice_history(DiceHistory::new(path));
+    }
+    if let Ok(dir) = storage::data_file(PROFILES_DIR) {
+        // Like the dice history, the profile is nice to have but mustn't keep anyone from playing
+        match PlayerProfile::open(&dir, &options.player) {
+            Ok(player) => app_state = app_state.with_player(player),
+            Err(error) => eprintln!(
+                "Playing without a profile, it couldn't be loaded: {}",
+                error
+            ),
+        }
+    }
+    if let Some(remote) = remote {
+        app_state = app_state.with_remote(remote);
+    }
 
+    let mut terminal = TerminalGuard::new()?;
+    let mut events = Events::new();
+    events.spawn_input(TICK_RATE);
+    events.spawn_ticks(TICK_RATE);
+    if let Some(remote) = &app_state.remote {
+        remote.spawn_reader(&mut events, TICK_RATE)?;
+    }
+    let mut ui_regions = UiRegions::default();
     loop {
-        terminal.draw(|rect| render_app(&app_state, rect))?;
+        terminal.draw(|rect| ui_regions = render_app(&app_state, rect))?;
 
-        match rx.recv()? {
-            Event::Input(event) => match app_state.input_mode {
-                InputMode::Normal => match event.code {
-                    KeyCode::Esc | KeyCode::Char('q') => {
-                        quit_app(terminal)?;
+        match events.next()? {
+            Event::Input(event) if terminal::is_interrupt(&event) => break,
+            Event::Input(event) if terminal::is_suspend(&event) => terminal.suspend()?,
+            Event::Input(event) => {
+                if app_state.handle_key(&event) == Flow::Quit {
+                    break;
+                }
+            }
+            Event::Mouse(mouse) => {
+                if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+                    continue;
+                }
+                if let Some(target) = ui_regions.target_at(mouse.column, mouse.row) {
+                    if app_state.handle_click(target) == Flow::Quit {
                         break;
                     }
-                    KeyCode::Char('n') => app_state.new_game(),
-                    KeyCode::Char('e') => app_state.select_dice_to_reroll(),
-                    KeyCode::Char('r') => app_state.roll_all_dice(),
-                    KeyCode::Char('1') => app_state.set_aces(),
-                    KeyCode::Char('2') => app_state.set_twos(),
-                    KeyCode::Char('3') => app_state.set_threes(),
-                    KeyCode::Char('4') => app_state.set_fours(),
-                    KeyCode::Char('5') => app_state.set_fives(),
-                    KeyCode::Char('6') => app_state.set_sixes(),
-                    KeyCode::Char('t') => app_state.set_three_of_a_kind(),
-                    KeyCode::Char('f') => app_state.set_four_of_a_kind(),
-                    KeyCode::Char('h') => app_state.set_full_house(),
-                    KeyCode::Char('s') => app_state.set_small_straight(),
-                    KeyCode::Char('l') => app_state.set_large_straight(),
-                    KeyCode::Char('y') => app_state.set_yahtzee(),
-                    KeyCode::Char('c') => app_state.set_chance(),
-                    _ => {}
-                },
-                InputMode::Selecting => match event.code {
-                    KeyCode::Enter => app_state.reroll_selected_dice(),
-                    KeyCode::Char(c) => {
-                        app_state.selection_input.push(c);
-                    }
-                    KeyCode::Backspace => {
-                        app_state.selection_input.pop();
-                    }
-                    KeyCode::Esc => app_state.cancel_selection_mode(),
-                    _ => {}
-                },
-            },
-            Event::Tick => {}
+                }
+            }
+            // Resize the buffers right away so the next draw lays the app out for the new size
+            Event::Resize => terminal.autoresize()?,
+            Event::Tick => app_state.on_tick(),
+            Event::Server(message) => app_state.apply_server_message(*message),
+            Event::Error(error) => return Err(error.into()),
         }
     }
 
+    // Stop reading input before the terminal guard hands the terminal back to the shell
+    events.shutdown();
+    drop(terminal);
+    if let (Command::Join(addr), Some(remote)) = (&options.command, &app_state.remote) {
+        if let (Some(token), false) = (&remote.token, remote.over) {
+            println!(
+                "Rejoin the game with: yahtzee join {} --token {}",
+                addr, token
+            );
+        }
+    }
     Ok(())
 }
 
-fn quit_app<B: Backend>(mut terminal: Terminal<B>) -> Result<(), Box<dyn std::error::Error>> {
-    disable_raw_mode()?;
-    terminal.show_cursor()?;
-    terminal.clear()?;
-    Ok(())
+/// What `join` sends the server to get a seat.
+fn greeting(options: &Options) -> Vec<ClientMessage> {
+    let mut messages = vec![match (&options.token, options.create) {
+        (Some(token), _) => ClientMessage::Rejoin {
+            token: token.clone(),
+        },
+        (None, true) => ClientMessage::Create {
+            name: options.player.clone(),
+            die: options.die_spec.to_string(),
+            seats: options.seats,
+        },
+        (None, false) => ClientMessage::Join {
+            name: options.player.clone(),
+            table: options.table,
+        },
+    }];
+    if options.bots {
+        messages.push(ClientMessage::AddBots);
+    }
+    messages
 }
@@ -0,0 +1,339 @@
+use crate::dice::{Dice, DieSpec};
+use crate::score::{Category, Score};
+use std::collections::HashMap;
+
+/// Working out the odds takes time that grows quickly with the number of faces, so bigger dice
+/// are left out.
+pub(crate) const MAX_FACES: usize = 8;
+
+/// The categories whose odds are worth knowing, i.e. the ones that need a particular pattern.
+pub(crate) const PATTERNS: [Category; 6] = [
+    Category::ThreeOfAKind,
+    Category::FourOfAKind,
+    Category::FullHouse,
+    Category::SmallStraight,
+    Category::LargeStraight,
+    Category::Yahtzee,
+];
+
+/// The chance of ending the turn with dice that score in `category`, if every roll left is
+/// played to get there.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Odds {
+    pub category: Category,
+    pub probability: f64,
+}
+
+/// The odds when `held` is kept and the other dice are rolled, followed by the best choices for
+/// the rolls after that. `held` has to be all dice once there are no rolls left.
+pub(crate) fn odds_keeping(
+    die_spec: &DieSpec,
+    number_of_dice: usize,
+    held: &[u32],
+    rolls_left: usize,
+) -> Option<Vec<Odds>> {
+    let hands = Hands::new(die_spec, number_of_dice)?;
+    let held = hands.encode(held)?;
+    Some(hands.odds(|calculator| match rolls_left {
+        0 => calculator.best(held, 0),
+        _ => calculator.keep(held, rolls_left),
+    }))
+}
+
+/// The odds when the player may still choose which of `dice` to keep.
+pub(crate) fn odds_from(
+    die_spec: &DieSpec,
+    number_of_dice: usize,
+    dice: &[u32],
+    rolls_left: usize,
+) -> Option<Vec<Odds>> {
+    let hands = Hands::new(die_spec, number_of_dice)?;
+    let dice = hands.encode(dice)?;
+    Some(hands.odds(|calculator| calculator.best(dice, rolls_left)))
+}
+
+/// A multiset of dice stored as the number of dice per face, `BITS` bits for each face. Adding
+/// two hands adds their counts.
+type Hand = u64;
+
+const BITS: u32 = 3;
+const MASK: u64 = (1 << BITS) - 1;
+
+struct Hands<'a> {
+    die_spec: &'a DieSpec,
+    number_of_dice: usize,
+    /// Every outcome of rolling `n` dice with its probability, indexed by `n`.
+    outcomes: Vec<Vec<(Hand, f64)>>,
+}
+
+impl<'a> Hands<'a> {
+    fn new(die_spec: &'a DieSpec, number_of_dice: usize) -> Option<Self> {
+        if die_spec.faces().len() > MAX_FACES || number_of_dice > MASK as usize {
+            return None;
+        }
+        let probabilities = die_spec.probabilities();
+        let outcomes = (0..=number_of_dice)
+            .map(|n| {
+                let mut outcomes = vec![];
+                collect_outcomes(&probabilities, 0, n, 0, 1.0, &mut outcomes);
+                outcomes
+            })
+            .collect();
+        Some(Self {
+            die_spec,
+            number_of_dice,
+            outcomes,
+        })
+    }
+
+    fn encode(&self, dice: &[u32]) -> Option<Hand> {
+        if dice.len() > self.number_of_dice {
+            return None;
+        }
+        let faces = self.die_spec.faces();
+        dice.iter().try_fold(0, |hand, die| {
+            let index = faces.iter().position(|face| face == die)?;
+            Some(hand + (1 << (BITS * index as u32)))
+        })
+    }
+
+    fn decode(&self, hand: Hand) -> Vec<u32> {
+        self.die_spec
+            .faces()
+            .iter()
+            .enumerate()
+            .flat_map(|(index, face)| {
+                let count = (hand >> (BITS * index as u32)) & MASK;
+                std::iter::repeat_n(*face, count as usize)
+            })
+            .collect()
+    }
+
+    fn odds<F>(&self, probability: F) -> Vec<Odds>
+    where
+        F: Fn(&mut Calculator) -> f64,
+    {
+        PATTERNS
+            .iter()
+            .map(|category| {
+                let mut calculator = Calculator {
+                    hands: self,
+                    category: *category,
+                    hits: HashMap::new(),
+                    keep: HashMap::new(),
+                    best: HashMap::new(),
+                };
+                Odds {
+                    category: *category,
+                    probability: probability(&mut calculator),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Enumerates the ways `n` dice can land, counting how many show each face from `index` on.
+fn collect_outcomes(
+    probabilities: &[f64],
+    index: usize,
+    left: usize,
+    hand: Hand,
+    probability: f64,
+    outcomes: &mut Vec<(Hand, f64)>,
+) {
+    if index == probabilities.len() - 1 || left == 0 {
+        let probability = probability * probabilities[index].powi(left as i32);
+        let hand = hand + ((left as u64) << (BITS * index as u32));
+        if probability > 0.0 {
+            outcomes.push((hand, probability));
+        }
+        return;
+    }
+    // Choosing which of the `left` dice show this face
+    let mut ways = 1.0;
+    for count in 0..=left {
+        collect_outcomes(
+            probabilities,
+            index + 1,
+            left - count,
+            hand + ((count as u64) << (BITS * index as u32)),
+            probability * ways * probabilities[index].powi(count as i32),
+            outcomes,
+        );
+        ways = ways * (left - count) as f64 / (count + 1) as f64;
+    }
+}
+
+/// Works out the odds for one category, remembering every position it has already seen.
+struct Calculator<'a> {
+    hands: &'a Hands<'a>,
+    category: Category,
+    hits: HashMap<Hand, bool>,
+    keep: HashMap<(Hand, usize), f64>,
+    best: HashMap<(Hand, usize), f64>,
+}
+
+impl Calculator<'_> {
+    fn size(&self, hand: Hand) -> usize {
+        (0..self.hands.die_spec.faces().len())
+            .map(|index| ((hand >> (BITS * index as u32)) & MASK) as usize)
+            .sum()
+    }
+
+    fn hits(&mut self, hand: Hand) -> bool {
+        if let Some(hit) = self.hits.get(&hand) {
+            return *hit;
+        }
+        let hit = self.size(hand) == self.hands.number_of_dice
+            && Score::new()
+                .potential(
+                    self.category,
+                    &Dice(self.hands.decode(hand)),
+                    self.hands.die_spec,
+                )
+                .is_some_and(|points| points > 0);
+        self.hits.insert(hand, hit);
+        hit
+    }
+
+    /// The chance of a hit when `held` is kept and the other dice are rolled.
+    fn keep(&mut self, held: Hand, rolls_left: usize) -> f64 {
+        if let Some(probability) = self.keep.get(&(held, rolls_left)) {
+            return *probability;
+        }
+        let rolled = self.hands.number_of_dice - self.size(held);
+        let hands = self.hands;
+        let probability = hands.outcomes[rolled]
+            .iter()
+            .map(|(outcome, probability)| probability * self.best(held + outcome, rolls_left - 1))
+            .sum();
+        self.keep.insert((held, rolls_left), probability);
+        probability
+    }
+
+    /// The chance of a hit with `hand` on the table, keeping the best dice for every roll left.
+    fn best(&mut self, hand: Hand, rolls_left: usize) -> f64 {
+        if self.hits(hand) {
+            return 1.0;
+        }
+        if rolls_left == 0 {
+            return 0.0;
+        }
+        if let Some(probability) = self.best.get(&(hand, rolls_left)) {
+            return *probability;
+        }
+        let probability = sub_hands(hand, self.hands.die_spec.faces().len())
+            .into_iter()
+            .map(|held| self.keep(held, rolls_left))
+            .fold(0.0, f64::max);
+        self.best.insert((hand, rolls_left), probability);
+        probability
+    }
+}
+
+/// Every hand that can be kept from `hand`, including none and all of it.
+fn sub_hands(hand: Hand, number_of_faces: usize) -> Vec<Hand> {
+    let mut sub_hands = vec![0];
+    for index in 0..number_of_faces {
+        let shift = BITS * index as u32;
+        let count = (hand >> shift) & MASK;
+        sub_hands = sub_hands
+            .into_iter()
+            .flat_map(|sub_hand| (0..=count).map(move |kept| sub_hand + (kept << shift)))
+            .collect();
+    }
+    sub_hands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn probability(odds: &[Odds], category: Category) -> f64 {
+        odds.iter()
+            .find(|odds| odds.category == category)
+            .unwrap()
+            .probability
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_outcomes_add_up_to_one() {
+        let die_spec = DieSpec::with_weighted_faces(vec![(1, 1), (2, 3), (5, 2)]).unwrap();
+        let hands = Hands::new(&die_spec, 5).unwrap();
+        for (n, outcomes) in hands.outcomes.iter().enumerate() {
+            assert_close(outcomes.iter().map(|(_, p)| p).sum(), 1.0);
+            assert!(outcomes
+                .iter()
+                .all(|(hand, _)| hands.decode(*hand).len() == n));
+        }
+    }
+
+    #[rstest]
+    // One roll left for the last die: it has to match
+    #[case(vec![3, 3, 3, 3], Category::Yahtzee, 1.0 / 6.0)]
+    // Any of the three other faces completes the straight
+    #[case(vec![2, 3, 4, 5], Category::LargeStraight, 2.0 / 6.0)]
+    #[case(vec![2, 3, 4, 5], Category::SmallStraight, 1.0)]
+    // Either 5-5, or a 3 and a 5 in any order
+    #[case(vec![3, 3, 5], Category::FullHouse, 3.0 / 36.0)]
+    #[case(vec![], Category::Yahtzee, 6.0 / 7776.0)]
+    fn test_single_roll_odds(
+        #[case] held: Vec<u32>,
+        #[case] category: Category,
+        #[case] expected: f64,
+    ) {
+        let odds = odds_keeping(&DieSpec::default(), 5, &held, 1).unwrap();
+        assert_close(probability(&odds, category), expected);
+    }
+
+    #[test]
+    fn test_yahtzee_odds_over_a_whole_turn() {
+        // The well known chance of a Yahtzee within three rolls when going for it
+        let odds = odds_keeping(&DieSpec::default(), 5, &[], 3).unwrap();
+        let yahtzee = probability(&odds, Category::Yahtzee);
+        assert!((yahtzee - 0.04603).abs() < 1e-4, "{}", yahtzee);
+    }
+
+    #[test]
+    fn test_more_rolls_never_lower_the_odds() {
+        let die_spec = DieSpec::default();
+        let dice = [1, 3, 3, 5, 6];
+        for rolls_left in 0..3 {
+            let fewer = odds_from(&die_spec, 5, &dice, rolls_left).unwrap();
+            let more = odds_from(&die_spec, 5, &dice, rolls_left + 1).unwrap();
+            for (fewer, more) in fewer.iter().zip(&more) {
+                assert!(fewer.probability <= more.probability + 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dice_that_already_score_are_certain() {
+        let odds = odds_from(&DieSpec::default(), 5, &[2, 2, 3, 3, 3], 0).unwrap();
+        assert_close(probability(&odds, Category::FullHouse), 1.0);
+        assert_close(probability(&odds, Category::ThreeOfAKind), 1.0);
+        assert_close(probability(&odds, Category::Yahtzee), 0.0);
+    }
+
+    #[test]
+    fn test_odds_follow_the_die() {
+        // A coin-like die always ends up with at least three of a kind
+        let die_spec = DieSpec::with_faces(vec![1, 2]).unwrap();
+        let odds = odds_keeping(&die_spec, 5, &[], 1).unwrap();
+        assert_close(probability(&odds, Category::ThreeOfAKind), 1.0);
+        assert_close(probability(&odds, Category::SmallStraight), 0.0);
+
+        assert_eq!(odds_keeping(&DieSpec::new(20).unwrap(), 5, &[], 3), None);
+        assert_eq!(odds_keeping(&DieSpec::default(), 5, &[7], 3), None);
+    }
+}
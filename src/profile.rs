@@ -0,0 +1,312 @@
+use crate::score::{Category, Score, YAHTZEE_BONUS, YAHTZEE_POINTS};
+use crate::storage::{read_json, write_json};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub(crate) const PROFILES_DIR: &str = "profiles";
+const HISTOGRAM_BUCKET_SIZE: u32 = 50;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct GameRecord {
+    pub played_at: DateTime<Utc>,
+    pub total_score: u32,
+    pub upper_bonus: bool,
+    pub categories: BTreeMap<Category, u32>,
+    #[serde(default)]
+    pub yahtzee_bonus: u32,
+}
+
+impl GameRecord {
+    pub fn new(score: &Score, played_at: DateTime<Utc>) -> Self {
+        Self {
+            played_at,
+            total_score: score.total_score(),
+            upper_bonus: score.upper_section.bonus.is_some(),
+            categories: Category::ALL
+                .iter()
+                .map(|category| (*category, score.get(*category).unwrap_or_default()))
+                .collect(),
+            yahtzee_bonus: score.lower_section.yahtzee_bonus,
+        }
+    }
+
+    /// The Yahtzee in the Yahtzee box and every one that earned a bonus. Games recorded before
+    /// the Joker rules added `YAHTZEE_POINTS` to the box for every Yahtzee instead.
+    pub fn yahtzees(&self) -> u32 {
+        self.categories
+            .get(&Category::Yahtzee)
+            .map_or(0, |score| score / YAHTZEE_POINTS)
+            + self.yahtzee_bonus / YAHTZEE_BONUS
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Profile {
+    pub name: String,
+    pub games: Vec<GameRecord>,
+}
+
+/// A named profile together with the file it is stored in.
+pub(crate) struct PlayerProfile {
+    path: PathBuf,
+    pub profile: Profile,
+}
+
+impl PlayerProfile {
+    /// Loads the profile called `name` from `dir`, starting a fresh one if it doesn't exist yet.
+    pub fn open(dir: &Path, name: &str) -> io::Result<Self> {
+        let path = dir.join(format!("{}.json", profile_file_stem(name)));
+        let profile = match read_json::<Profile>(&path)? {
+            Some(profile) if profile.name != name => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} holds the profile of {}", path.display(), profile.name),
+                ))
+            }
+            Some(profile) => Some(profile),
+            // Profiles used to be stored under a lossy file name, which several names could share
+            None => read_json::<Profile>(&dir.join(format!("{}.json", legacy_file_stem(name))))?
+                .filter(|profile| profile.name == name),
+        };
+        let profile = profile.unwrap_or_else(|| Profile {
+            name: name.to_string(),
+            games: vec![],
+        });
+        Ok(Self { path, profile })
+    }
+
+    pub fn record_game(&mut self, game: GameRecord) -> io::Result<()> {
+        self.profile.games.push(game);
+        write_json(&self.path, &self.profile)
+    }
+}
+
+/// Keeps lowercase letters, digits and dashes, and writes every other byte as `_` and two hex
+/// digits. No two names share a file, even on file systems that ignore case.
+fn profile_file_stem(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'0'..=b'9' | b'-' => char::from(byte).to_string(),
+            _ => format!("_{:02x}", byte),
+        })
+        .collect()
+}
+
+fn legacy_file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ProfileStats {
+    pub games_played: usize,
+    pub average_score: f64,
+    pub best_score: u32,
+    pub upper_bonus_rate: f64,
+    pub yahtzees: u32,
+    pub category_averages: Vec<(Category, f64)>,
+    /// Number of games per score bucket, keyed by the lower end of the bucket.
+    pub histogram: Vec<(u32, u64)>,
+    /// Average score per day on which games were played, oldest first.
+    pub trend: Vec<(NaiveDate, f64)>,
+}
+
+impl ProfileStats {
+    pub fn new(profile: &Profile) -> Self {
+        let games = &profile.games;
+        let games_played = games.len();
+        let average = |total: f64| {
+            if games_played == 0 {
+                0.0
+            } else {
+                total / games_played as f64
+            }
+        };
+
+        let category_averages = Category::ALL
+            .iter()
+            .map(|category| {
+                let total: u64 = games
+                    .iter()
+                    .map(|game| game.categories.get(category).copied().unwrap_or_default())
+                    .map(u64::from)
+                    .sum();
+                (*category, average(total as f64))
+            })
+            .collect();
+
+        let best_score = games.iter().map(|game| game.total_score).max().unwrap_or(0);
+        let histogram = (0..=best_score / HISTOGRAM_BUCKET_SIZE)
+            .map(|bucket| {
+                let count = games
+                    .iter()
+                    .filter(|game| game.total_score / HISTOGRAM_BUCKET_SIZE == bucket)
+                    .count();
+                (bucket * HISTOGRAM_BUCKET_SIZE, count as u64)
+            })
+            .collect();
+
+        let mut days: BTreeMap<NaiveDate, (u64, u32)> = BTreeMap::new();
+        for game in games {
+            let day = days.entry(game.played_at.naive_utc().date()).or_default();
+            day.0 += u64::from(game.total_score);
+            day.1 += 1;
+        }
+
+        Self {
+            games_played,
+            average_score: average(
+                games
+                    .iter()
+                    .map(|game| u64::from(game.total_score))
+                    .sum::<u64>() as f64,
+            ),
+            best_score,
+            upper_bonus_rate: average(games.iter().filter(|game| game.upper_bonus).count() as f64),
+            yahtzees: games.iter().map(GameRecord::yahtzees).sum(),
+            category_averages,
+            histogram,
+            trend: days
+                .into_iter()
+                .map(|(date, (total, count))| (date, total as f64 / f64::from(count)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::temp_path;
+    use chrono::TimeZone;
+    use std::fs;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2022, 1, day).unwrap()
+    }
+
+    fn game(day: u32, total_score: u32, upper_bonus: bool, yahtzee: u32) -> GameRecord {
+        let mut categories: BTreeMap<Category, u32> = Category::ALL
+            .iter()
+            .map(|category| (*category, 0))
+            .collect();
+        categories.insert(Category::Chance, total_score - yahtzee);
+        categories.insert(Category::Yahtzee, yahtzee);
+        GameRecord {
+            played_at: Utc.from_utc_datetime(&date(day).and_hms_opt(12, 0, 0).unwrap()),
+            total_score,
+            upper_bonus,
+            categories,
+            yahtzee_bonus: 0,
+        }
+    }
+
+    #[test]
+    fn test_game_record_from_score() {
+        let mut score = Score::new();
+        score.lower_section.yahtzee = Some(50);
+        score.lower_section.yahtzee_bonus = 200;
+        score.upper_section.sixes = Some(24);
+        let record = GameRecord::new(&score, Utc::now());
+        assert_eq!(record.total_score, 274);
+        assert_eq!(record.yahtzees(), 3);
+        assert!(!record.upper_bonus);
+        assert_eq!(record.categories.len(), 13);
+        assert_eq!(record.categories[&Category::Sixes], 24);
+    }
+
+    #[test]
+    fn test_stats_of_empty_profile() {
+        let stats = ProfileStats::new(&Profile::default());
+        assert_eq!(stats.games_played, 0);
+        assert_eq!(stats.average_score, 0.0);
+        assert_eq!(stats.best_score, 0);
+        assert_eq!(stats.histogram, vec![(0, 0)]);
+        assert!(stats.trend.is_empty());
+    }
+
+    #[test]
+    fn test_stats_aggregate_games() {
+        let profile = Profile {
+            name: "ada".to_string(),
+            games: vec![
+                game(1, 120, false, 0),
+                game(1, 260, true, 100),
+                game(3, 180, true, 50),
+            ],
+        };
+        let stats = ProfileStats::new(&profile);
+        assert_eq!(stats.games_played, 3);
+        assert_eq!(stats.average_score, 560.0 / 3.0);
+        assert_eq!(stats.best_score, 260);
+        assert_eq!(stats.upper_bonus_rate, 2.0 / 3.0);
+        assert_eq!(stats.yahtzees, 3);
+        assert!(stats.category_averages.contains(&(Category::Yahtzee, 50.0)));
+        assert_eq!(
+            stats.histogram,
+            vec![(0, 0), (50, 0), (100, 1), (150, 1), (200, 0), (250, 1)]
+        );
+        assert_eq!(stats.trend, vec![(date(1), 190.0), (date(3), 180.0),]);
+    }
+
+    #[test]
+    fn test_profile_is_persisted() {
+        let dir = temp_path(PROFILES_DIR);
+        let mut player = PlayerProfile::open(&dir, "Ada L.").unwrap();
+        assert!(player.profile.games.is_empty());
+        player.record_game(game(2, 200, true, 0)).unwrap();
+        assert!(dir.join("_41da_20_4c_2e.json").exists());
+
+        let reopened = PlayerProfile::open(&dir, "Ada L.").unwrap();
+        assert_eq!(reopened.profile.name, "Ada L.");
+        assert_eq!(reopened.profile.games, vec![game(2, 200, true, 0)]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_similar_names_get_their_own_profiles() {
+        let dir = temp_path(PROFILES_DIR);
+        for name in ["Ada", "ada", "Zoë", "Zoé", "a.b", "a_b"] {
+            let mut player = PlayerProfile::open(&dir, name).unwrap();
+            assert!(
+                player.profile.games.is_empty(),
+                "{} got a used profile",
+                name
+            );
+            player.record_game(game(2, 200, true, 0)).unwrap();
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 6);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_profiles_under_the_old_file_names_are_found() {
+        let dir = temp_path(PROFILES_DIR);
+        let profile = Profile {
+            name: "Ada L.".to_string(),
+            games: vec![game(2, 200, true, 0)],
+        };
+        write_json(&dir.join("ada_l_.json"), &profile).unwrap();
+        assert_eq!(
+            PlayerProfile::open(&dir, "Ada L.").unwrap().profile,
+            profile
+        );
+        // Only by the player who wrote it
+        assert!(PlayerProfile::open(&dir, "ada l_")
+            .unwrap()
+            .profile
+            .games
+            .is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
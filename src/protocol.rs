@@ -0,0 +1,279 @@
+//! The network protocol: every message is a single JSON object on its own line, tagged with its
+//! `type`. A server hosts any number of tables, which `list_games` shows. A client takes a seat
+//! at one with `create`, `join` or `rejoin`, e.g.
+//!
+//! ```text
+//! {"type":"join","name":"ada"}
+//! ```
+//!
+//! to be seated at any table still waiting for players, and is answered with a `welcome` holding
+//! its table, seat, session token and the whole game so far. From then on it receives every game
+//! event at its table as it happens, e.g.
+//!
+//! ```text
+//! {"type":"event","event":"rolled","seat":0,"dice":[2,2,5,6,2],"rolled":[0,1,2,3,4],"rolls_left":2}
+//! ```
+//!
+//! Anyone can also `watch` a table, receiving the game so far followed by its events, without
+//! being able to play.
+//!
+//! The game starts once every seat is taken and everyone sent `ready`, after which the players
+//! take turns with `hold`, `roll` and `score` messages. A client whose connection dropped gets its
+//! seat back by sending its token in a `rejoin`. Anything the server can't accept is answered with
+//! an `error` for the client that sent it.
+
+use crate::dice::DieSpec;
+use crate::game::{GameEvent, GameSnapshot};
+use crate::score::Category;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Read, Write};
+use thiserror::Error;
+
+/// The port the server listens on unless told otherwise.
+pub(crate) const DEFAULT_PORT: u16 = 7878;
+
+/// The port browsers connect to, if the server lets them in, and the one `web/table.html` tries.
+pub(crate) const DEFAULT_WS_PORT: u16 = 7879;
+
+/// The most seats a table can have.
+pub(crate) const MAX_SEATS: usize = 8;
+
+/// The longest message a peer may send, so it can't make the other side buffer without end.
+pub(crate) const MAX_LINE: usize = 1 << 20;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ClientMessage {
+    ListGames,
+    /// Opens a table playing with `die`, written like `--die`, and seats the sender at it.
+    Create {
+        name: String,
+        die: String,
+        seats: usize,
+    },
+    /// Takes a seat at `table`, or at any table waiting for players if there is none.
+    Join {
+        name: String,
+        #[serde(default)]
+        table: Option<usize>,
+    },
+    Rejoin {
+        token: String,
+    },
+    /// Follows `table` without playing, or any game in progress if there is none.
+    Watch {
+        #[serde(default)]
+        table: Option<usize>,
+    },
+    Ready,
+    /// Fills the free seats at the sender's table with bots.
+    AddBots,
+    /// Which dice to keep for the next roll, one flag per die.
+    Hold {
+        held: Vec<bool>,
+    },
+    Roll,
+    Score {
+        category: Category,
+    },
+    Chat {
+        text: String,
+    },
+    Leave,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ServerMessage {
+    Games {
+        games: Vec<TableSummary>,
+    },
+    Welcome {
+        table: usize,
+        seat: usize,
+        /// Gets the seat back with a `rejoin` if the connection drops.
+        token: String,
+        game: GameSnapshot,
+    },
+    /// Sent to a spectator, who then receives the game events like the players.
+    Watching {
+        table: usize,
+        game: GameSnapshot,
+    },
+    Event(GameEvent),
+    Chat {
+        from: String,
+        text: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// A table as listed in the lobby.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TableSummary {
+    pub table: usize,
+    pub die_spec: DieSpec,
+    pub number_of_seats: usize,
+    /// The players at the table right now.
+    pub players: Vec<String>,
+    pub started: bool,
+    pub over: bool,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum ProtocolError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid message: {0}")]
+    Invalid(#[from] serde_json::Error),
+}
+
+pub(crate) fn write_message<W: Write, M: Serialize>(writer: &mut W, message: &M) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    // Written in one go, so messages from several threads never interleave
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// Reads the next message, or `None` once the other side closed the connection. A line longer
+/// than `MAX_LINE` is skipped and reported as invalid.
+pub(crate) fn read_message<R: BufRead, M: DeserializeOwned>(
+    reader: &mut R,
+) -> Result<Option<M>, ProtocolError> {
+    let mut line = vec![];
+    loop {
+        line.clear();
+        let read = reader
+            .by_ref()
+            .take(MAX_LINE as u64 + 1)
+            .read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.len() > MAX_LINE && line.last() != Some(&b'\n') {
+            skip_line(reader)?;
+            return Err(line_too_long());
+        }
+        if let Some(message) = parse_message(&line)? {
+            return Ok(Some(message));
+        }
+    }
+}
+
+/// Parses a line as read by `read_message`, which is `None` if the line is blank.
+pub(crate) fn parse_message<M: DeserializeOwned>(line: &[u8]) -> Result<Option<M>, ProtocolError> {
+    // Blank lines are tolerated, which makes talking to the server by hand easier
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(line)?))
+}
+
+pub(crate) fn line_too_long() -> ProtocolError {
+    ProtocolError::Invalid(serde::de::Error::custom(format!(
+        "longer than {} bytes",
+        MAX_LINE
+    )))
+}
+
+/// Throws away the rest of the line without keeping it in memory.
+fn skip_line<R: BufRead>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        match buffer.iter().position(|byte| *byte == b'\n') {
+            Some(end) => {
+                reader.consume(end + 1);
+                return Ok(());
+            }
+            None => {
+                let length = buffer.len();
+                reader.consume(length);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_messages_are_json_lines() {
+        let mut buffer = vec![];
+        write_message(&mut buffer, &ClientMessage::Roll).unwrap();
+        write_message(
+            &mut buffer,
+            &ClientMessage::Score {
+                category: Category::Chance,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.clone()).unwrap(),
+            "{\"type\":\"roll\"}\n{\"type\":\"score\",\"category\":\"Chance\"}\n"
+        );
+
+        let mut reader = Cursor::new(buffer);
+        let roll: Option<ClientMessage> = read_message(&mut reader).unwrap();
+        assert_eq!(roll, Some(ClientMessage::Roll));
+        let score: Option<ClientMessage> = read_message(&mut reader).unwrap();
+        assert_eq!(
+            score,
+            Some(ClientMessage::Score {
+                category: Category::Chance
+            })
+        );
+        let end: Option<ClientMessage> = read_message(&mut reader).unwrap();
+        assert_eq!(end, None);
+    }
+
+    #[test]
+    fn test_overlong_lines_are_skipped() {
+        let mut input = vec![b' '; MAX_LINE + 10];
+        input.extend_from_slice(b"\n{\"type\":\"roll\"}\n");
+        let mut reader = Cursor::new(input);
+        let result: Result<Option<ClientMessage>, _> = read_message(&mut reader);
+        assert!(matches!(result, Err(ProtocolError::Invalid(_))));
+        let roll: Option<ClientMessage> = read_message(&mut reader).unwrap();
+        assert_eq!(roll, Some(ClientMessage::Roll));
+    }
+
+    #[test]
+    fn test_game_events_are_flattened_into_the_message() {
+        let message = ServerMessage::Event(GameEvent::TurnStarted { seat: 1 });
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(json, r#"{"type":"event","event":"turn_started","seat":1}"#);
+        assert_eq!(
+            serde_json::from_str::<ServerMessage>(&json).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn test_joining_any_table_needs_no_table() {
+        let mut reader = Cursor::new(r#"{"type":"join","name":"ada"}"#);
+        let join: Option<ClientMessage> = read_message(&mut reader).unwrap();
+        assert_eq!(
+            join,
+            Some(ClientMessage::Join {
+                name: "ada".to_string(),
+                table: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_lines_are_errors() {
+        let mut reader = Cursor::new("\n{\"type\":\"fly\"}\n");
+        let result: Result<Option<ClientMessage>, _> = read_message(&mut reader);
+        assert!(matches!(result, Err(ProtocolError::Invalid(_))));
+    }
+}
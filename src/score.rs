@@ -1,16 +1,167 @@
-use crate::dice::Dice;
+use crate::dice::{Dice, DieSpec};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug)]
+pub(crate) const UPPER_BONUS_THRESHOLD: u32 = 63;
+pub(crate) const UPPER_BONUS: u32 = 35;
+pub(crate) const FULL_HOUSE_POINTS: u32 = 25;
+pub(crate) const SMALL_STRAIGHT_LENGTH: usize = 4;
+pub(crate) const SMALL_STRAIGHT_POINTS: u32 = 30;
+pub(crate) const LARGE_STRAIGHT_LENGTH: usize = 5;
+pub(crate) const LARGE_STRAIGHT_POINTS: u32 = 40;
+pub(crate) const YAHTZEE_POINTS: u32 = 50;
+pub(crate) const YAHTZEE_BONUS: u32 = 100;
+
+#[derive(Debug, PartialEq)]
 pub(crate) enum ScoreError {
     AlreadySet,
+    /// A Joker has to be scored in one of `Score::joker_categories`.
+    JokerRule,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub(crate) enum Category {
+    Aces,
+    Twos,
+    Threes,
+    Fours,
+    Fives,
+    Sixes,
+    ThreeOfAKind,
+    FourOfAKind,
+    FullHouse,
+    SmallStraight,
+    LargeStraight,
+    Yahtzee,
+    Chance,
+}
+
+impl Category {
+    pub const ALL: [Category; 13] = [
+        Category::Aces,
+        Category::Twos,
+        Category::Threes,
+        Category::Fours,
+        Category::Fives,
+        Category::Sixes,
+        Category::ThreeOfAKind,
+        Category::FourOfAKind,
+        Category::FullHouse,
+        Category::SmallStraight,
+        Category::LargeStraight,
+        Category::Yahtzee,
+        Category::Chance,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Aces => "Aces",
+            Category::Twos => "Twos",
+            Category::Threes => "Threes",
+            Category::Fours => "Fours",
+            Category::Fives => "Fives",
+            Category::Sixes => "Sixes",
+            Category::ThreeOfAKind => "Three of a kind",
+            Category::FourOfAKind => "Four of a kind",
+            Category::FullHouse => "Full house",
+            Category::SmallStraight => "Small straight",
+            Category::LargeStraight => "Large straight",
+            Category::Yahtzee => "Yahtzee",
+            Category::Chance => "Chance",
+        }
+    }
+
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Category::Aces => "1s",
+            Category::Twos => "2s",
+            Category::Threes => "3s",
+            Category::Fours => "4s",
+            Category::Fives => "5s",
+            Category::Sixes => "6s",
+            Category::ThreeOfAKind => "3K",
+            Category::FourOfAKind => "4K",
+            Category::FullHouse => "FH",
+            Category::SmallStraight => "SS",
+            Category::LargeStraight => "LS",
+            Category::Yahtzee => "Y",
+            Category::Chance => "C",
+        }
+    }
+
+    /// The face counted by an upper section category.
+    pub fn face(&self) -> Option<u32> {
+        match self {
+            Category::Aces => Some(1),
+            Category::Twos => Some(2),
+            Category::Threes => Some(3),
+            Category::Fours => Some(4),
+            Category::Fives => Some(5),
+            Category::Sixes => Some(6),
+            _ => None,
+        }
+    }
+
+    /// Explains how the category is scored when playing with the given die.
+    pub fn rule(&self, die_spec: &DieSpec) -> String {
+        let straight = |length: usize, points: u32| {
+            let runs: Vec<String> = die_spec
+                .faces()
+                .windows(length)
+                .map(|run| run.iter().map(u32::to_string).collect::<Vec<_>>().join("-"))
+                .collect();
+            let examples = match runs.as_slice() {
+                [] => "which this die can't roll".to_string(),
+                [run] => format!("({})", run),
+                [first, second] => format!("({} or {})", first, second),
+                [first, second, third] => format!("({}, {} or {})", first, second, third),
+                [first, ..] => format!("(e.g. {})", first),
+            };
+            format!(
+                "{} points for {} faces in a row {}",
+                points, length, examples
+            )
+        };
+        match self {
+            Category::ThreeOfAKind => {
+                "Sum of all dice if at least three show the same face".to_string()
+            }
+            Category::FourOfAKind => {
+                "Sum of all dice if at least four show the same face".to_string()
+            }
+            Category::FullHouse => format!(
+                "{} points for three of one face and two of another",
+                FULL_HOUSE_POINTS
+            ),
+            Category::SmallStraight => straight(SMALL_STRAIGHT_LENGTH, SMALL_STRAIGHT_POINTS),
+            Category::LargeStraight => straight(LARGE_STRAIGHT_LENGTH, LARGE_STRAIGHT_POINTS),
+            Category::Yahtzee => format!(
+                "{} points for all dice showing the same face",
+                YAHTZEE_POINTS
+            ),
+            Category::Chance => "Sum of all dice".to_string(),
+            upper => {
+                let face = upper.face().unwrap_or_default();
+                if die_spec.faces().contains(&face) {
+                    format!("Sum of all dice showing {}", face)
+                } else {
+                    format!(
+                        "Sum of all dice showing {}, which this die can't roll",
+                        face
+                    )
+                }
+            }
+        }
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub(crate) struct Score {
     pub upper_section: ScoreUpperSection,
     pub lower_section: ScoreLowerSection,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub(crate) struct ScoreUpperSection {
     pub aces: Option<u32>,
     pub twos: Option<u32>,
@@ -21,6 +172,7 @@ pub(crate) struct ScoreUpperSection {
     pub bonus: Option<u32>,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub(crate) struct ScoreLowerSection {
     pub three_of_a_kind: Option<u32>,
     pub four_of_a_kind: Option<u32>,
@@ -29,6 +181,9 @@ pub(crate) struct ScoreLowerSection {
     pub large_straight: Option<u32>,
     pub yahtzee: Option<u32>,
     pub chance: Option<u32>,
+    /// Earned by every further Yahtzee while the Yahtzee box holds points.
+    #[serde(default)]
+    pub yahtzee_bonus: u32,
 }
 
 impl ScoreUpperSection {
@@ -99,7 +254,7 @@ impl ScoreUpperSection {
     }
 
     pub fn score_without_bonus(&self) -> u32 {
-        vec![
+        [
             self.aces,
             self.twos,
             self.threes,
@@ -109,17 +264,18 @@ impl ScoreUpperSection {
         ]
         .iter()
         .map(|value| value.unwrap_or_default())
-        .sum()
+        .fold(0, u32::saturating_add)
     }
 
     pub fn total_score(&self) -> u32 {
-        self.score_without_bonus() + self.bonus.unwrap_or_default()
+        self.score_without_bonus()
+            .saturating_add(self.bonus.unwrap_or_default())
     }
 
     fn check_and_set_bonus(&mut self) {
         let score_without_bonus = self.score_without_bonus();
-        if score_without_bonus >= 63 {
-            self.bonus = Some(35)
+        if score_without_bonus >= UPPER_BONUS_THRESHOLD {
+            self.bonus = Some(UPPER_BONUS)
         };
     }
 }
@@ -134,10 +290,11 @@ impl ScoreLowerSection {
             large_straight: None,
             yahtzee: None,
             chance: None,
+            yahtzee_bonus: 0,
         }
     }
     pub fn total_score(&self) -> u32 {
-        vec![
+        [
             self.three_of_a_kind,
             self.four_of_a_kind,
             self.full_house,
@@ -145,10 +302,11 @@ impl ScoreLowerSection {
             self.large_straight,
             self.yahtzee,
             self.chance,
+            Some(self.yahtzee_bonus),
         ]
         .iter()
         .map(|value| value.unwrap_or_default())
-        .sum()
+        .fold(0, u32::saturating_add)
     }
 
     pub fn set_three_of_a_kind(&mut self, dice_roll: &Dice) -> Result<(), ScoreError> {
@@ -157,7 +315,7 @@ impl ScoreLowerSection {
         }
         let frequencies = get_dice_frequencies(dice_roll.0.as_slice());
         let value = if frequencies.values().any(|count| *count >= 3) {
-            dice_roll.0.as_slice().iter().sum()
+            sum_of_dice(dice_roll)
         } else {
             0
         };
@@ -171,7 +329,7 @@ impl ScoreLowerSection {
         }
         let frequencies = get_dice_frequencies(dice_roll.0.as_slice());
         let value = if frequencies.values().any(|count| *count >= 4) {
-            dice_roll.0.as_slice().iter().sum()
+            sum_of_dice(dice_roll)
         } else {
             0
         };
@@ -187,7 +345,7 @@ impl ScoreLowerSection {
         let value = if frequencies.values().any(|count| *count == 3)
             && frequencies.values().any(|count| *count == 2)
         {
-            25
+            FULL_HOUSE_POINTS
         } else {
             0
         };
@@ -195,12 +353,20 @@ impl ScoreLowerSection {
         Ok(())
     }
 
-    pub fn set_small_straight(&mut self, dice_roll: &Dice) -> Result<(), ScoreError> {
+    pub fn set_small_straight(
+        &mut self,
+        dice_roll: &Dice,
+        die_spec: &DieSpec,
+    ) -> Result<(), ScoreError> {
         if self.small_straight.is_some() {
             return Err(ScoreError::AlreadySet);
         }
-        let value = if has_at_least_n_consecutive_numbers(dice_roll.0.clone(), 4) {
-            30
+        let value = if has_at_least_n_consecutive_numbers(
+            &dice_roll.0,
+            die_spec.faces(),
+            SMALL_STRAIGHT_LENGTH,
+        ) {
+            SMALL_STRAIGHT_POINTS
         } else {
             0
         };
@@ -208,12 +374,20 @@ impl ScoreLowerSection {
         Ok(())
     }
 
-    pub fn set_large_straight(&mut self, dice_roll: &Dice) -> Result<(), ScoreError> {
+    pub fn set_large_straight(
+        &mut self,
+        dice_roll: &Dice,
+        die_spec: &DieSpec,
+    ) -> Result<(), ScoreError> {
         if self.large_straight.is_some() {
             return Err(ScoreError::AlreadySet);
         }
-        let value = if has_at_least_n_consecutive_numbers(dice_roll.0.clone(), 5) {
-            40
+        let value = if has_at_least_n_consecutive_numbers(
+            &dice_roll.0,
+            die_spec.faces(),
+            LARGE_STRAIGHT_LENGTH,
+        ) {
+            LARGE_STRAIGHT_POINTS
         } else {
             0
         };
@@ -222,12 +396,15 @@ impl ScoreLowerSection {
     }
 
     pub fn set_yahtzee(&mut self, dice_roll: &Dice) -> Result<(), ScoreError> {
-        let value = if dice_roll.0.iter().collect::<HashSet<_>>().len() == 1 {
-            self.yahtzee.map_or_else(|| Some(50), |v| Some(v + 50))
+        if self.yahtzee.is_some() {
+            return Err(ScoreError::AlreadySet);
+        }
+        let value = if is_yahtzee(dice_roll) {
+            YAHTZEE_POINTS
         } else {
-            Some(0)
+            0
         };
-        self.yahtzee = value;
+        self.yahtzee = Some(value);
         Ok(())
     }
 
@@ -235,11 +412,23 @@ impl ScoreLowerSection {
         if self.chance.is_some() {
             return Err(ScoreError::AlreadySet);
         }
-        self.chance = Some(dice_roll.0.as_slice().iter().sum());
+        self.chance = Some(sum_of_dice(dice_roll));
         Ok(())
     }
 }
 
+/// Never overflows, not even for dice with huge faces.
+fn sum_of_dice(dice_roll: &Dice) -> u32 {
+    dice_roll
+        .0
+        .iter()
+        .fold(0, |sum, die| sum.saturating_add(*die))
+}
+
+fn is_yahtzee(dice_roll: &Dice) -> bool {
+    dice_roll.0.iter().collect::<HashSet<_>>().len() == 1
+}
+
 fn get_dice_frequencies(dice_vec: &[u32]) -> HashMap<u32, i32> {
     dice_vec.iter().fold(HashMap::new(), |mut map, value| {
         *map.entry(*value).or_insert(0) += 1;
@@ -247,23 +436,19 @@ fn get_dice_frequencies(dice_vec: &[u32]) -> HashMap<u32, i32> {
     })
 }
 
-fn has_at_least_n_consecutive_numbers(mut rolls: Vec<u32>, n: u32) -> bool {
-    rolls.sort_unstable();
-    let n_consecutive_increases = rolls.windows(2).fold(1, |mut consecutive_incr, window| {
-        if let (Some(a), Some(b)) = (window.first(), window.last()) {
-            if *a + 1 == *b {
-                // if two consecutive elements
-                consecutive_incr += 1;
-            } else if *a == *b {
-                //     do nothing
-            } else if consecutive_incr < n {
-                // Break the streak if we haven't reached our minimum yet
-                consecutive_incr = 1
-            }
+/// Checks whether the roll contains `n` faces that follow each other in the die's face order.
+/// For standard dice this means consecutive numbers, custom dice use their own face order.
+fn has_at_least_n_consecutive_numbers(rolls: &[u32], faces: &[u32], n: usize) -> bool {
+    let rolled_faces: HashSet<&u32> = rolls.iter().collect();
+    let mut streak = 0;
+    faces.iter().any(|face| {
+        if rolled_faces.contains(face) {
+            streak += 1;
+        } else {
+            streak = 0;
         }
-        consecutive_incr
-    });
-    n <= n_consecutive_increases
+        streak >= n
+    })
 }
 
 impl Score {
@@ -275,14 +460,138 @@ impl Score {
     }
 
     pub fn total_score(&self) -> u32 {
-        self.upper_section.total_score() + self.lower_section.total_score()
+        self.upper_section
+            .total_score()
+            .saturating_add(self.lower_section.total_score())
+    }
+
+    pub fn get(&self, category: Category) -> Option<u32> {
+        let upper = &self.upper_section;
+        let lower = &self.lower_section;
+        match category {
+            Category::Aces => upper.aces,
+            Category::Twos => upper.twos,
+            Category::Threes => upper.threes,
+            Category::Fours => upper.fours,
+            Category::Fives => upper.fives,
+            Category::Sixes => upper.sixes,
+            Category::ThreeOfAKind => lower.three_of_a_kind,
+            Category::FourOfAKind => lower.four_of_a_kind,
+            Category::FullHouse => lower.full_house,
+            Category::SmallStraight => lower.small_straight,
+            Category::LargeStraight => lower.large_straight,
+            Category::Yahtzee => lower.yahtzee,
+            Category::Chance => lower.chance,
+        }
+    }
+
+    pub fn set(
+        &mut self,
+        category: Category,
+        dice_roll: &Dice,
+        die_spec: &DieSpec,
+    ) -> Result<(), ScoreError> {
+        if self.get(category).is_some() {
+            return Err(ScoreError::AlreadySet);
+        }
+        if self.is_joker(dice_roll) {
+            if !self.joker_categories(dice_roll).contains(&category) {
+                return Err(ScoreError::JokerRule);
+            }
+            let lower = &mut self.lower_section;
+            if lower.yahtzee != Some(0) {
+                lower.yahtzee_bonus = lower.yahtzee_bonus.saturating_add(YAHTZEE_BONUS);
+            }
+            // A Joker counts as a full house and as either straight
+            let joker_box = match category {
+                Category::FullHouse => Some((&mut lower.full_house, FULL_HOUSE_POINTS)),
+                Category::SmallStraight => Some((&mut lower.small_straight, SMALL_STRAIGHT_POINTS)),
+                Category::LargeStraight => Some((&mut lower.large_straight, LARGE_STRAIGHT_POINTS)),
+                _ => None,
+            };
+            if let Some((value, points)) = joker_box {
+                *value = Some(points);
+                return Ok(());
+            }
+        }
+        let upper = &mut self.upper_section;
+        let lower = &mut self.lower_section;
+        match category {
+            Category::Aces => upper.set_aces(dice_roll),
+            Category::Twos => upper.set_twos(dice_roll),
+            Category::Threes => upper.set_threes(dice_roll),
+            Category::Fours => upper.set_fours(dice_roll),
+            Category::Fives => upper.set_fives(dice_roll),
+            Category::Sixes => upper.set_sixes(dice_roll),
+            Category::ThreeOfAKind => lower.set_three_of_a_kind(dice_roll),
+            Category::FourOfAKind => lower.set_four_of_a_kind(dice_roll),
+            Category::FullHouse => lower.set_full_house(dice_roll),
+            Category::SmallStraight => lower.set_small_straight(dice_roll, die_spec),
+            Category::LargeStraight => lower.set_large_straight(dice_roll, die_spec),
+            Category::Yahtzee => lower.set_yahtzee(dice_roll),
+            Category::Chance => lower.set_chance(dice_roll),
+        }
+    }
+
+    /// The points scoring `category` with the given dice would add to its box, or `None` if the
+    /// box can't be scored anymore.
+    pub fn potential(
+        &self,
+        category: Category,
+        dice_roll: &Dice,
+        die_spec: &DieSpec,
+    ) -> Option<u32> {
+        let mut score = self.clone();
+        score.set(category, dice_roll, die_spec).ok()?;
+        Some(score.get(category).unwrap_or_default() - self.get(category).unwrap_or_default())
+    }
+
+    /// Whether the dice are a Yahtzee that can't go in the Yahtzee box anymore. Such a Joker earns
+    /// a bonus, unless the box was scratched, and is scored in one of the `joker_categories`.
+    pub fn is_joker(&self, dice_roll: &Dice) -> bool {
+        self.lower_section.yahtzee.is_some() && is_yahtzee(dice_roll)
+    }
+
+    /// The upper box of the Joker's face if that is open, otherwise any open lower box, and only
+    /// once those are full any open upper box.
+    pub fn joker_categories(&self, dice_roll: &Dice) -> Vec<Category> {
+        let open = |categories: &[Category]| -> Vec<Category> {
+            categories
+                .iter()
+                .copied()
+                .filter(|category| self.get(*category).is_none())
+                .collect()
+        };
+        let face = dice_roll.0.first().copied();
+        let face_box = open(&Category::ALL[..6])
+            .into_iter()
+            .find(|category| category.face() == face);
+        if let Some(category) = face_box {
+            return vec![category];
+        }
+        let lower = open(&Category::ALL[6..]);
+        if lower.is_empty() {
+            open(&Category::ALL[..6])
+        } else {
+            lower
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        Category::ALL
+            .iter()
+            .all(|category| self.get(*category).is_some())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
     use rstest::rstest;
+    use std::collections::HashSet;
 
     #[rstest]
     #[case(vec![2, 2, 1, 1, 1], 7)]
@@ -358,7 +667,9 @@ mod tests {
     #[case(vec![2, 3, 3, 4, 5])]
     fn test_small_straight_accepts_valid_cases(#[case] dice_input: Vec<u32>) {
         let mut score = ScoreLowerSection::new();
-        score.set_small_straight(&Dice(dice_input)).unwrap();
+        score
+            .set_small_straight(&Dice(dice_input), &DieSpec::default())
+            .unwrap();
         assert_eq!(score.small_straight, Some(30));
         assert_eq!(score.total_score(), 30);
     }
@@ -371,7 +682,9 @@ mod tests {
     #[case(vec![6, 5, 4, 2, 1])]
     fn test_small_straight_sets_zero_invalid_cases(#[case] dice_input: Vec<u32>) {
         let mut score = ScoreLowerSection::new();
-        score.set_small_straight(&Dice(dice_input)).unwrap();
+        score
+            .set_small_straight(&Dice(dice_input), &DieSpec::default())
+            .unwrap();
         assert_eq!(score.small_straight, Some(0));
         assert_eq!(score.total_score(), 0);
     }
@@ -381,7 +694,9 @@ mod tests {
     #[case(vec![2, 3, 4, 5, 6])]
     fn test_large_straight_accepts_valid_cases(#[case] dice_input: Vec<u32>) {
         let mut score = ScoreLowerSection::new();
-        score.set_large_straight(&Dice(dice_input)).unwrap();
+        score
+            .set_large_straight(&Dice(dice_input), &DieSpec::default())
+            .unwrap();
         assert_eq!(score.large_straight, Some(40));
         assert_eq!(score.total_score(), 40);
     }
@@ -394,11 +709,58 @@ mod tests {
     #[case(vec![6, 5, 4, 2, 1])]
     fn test_large_straight_sets_zero_invalid_cases(#[case] dice_input: Vec<u32>) {
         let mut score = ScoreLowerSection::new();
-        score.set_large_straight(&Dice(dice_input)).unwrap();
+        score
+            .set_large_straight(&Dice(dice_input), &DieSpec::default())
+            .unwrap();
         assert_eq!(score.large_straight, Some(0));
         assert_eq!(score.total_score(), 0);
     }
 
+    #[rstest]
+    #[case("d8", vec![5, 6, 7, 8, 1], 30, 0)]
+    #[case("d8", vec![4, 8, 6, 5, 7], 30, 40)]
+    #[case("d8", vec![1, 3, 5, 7, 8], 0, 0)]
+    #[case("d20", vec![17, 18, 19, 20, 20], 30, 0)]
+    #[case("d20", vec![16, 17, 18, 19, 20], 30, 40)]
+    #[case("2,4,6,8,10", vec![2, 4, 6, 8, 8], 30, 0)]
+    #[case("2,4,6,8,10", vec![10, 8, 6, 4, 2], 30, 40)]
+    #[case("2,4,6,8,10", vec![2, 3, 4, 5, 6], 0, 0)]
+    #[case("1:1,2:1,3:1,4:1,5:1,6:5", vec![3, 4, 5, 6, 6], 30, 0)]
+    fn test_straights_follow_die_faces(
+        #[case] die_spec: &str,
+        #[case] dice_input: Vec<u32>,
+        #[case] expected_small_straight: u32,
+        #[case] expected_large_straight: u32,
+    ) {
+        let die_spec: DieSpec = die_spec.parse().unwrap();
+        let dice = Dice(dice_input);
+        let mut score = ScoreLowerSection::new();
+        score.set_small_straight(&dice, &die_spec).unwrap();
+        score.set_large_straight(&dice, &die_spec).unwrap();
+        assert_eq!(score.small_straight, Some(expected_small_straight));
+        assert_eq!(score.large_straight, Some(expected_large_straight));
+    }
+
+    #[rstest]
+    #[case(vec![20, 20, 20, 1, 2], 63, 0, 0)]
+    #[case(vec![8, 8, 8, 8, 7], 39, 39, 0)]
+    #[case(vec![12, 12, 12, 7, 7], 50, 0, 25)]
+    fn test_frequency_checks_work_for_large_faces(
+        #[case] dice_input: Vec<u32>,
+        #[case] expected_three_of_a_kind: u32,
+        #[case] expected_four_of_a_kind: u32,
+        #[case] expected_full_house: u32,
+    ) {
+        let dice = Dice(dice_input);
+        let mut score = ScoreLowerSection::new();
+        score.set_three_of_a_kind(&dice).unwrap();
+        score.set_four_of_a_kind(&dice).unwrap();
+        score.set_full_house(&dice).unwrap();
+        assert_eq!(score.three_of_a_kind, Some(expected_three_of_a_kind));
+        assert_eq!(score.four_of_a_kind, Some(expected_four_of_a_kind));
+        assert_eq!(score.full_house, Some(expected_full_house));
+    }
+
     #[rstest]
     #[case(vec![2, 2, 1, 1, 1])]
     #[case(vec![2, 2, 3, 3, 3])]
@@ -454,16 +816,113 @@ mod tests {
     }
 
     #[test]
-    fn test_yahtzee_multiple_works() {
+    fn test_yahtzee_box_is_scored_once() {
         let mut score = ScoreLowerSection::new();
-        let dice_input = Dice(vec![1, 1, 1, 1, 1]);
-        score.set_yahtzee(&dice_input).unwrap();
+        score.set_yahtzee(&Dice(vec![4, 4, 4, 4, 4])).unwrap();
+        assert!(score.set_yahtzee(&Dice(vec![4, 4, 4, 4, 4])).is_err());
+        assert!(score.set_yahtzee(&Dice(vec![4, 4, 4, 4, 1])).is_err());
         assert_eq!(score.yahtzee, Some(50));
-        assert_eq!(score.total_score(), 50);
+    }
+
+    #[test]
+    fn test_joker_must_take_its_upper_box_first() {
+        let mut score = Score::new();
+        let dice = Dice(vec![3, 3, 3, 3, 3]);
+        let die_spec = DieSpec::default();
+        assert!(!score.is_joker(&dice));
+        score.set(Category::Yahtzee, &dice, &die_spec).unwrap();
+        assert!(score.is_joker(&dice));
+        assert_eq!(score.joker_categories(&dice), vec![Category::Threes]);
+        assert_eq!(
+            score.set(Category::Chance, &dice, &die_spec),
+            Err(ScoreError::JokerRule)
+        );
+        assert_eq!(
+            score.set(Category::Yahtzee, &dice, &die_spec),
+            Err(ScoreError::AlreadySet)
+        );
+        score.set(Category::Threes, &dice, &die_spec).unwrap();
+        assert_eq!(score.get(Category::Threes), Some(15));
+        assert_eq!(score.lower_section.yahtzee_bonus, YAHTZEE_BONUS);
+        assert_eq!(score.total_score(), 165);
+    }
+
+    #[test]
+    fn test_joker_fills_any_lower_box_at_full_points() {
+        let mut score = Score::new();
+        let dice = Dice(vec![3, 3, 3, 3, 3]);
+        let die_spec = DieSpec::default();
+        score
+            .set(Category::Threes, &Dice(vec![1; 5]), &die_spec)
+            .unwrap();
+        score.set(Category::Yahtzee, &dice, &die_spec).unwrap();
+        assert_eq!(score.joker_categories(&dice).len(), 6);
+        assert_eq!(
+            score.set(Category::Aces, &dice, &die_spec),
+            Err(ScoreError::JokerRule)
+        );
+        score
+            .set(Category::LargeStraight, &dice, &die_spec)
+            .unwrap();
+        score.set(Category::FullHouse, &dice, &die_spec).unwrap();
+        assert_eq!(score.get(Category::LargeStraight), Some(40));
+        assert_eq!(score.get(Category::FullHouse), Some(25));
+        assert_eq!(score.lower_section.yahtzee_bonus, 2 * YAHTZEE_BONUS);
 
-        score.set_yahtzee(&dice_input).unwrap();
-        assert_eq!(score.yahtzee, Some(100));
-        assert_eq!(score.total_score(), 100);
+        for category in Category::ALL[6..].iter() {
+            let _ = score.set(*category, &Dice(vec![1, 2, 4, 5, 6]), &die_spec);
+        }
+        assert_eq!(
+            score.joker_categories(&dice),
+            vec![
+                Category::Aces,
+                Category::Twos,
+                Category::Fours,
+                Category::Fives,
+                Category::Sixes
+            ]
+        );
+        score.set(Category::Sixes, &dice, &die_spec).unwrap();
+        assert_eq!(score.get(Category::Sixes), Some(0));
+    }
+
+    #[test]
+    fn test_joker_earns_no_bonus_after_a_scratched_yahtzee() {
+        let mut score = Score::new();
+        let die_spec = DieSpec::default();
+        score
+            .set(Category::Yahtzee, &Dice(vec![1, 2, 3, 4, 6]), &die_spec)
+            .unwrap();
+        let dice = Dice(vec![2, 2, 2, 2, 2]);
+        assert!(score.is_joker(&dice));
+        score.set(Category::Twos, &dice, &die_spec).unwrap();
+        assert_eq!(score.lower_section.yahtzee_bonus, 0);
+        assert_eq!(score.total_score(), 10);
+    }
+
+    #[rstest]
+    #[case(Category::FullHouse, vec![2, 2, 3, 3, 3], Some(25))]
+    #[case(Category::FullHouse, vec![2, 2, 3, 3, 4], Some(0))]
+    #[case(Category::Sixes, vec![6, 6, 3, 3, 4], Some(12))]
+    #[case(Category::Aces, vec![6, 6, 3, 3, 4], None)]
+    #[case(Category::Yahtzee, vec![5, 5, 5, 5, 5], None)]
+    #[case(Category::Yahtzee, vec![5, 5, 5, 5, 1], None)]
+    #[case(Category::Fives, vec![5, 5, 5, 5, 5], Some(25))]
+    #[case(Category::FullHouse, vec![5, 5, 5, 5, 5], None)]
+    fn test_potential_does_not_change_score(
+        #[case] category: Category,
+        #[case] dice_input: Vec<u32>,
+        #[case] expected: Option<u32>,
+    ) {
+        let mut score = Score::new();
+        score.upper_section.aces = Some(3);
+        score.lower_section.yahtzee = Some(50);
+        let dice = Dice(dice_input);
+        assert_eq!(
+            score.potential(category, &dice, &DieSpec::default()),
+            expected
+        );
+        assert_eq!(score.total_score(), 53);
     }
 
     #[rstest]
@@ -506,10 +965,30 @@ mod tests {
             large_straight: dice_input[4],
             yahtzee: dice_input[5],
             chance: dice_input[6],
+            yahtzee_bonus: 0,
         };
         assert_eq!(score_lower.total_score(), expected_output);
     }
 
+    #[test]
+    fn test_score_is_complete_once_every_category_is_set() {
+        let mut score = Score::new();
+        let dice = Dice(vec![1, 1, 1, 1, 1]);
+        assert!(!score.is_complete());
+        for category in Category::ALL {
+            if category != Category::Yahtzee {
+                score.set(category, &dice, &DieSpec::default()).unwrap();
+            }
+        }
+        assert!(!score.is_complete());
+        score
+            .set(Category::Yahtzee, &dice, &DieSpec::default())
+            .unwrap();
+        assert!(score.is_complete());
+        assert_eq!(score.get(Category::Aces), Some(5));
+        assert_eq!(score.get(Category::Yahtzee), Some(50));
+    }
+
     #[test]
     fn test_score_full_score() {
         let score = Score {
@@ -530,8 +1009,221 @@ mod tests {
                 large_straight: Some(40),
                 yahtzee: Some(50),
                 chance: Some(14),
+                yahtzee_bonus: 0,
             },
         };
         assert_eq!(score.total_score(), 319);
     }
+
+    #[rstest]
+    #[case(
+        "d6",
+        Category::SmallStraight,
+        "30 points for 4 faces in a row (1-2-3-4, 2-3-4-5 or 3-4-5-6)"
+    )]
+    #[case(
+        "d6",
+        Category::LargeStraight,
+        "40 points for 5 faces in a row (1-2-3-4-5 or 2-3-4-5-6)"
+    )]
+    #[case(
+        "d20",
+        Category::LargeStraight,
+        "40 points for 5 faces in a row (e.g. 1-2-3-4-5)"
+    )]
+    #[case(
+        "2,4,6,8",
+        Category::SmallStraight,
+        "30 points for 4 faces in a row (2-4-6-8)"
+    )]
+    #[case(
+        "2,4,6,8",
+        Category::LargeStraight,
+        "40 points for 5 faces in a row which this die can't roll"
+    )]
+    #[case("d6", Category::Aces, "Sum of all dice showing 1")]
+    #[case(
+        "2,4,6,8",
+        Category::Aces,
+        "Sum of all dice showing 1, which this die can't roll"
+    )]
+    fn test_rules_follow_die_faces(
+        #[case] die_spec: &str,
+        #[case] category: Category,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(category.rule(&die_spec.parse().unwrap()), expected);
+    }
+
+    /// The checks below run over every ordered roll of five standard dice.
+    fn all_rolls() -> impl Iterator<Item = Vec<u32>> {
+        (0..6u32.pow(5)).map(|mut n| {
+            (0..5)
+                .map(|_| {
+                    let face = n % 6 + 1;
+                    n /= 6;
+                    face
+                })
+                .collect()
+        })
+    }
+
+    fn potentials(dice_input: &[u32]) -> Vec<Option<u32>> {
+        let dice = Dice(dice_input.to_vec());
+        Category::ALL
+            .iter()
+            .map(|category| Score::new().potential(*category, &dice, &DieSpec::default()))
+            .collect()
+    }
+
+    fn max_points(category: Category) -> u32 {
+        match category.face() {
+            Some(face) => 5 * face,
+            None => match category {
+                Category::FullHouse => FULL_HOUSE_POINTS,
+                Category::SmallStraight => SMALL_STRAIGHT_POINTS,
+                Category::LargeStraight => LARGE_STRAIGHT_POINTS,
+                Category::Yahtzee => YAHTZEE_POINTS,
+                _ => 30,
+            },
+        }
+    }
+
+    #[test]
+    fn test_all_rolls_are_enumerated() {
+        let rolls: HashSet<Vec<u32>> = all_rolls().collect();
+        assert_eq!(rolls.len(), 7776);
+        assert!(rolls.iter().flatten().all(|face| (1..=6).contains(face)));
+    }
+
+    #[test]
+    fn test_scores_do_not_depend_on_dice_order() {
+        let mut rng = StdRng::seed_from_u64(41);
+        for roll in all_rolls() {
+            let expected = potentials(&roll);
+            let mut shuffled = roll.clone();
+            for _ in 0..3 {
+                shuffled.shuffle(&mut rng);
+                assert_eq!(
+                    potentials(&shuffled),
+                    expected,
+                    "{:?} vs {:?}",
+                    roll,
+                    shuffled
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_category_implications_hold_for_all_rolls() {
+        let index = |category| Category::ALL.iter().position(|c| *c == category).unwrap();
+        for roll in all_rolls() {
+            let points = potentials(&roll);
+            let scores = |category| points[index(category)].unwrap() > 0;
+            if scores(Category::LargeStraight) {
+                assert!(scores(Category::SmallStraight), "{:?}", roll);
+            }
+            if scores(Category::Yahtzee) {
+                assert!(scores(Category::FourOfAKind), "{:?}", roll);
+            }
+            if scores(Category::FourOfAKind) {
+                assert!(scores(Category::ThreeOfAKind), "{:?}", roll);
+            }
+
+            // Straights checked against the runs a standard die can roll
+            let faces: HashSet<u32> = roll.iter().copied().collect();
+            let contains_run = |run: &[u32]| run.iter().all(|face| faces.contains(face));
+            assert_eq!(
+                scores(Category::SmallStraight),
+                [[1, 2, 3, 4], [2, 3, 4, 5], [3, 4, 5, 6]]
+                    .iter()
+                    .any(|run| contains_run(run)),
+                "{:?}",
+                roll
+            );
+            assert_eq!(
+                scores(Category::LargeStraight),
+                [[1, 2, 3, 4, 5], [2, 3, 4, 5, 6]]
+                    .iter()
+                    .any(|run| contains_run(run)),
+                "{:?}",
+                roll
+            );
+        }
+    }
+
+    #[test]
+    fn test_sums_match_the_dice_for_all_rolls() {
+        for roll in all_rolls() {
+            let sum: u32 = roll.iter().sum();
+            let points = potentials(&roll);
+            for (category, points) in Category::ALL.iter().zip(points) {
+                let points = points.unwrap();
+                if let Some(face) = category.face() {
+                    let count = roll.iter().filter(|die| **die == face).count() as u32;
+                    assert_eq!(points, face * count, "{:?} {:?}", category, roll);
+                }
+                match category {
+                    Category::Chance => assert_eq!(points, sum, "{:?}", roll),
+                    Category::ThreeOfAKind | Category::FourOfAKind => {
+                        assert!(points == 0 || points == sum, "{:?} {:?}", category, roll)
+                    }
+                    _ => {}
+                }
+                assert!(points <= max_points(*category), "{:?} {:?}", category, roll);
+            }
+        }
+    }
+
+    #[test]
+    fn test_total_never_exceeds_the_maximum() {
+        let upper_max: u32 = Category::ALL[..6].iter().map(|c| max_points(*c)).sum();
+        let lower_max: u32 = Category::ALL[6..].iter().map(|c| max_points(*c)).sum();
+        let maximum = upper_max + UPPER_BONUS + lower_max;
+        assert_eq!(maximum, 375);
+
+        let rolls: Vec<Vec<u32>> = all_rolls().collect();
+        let mut rng = StdRng::seed_from_u64(375);
+        for _ in 0..1000 {
+            let mut categories = Category::ALL;
+            categories.shuffle(&mut rng);
+            let mut score = Score::new();
+            for category in categories {
+                // Jokers that the category can't take are rolled again
+                let dice = loop {
+                    let dice = Dice(rolls.choose(&mut rng).unwrap().clone());
+                    if score
+                        .potential(category, &dice, &DieSpec::default())
+                        .is_some()
+                    {
+                        break dice;
+                    }
+                };
+                score.set(category, &dice, &DieSpec::default()).unwrap();
+            }
+            let bonus = score.lower_section.yahtzee_bonus;
+            assert!(score.total_score() - bonus <= maximum, "{:?}", score);
+        }
+    }
+
+    #[test]
+    fn test_huge_faces_never_overflow() {
+        let die_spec: DieSpec = "999999,1000000".parse().unwrap();
+        let mut score = Score::new();
+        let dice = Dice(vec![1_000_000; 5]);
+        // Chance comes after Yahtzee and takes a Joker
+        for category in Category::ALL {
+            score.set(category, &dice, &die_spec).unwrap();
+        }
+        assert_eq!(score.total_score(), 15_000_150);
+
+        // Dice that no spec allows still only saturate
+        let dice = Dice(vec![u32::MAX; 5]);
+        let mut score = Score::new();
+        for category in [Category::ThreeOfAKind, Category::Chance] {
+            score.set(category, &dice, &die_spec).unwrap();
+        }
+        assert_eq!(score.total_score(), u32::MAX);
+    }
 }
@@ -0,0 +1,152 @@
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// Examples of everything a selection can contain, shown in the help.
+pub(crate) const SYNTAX: [(&str, &str); 6] = [
+    ("1,3,5 / 1 3 5 / 135", "Dice 1, 3 and 5"),
+    ("1-3", "Dice 1 to 3"),
+    ("6s", "Every die showing a 6"),
+    ("!2", "Every die but die 2, also !6s"),
+    ("*", "All dice"),
+    ("1-3 !2", "Terms can be combined"),
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub(crate) enum SelectionError {
+    #[error("pick at least one die to reroll, e.g. 1,3,5")]
+    Empty,
+    #[error("'{0}' is not a die, a range like 1-3 or a face like 6s")]
+    InvalidTerm(String),
+    #[error("there is no die {die}, only dice 1 to {number_of_dice}")]
+    NoSuchDie { die: usize, number_of_dice: usize },
+    #[error("the range {start}-{end} runs backwards, try {end}-{start}")]
+    BackwardsRange { start: usize, end: usize },
+    #[error("no die shows a {0}")]
+    FaceNotShown(u32),
+}
+
+/// Parses which of `dice` to reroll into sorted, zero based ids. Terms are separated by commas or
+/// spaces and can be die numbers, ranges, faces, `*` for all dice, or any of these prefixed with
+/// `!` to leave dice out. A selection of only exclusions starts from all dice.
+pub(crate) fn parse_selection(input: &str, dice: &[u32]) -> Result<Vec<usize>, SelectionError> {
+    let mut included = BTreeSet::new();
+    let mut excluded = BTreeSet::new();
+    let mut has_inclusions = false;
+    for term in input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|term| !term.is_empty())
+    {
+        match term.strip_prefix('!') {
+            Some(rest) => excluded.extend(parse_term(rest, term, dice)?),
+            None => {
+                has_inclusions = true;
+                included.extend(parse_term(term, term, dice)?);
+            }
+        }
+    }
+    if !has_inclusions && !excluded.is_empty() {
+        included = (0..dice.len()).collect();
+    }
+    let selected: Vec<usize> = included.difference(&excluded).copied().collect();
+    if selected.is_empty() {
+        return Err(SelectionError::Empty);
+    }
+    Ok(selected)
+}
+
+/// Parses a single term, reporting errors against `original`, which includes a leading `!`.
+fn parse_term(term: &str, original: &str, dice: &[u32]) -> Result<Vec<usize>, SelectionError> {
+    let number_of_dice = dice.len();
+    let invalid = || SelectionError::InvalidTerm(original.to_string());
+    let die = |text: &str| -> Result<usize, SelectionError> {
+        let die: usize = text.parse().map_err(|_| invalid())?;
+        if die == 0 || die > number_of_dice {
+            return Err(SelectionError::NoSuchDie {
+                die,
+                number_of_dice,
+            });
+        }
+        Ok(die - 1)
+    };
+
+    if term == "*" {
+        return Ok((0..number_of_dice).collect());
+    }
+    if let Some(face) = term.strip_suffix("'s").or_else(|| term.strip_suffix('s')) {
+        let face: u32 = face.parse().map_err(|_| invalid())?;
+        let ids: Vec<usize> = (0..number_of_dice).filter(|id| dice[*id] == face).collect();
+        if ids.is_empty() {
+            return Err(SelectionError::FaceNotShown(face));
+        }
+        return Ok(ids);
+    }
+    if let Some((start, end)) = term.split_once('-') {
+        let (start, end) = (die(start)?, die(end)?);
+        if start > end {
+            return Err(SelectionError::BackwardsRange {
+                start: start + 1,
+                end: end + 1,
+            });
+        }
+        return Ok((start..=end).collect());
+    }
+    // With fewer than ten dice every die number is a single digit, so `135` can only mean 1, 3, 5
+    if term.len() > 1 && number_of_dice < 10 && term.chars().all(|c| c.is_ascii_digit()) {
+        return term.chars().map(|c| die(&c.to_string())).collect();
+    }
+    die(term).map(|id| vec![id])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const DICE: [u32; 5] = [6, 2, 6, 4, 1];
+
+    #[rstest]
+    #[case("1,3,5", vec![0, 2, 4])]
+    #[case("1 3 5", vec![0, 2, 4])]
+    #[case(" 1, 3 ,5,", vec![0, 2, 4])]
+    #[case("135", vec![0, 2, 4])]
+    #[case("3,1,3", vec![0, 2])]
+    #[case("2-4", vec![1, 2, 3])]
+    #[case("4-4", vec![3])]
+    #[case("6s", vec![0, 2])]
+    #[case("6's", vec![0, 2])]
+    #[case("!2", vec![0, 2, 3, 4])]
+    #[case("!6s !1s", vec![1, 3])]
+    #[case("*", vec![0, 1, 2, 3, 4])]
+    #[case("1-4 !2-3", vec![0, 3])]
+    #[case("1s 2s", vec![1, 4])]
+    fn test_valid_selections(#[case] input: &str, #[case] expected: Vec<usize>) {
+        assert_eq!(parse_selection(input, &DICE), Ok(expected));
+    }
+
+    #[rstest]
+    #[case("", SelectionError::Empty)]
+    #[case(" , ", SelectionError::Empty)]
+    #[case("!*", SelectionError::Empty)]
+    #[case("1 !1", SelectionError::Empty)]
+    #[case("0", SelectionError::NoSuchDie { die: 0, number_of_dice: 5 })]
+    #[case("6", SelectionError::NoSuchDie { die: 6, number_of_dice: 5 })]
+    #[case("106", SelectionError::NoSuchDie { die: 0, number_of_dice: 5 })]
+    #[case("2-7", SelectionError::NoSuchDie { die: 7, number_of_dice: 5 })]
+    #[case("4-2", SelectionError::BackwardsRange { start: 4, end: 2 })]
+    #[case("3s", SelectionError::FaceNotShown(3))]
+    #[case("x", SelectionError::InvalidTerm("x".to_string()))]
+    #[case("!", SelectionError::InvalidTerm("!".to_string()))]
+    #[case("1-", SelectionError::InvalidTerm("1-".to_string()))]
+    #[case("-1", SelectionError::InvalidTerm("-1".to_string()))]
+    #[case("!xs", SelectionError::InvalidTerm("!xs".to_string()))]
+    fn test_invalid_selections(#[case] input: &str, #[case] expected: SelectionError) {
+        assert_eq!(parse_selection(input, &DICE), Err(expected));
+    }
+
+    #[test]
+    fn test_digits_are_one_die_with_many_dice() {
+        let dice = [1; 12];
+        assert_eq!(parse_selection("12", &dice), Ok(vec![11]));
+        assert_eq!(parse_selection("10-12", &dice), Ok(vec![9, 10, 11]));
+    }
+}
@@ -0,0 +1,1094 @@
+use crate::bot::{play_step, Bot, GreedyBot};
+use crate::dice::{DieSpec, DieSpecError};
+use crate::game::{Game, GameError};
+use crate::protocol::{
+    read_message, write_message, ClientMessage, ProtocolError, ServerMessage, TableSummary,
+    MAX_SEATS,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
+
+/// A client that stops reading is dropped rather than holding up everyone else.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a browser's connection waits for a message before passing on the ones for it.
+#[cfg(feature = "websocket")]
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a browser may take to open its WebSocket, so a silent one doesn't hold a thread.
+#[cfg(feature = "websocket")]
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Why a message from a client was turned down.
+#[derive(Debug, Error, PartialEq)]
+enum LobbyError {
+    #[error("join a game first")]
+    NotJoined,
+    #[error("you have already joined a game, leave it first")]
+    AlreadyJoined,
+    #[error("there is no table {0}")]
+    NoSuchTable(usize),
+    #[error("a table has 1 to {} seats", MAX_SEATS)]
+    InvalidSeats,
+    #[error("invalid die: {0}")]
+    InvalidDie(#[from] DieSpecError),
+    #[error("unknown session, it may have ended")]
+    UnknownSession,
+    #[error("no game is being played")]
+    NothingToWatch,
+    #[error("spectators can't play")]
+    Spectating,
+    #[error(transparent)]
+    Game(#[from] GameError),
+}
+
+struct Client {
+    /// Written out by a thread of the client's own, so a client that stops reading holds up
+    /// nobody else.
+    outbox: Sender<ServerMessage>,
+    /// The table and seat the client plays at.
+    seat: Option<(usize, usize)>,
+    /// The table the client follows as a spectator.
+    watching: Option<usize>,
+    /// How many of its table's game events the client has been sent.
+    events_sent: usize,
+}
+
+impl Client {
+    fn table(&self) -> Option<usize> {
+        self.seat.map(|(table, _)| table).or(self.watching)
+    }
+}
+
+/// A game and the bots playing in it.
+struct Table {
+    game: Game,
+    bots: BTreeMap<usize, Box<dyn Bot>>,
+}
+
+impl Table {
+    fn summary(&self, table: usize) -> TableSummary {
+        let snapshot = self.game.snapshot();
+        TableSummary {
+            table,
+            die_spec: snapshot.die_spec,
+            number_of_seats: snapshot.number_of_seats,
+            players: snapshot
+                .players
+                .into_iter()
+                .filter(|player| player.present)
+                .map(|player| player.name)
+                .collect(),
+            started: self.game.is_started(),
+            over: snapshot.over,
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        let players = self.game.players();
+        !self.game.is_started()
+            && (players.len() < self.game.number_of_seats()
+                || players.iter().any(|player| !player.present))
+    }
+
+    fn has_humans(&self) -> bool {
+        self.game
+            .players()
+            .iter()
+            .enumerate()
+            .any(|(seat, player)| player.present && !self.bots.contains_key(&seat))
+    }
+
+    /// Seats a ready bot in every free seat.
+    fn add_bots(&mut self) -> Result<(), GameError> {
+        let mut added = false;
+        loop {
+            let name = format!("Bot {}", self.bots.len() + 1);
+            let seat = match self.game.join(&name) {
+                Ok(seat) => seat,
+                Err(GameError::Full(_) | GameError::Started) if added => return Ok(()),
+                Err(error) => return Err(error),
+            };
+            self.bots.insert(seat, Box::new(GreedyBot));
+            self.game.ready(seat)?;
+            added = true;
+        }
+    }
+
+    /// Plays the bots' turns for as long as it's their turn. They wait while no human is at the
+    /// table, so a game everyone lost the connection to can still be resumed.
+    fn play_bots(&mut self) {
+        while self.has_humans() {
+            let seat = match self.game.snapshot().current {
+                Some(seat) if self.bots.contains_key(&seat) => seat,
+                _ => return,
+            };
+            let bot = self
+                .bots
+                .get_mut(&seat)
+                .expect("the current seat has a bot");
+            // A confused bot scores what it can rather than stalling the table
+            if play_step(&mut self.game, seat, bot.as_mut()).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Every table on the server and everyone connected to it. Games only ever change here, so all
+/// dice are rolled by the server.
+struct Lobby {
+    tables: BTreeMap<usize, Table>,
+    clients: BTreeMap<usize, Client>,
+    /// The table and seat each session token gives back.
+    sessions: HashMap<String, (usize, usize)>,
+    next_table_id: usize,
+    next_client_id: usize,
+    /// Used for tables opened by `join` when no table is waiting for players.
+    die_spec: DieSpec,
+    number_of_seats: usize,
+    new_game: Box<dyn Fn(DieSpec, usize) -> Game + Send>,
+}
+
+impl Lobby {
+    fn connect(&mut self, outbox: Sender<ServerMessage>) -> usize {
+        let id = self.next_client_id;
+        self.next_client_id += 1;
+        self.clients.insert(
+            id,
+            Client {
+                outbox,
+                seat: None,
+                watching: None,
+                events_sent: 0,
+            },
+        );
+        id
+    }
+
+    /// Leaves the seat of a client whose connection dropped, but keeps its session so it can
+    /// carry on after reconnecting.
+    fn disconnect(&mut self, id: usize) {
+        if let Some(Client {
+            seat: Some((table, seat)),
+            ..
+        }) = self.clients.remove(&id)
+        {
+            self.leave(table, seat);
+            self.update();
+        }
+    }
+
+    fn handle(&mut self, id: usize, message: ClientMessage) {
+        if let Err(error) = self.apply(id, message) {
+            self.send(
+                id,
+                &ServerMessage::Error {
+                    message: error.to_string(),
+                },
+            );
+        }
+        self.update();
+    }
+
+    fn apply(&mut self, id: usize, message: ClientMessage) -> Result<(), LobbyError> {
+        let seated = self.clients.get(&id).and_then(|client| client.seat);
+        let watching = self.clients.get(&id).and_then(|client| client.watching);
+        match (message, seated) {
+            (ClientMessage::ListGames, _) => {
+                let games = self
+                    .tables
+                    .iter()
+                    .map(|(table_id, table)| table.summary(*table_id))
+                    .collect();
+                self.send(id, &ServerMessage::Games { games });
+            }
+            (
+                ClientMessage::Create { .. }
+                | ClientMessage::Join { .. }
+                | ClientMessage::Rejoin { .. },
+                Some(_),
+            ) => return Err(LobbyError::AlreadyJoined),
+            (ClientMessage::Create { name, die, seats }, None) => {
+                if seats == 0 || seats > MAX_SEATS {
+                    return Err(LobbyError::InvalidSeats);
+                }
+                let die = die.parse()?;
+                let table = self.open_table(die, seats);
+                self.seat(id, table, &name)?;
+            }
+            (ClientMessage::Join { name, table }, None) => {
+                let table = match table {
+                    Some(table) => table,
+                    None => match self.tables.iter().find(|(_, table)| table.is_open()) {
+                        Some((table, _)) => *table,
+                        None => self.open_table(self.die_spec.clone(), self.number_of_seats),
+                    },
+                };
+                self.seat(id, table, &name)?;
+            }
+            (ClientMessage::Rejoin { token }, None) => {
+                let (table, seat) = *self
+                    .sessions
+                    .get(&token)
+                    .ok_or(LobbyError::UnknownSession)?;
+                self.table(table)?.game.rejoin(seat)?;
+                self.welcome(id, table, seat, token);
+            }
+            (ClientMessage::Watch { .. }, Some(_)) => return Err(LobbyError::AlreadyJoined),
+            (ClientMessage::Watch { table }, None) => {
+                let table = match table {
+                    Some(table) => table,
+                    None => self
+                        .tables
+                        .iter()
+                        .find(|(_, table)| table.game.is_started() && !table.game.snapshot().over)
+                        .map(|(table, _)| *table)
+                        .ok_or(LobbyError::NothingToWatch)?,
+                };
+                let game = &self.table(table)?.game;
+                let watching = ServerMessage::Watching {
+                    table,
+                    game: game.snapshot(),
+                };
+                let events_sent = game.events().len();
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.watching = Some(table);
+                    client.events_sent = events_sent;
+                }
+                self.send(id, &watching);
+            }
+            (ClientMessage::Leave, None) if watching.is_some() => {
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.watching = None;
+                }
+            }
+            (_, None) if watching.is_some() => return Err(LobbyError::Spectating),
+            (_, None) => return Err(LobbyError::NotJoined),
+            (ClientMessage::Ready, Some((table, seat))) => self.table(table)?.game.ready(seat)?,
+            (ClientMessage::AddBots, Some((table, _))) => self.table(table)?.add_bots()?,
+            (ClientMessage::Hold { held }, Some((table, seat))) => {
+                self.table(table)?.game.hold(seat, held)?
+            }
+            (ClientMessage::Roll, Some((table, seat))) => self.table(table)?.game.roll(seat)?,
+            (ClientMessage::Score { category }, Some((table, seat))) => {
+                self.table(table)?.game.score(seat, category)?
+            }
+            (ClientMessage::Chat { text }, Some((table, seat))) => {
+                let chat = ServerMessage::Chat {
+                    from: self.table(table)?.game.players()[seat].name.clone(),
+                    text,
+                };
+                let ids: Vec<usize> = self
+                    .clients
+                    .iter()
+                    .filter(|(_, client)| client.table() == Some(table))
+                    .map(|(id, _)| *id)
+                    .collect();
+                for id in ids {
+                    self.send(id, &chat);
+                }
+            }
+            (ClientMessage::Leave, Some((table, seat))) => {
+                self.leave(table, seat);
+                // Leaving on purpose gives the seat up for good
+                self.sessions.retain(|_, session| *session != (table, seat));
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.seat = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn table(&mut self, table: usize) -> Result<&mut Table, LobbyError> {
+        self.tables
+            .get_mut(&table)
+            .ok_or(LobbyError::NoSuchTable(table))
+    }
+
+    fn open_table(&mut self, die_spec: DieSpec, number_of_seats: usize) -> usize {
+        let id = self.next_table_id;
+        self.next_table_id += 1;
+        self.tables.insert(
+            id,
+            Table {
+                game: (self.new_game)(die_spec, number_of_seats),
+                bots: BTreeMap::new(),
+            },
+        );
+        id
+    }
+
+    fn seat(&mut self, id: usize, table: usize, name: &str) -> Result<(), LobbyError> {
+        let seat = self.table(table)?.game.join(name)?;
+        let token = format!("{:016x}", rand::random::<u64>());
+        self.sessions.insert(token.clone(), (table, seat));
+        self.welcome(id, table, seat, token);
+        Ok(())
+    }
+
+    fn welcome(&mut self, id: usize, table: usize, seat: usize, token: String) {
+        let game = match self.tables.get(&table) {
+            Some(table) => &table.game,
+            None => return,
+        };
+        let welcome = ServerMessage::Welcome {
+            table,
+            seat,
+            token,
+            game: game.snapshot(),
+        };
+        let events_sent = game.events().len();
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.seat = Some((table, seat));
+            client.watching = None;
+            client.events_sent = events_sent;
+        }
+        self.send(id, &welcome);
+    }
+
+    fn leave(&mut self, table: usize, seat: usize) {
+        if let Some(table_state) = self.tables.get_mut(&table) {
+            table_state.game.leave(seat);
+            // Until the game starts the seat is up for grabs
+            if !table_state.game.is_started() {
+                self.sessions.retain(|_, session| *session != (table, seat));
+            }
+        }
+    }
+
+    /// Lets the bots move, sends every client the events it hasn't seen yet, and closes the
+    /// tables nobody is coming back to.
+    fn update(&mut self) {
+        for table in self.tables.values_mut() {
+            table.play_bots();
+        }
+        for client in self.clients.values_mut() {
+            let table = match client.table().and_then(|table| self.tables.get(&table)) {
+                Some(table) => table,
+                None => continue,
+            };
+            let events = table.game.events();
+            for event in &events[client.events_sent..] {
+                // A client that can't be written to is cleaned up once its reader notices
+                let _ = client.outbox.send(ServerMessage::Event(event.clone()));
+            }
+            client.events_sent = events.len();
+        }
+        let clients = &self.clients;
+        let sessions = &mut self.sessions;
+        self.tables.retain(|id, table| {
+            let seated = clients.values().any(|client| client.table() == Some(*id));
+            let awaited = !table.game.snapshot().over && sessions.values().any(|(t, _)| t == id);
+            if !seated && !awaited {
+                sessions.retain(|_, (t, _)| t != id);
+            }
+            seated || awaited
+        });
+    }
+
+    fn send(&mut self, id: usize, message: &ServerMessage) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            let _ = client.outbox.send(message.clone());
+        }
+    }
+}
+
+/// Hosts any number of games over TCP, see `protocol` for the messages.
+pub(crate) struct Server {
+    listener: TcpListener,
+    lobby: Arc<Mutex<Lobby>>,
+}
+
+impl Server {
+    /// Binds to `addr`. Players who join without picking a table get one for `number_of_seats`
+    /// players, rolling `die_spec`.
+    pub fn bind<A: ToSocketAddrs>(
+        addr: A,
+        die_spec: DieSpec,
+        number_of_seats: usize,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            lobby: Arc::new(Mutex::new(Lobby {
+                tables: BTreeMap::new(),
+                clients: BTreeMap::new(),
+                sessions: HashMap::new(),
+                next_table_id: 1,
+                next_client_id: 0,
+                die_spec,
+                number_of_seats,
+                new_game: Box::new(Game::new),
+            })),
+        })
+    }
+
+    /// Replaces how the games at new tables are set up, e.g. to script their dice.
+    #[cfg(test)]
+    pub fn with_new_game<F>(self, new_game: F) -> Self
+    where
+        F: Fn(DieSpec, usize) -> Game + Send + 'static,
+    {
+        lock(&self.lobby).new_game = Box::new(new_game);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves every connection on its own thread until accepting connections fails.
+    pub fn run(self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let lobby = Arc::clone(&self.lobby);
+            thread::spawn(move || serve_client(&lobby, stream));
+        }
+        Ok(())
+    }
+
+    /// Also lets browsers in at `addr`. They speak the same protocol over WebSocket, a message
+    /// per text frame, and join the same tables. Returns the address that was bound.
+    #[cfg(feature = "websocket")]
+    pub fn listen_websocket<A: ToSocketAddrs>(&self, addr: A) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let lobby = Arc::clone(&self.lobby);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let lobby = Arc::clone(&lobby);
+                thread::spawn(move || serve_browser(&lobby, stream));
+            }
+        });
+        Ok(local_addr)
+    }
+}
+
+fn serve_client(lobby: &Mutex<Lobby>, stream: TcpStream) {
+    let mut writer = match stream
+        .set_write_timeout(Some(WRITE_TIMEOUT))
+        .and_then(|_| stream.try_clone())
+    {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let (sender, outbox) = mpsc::channel();
+    thread::spawn(move || {
+        for message in outbox {
+            if write_message(&mut writer, &message).is_err() {
+                // Wakes up the reader below, which then disconnects the client
+                let _ = writer.shutdown(Shutdown::Both);
+                return;
+            }
+        }
+    });
+    let connection = Connection::new(lobby, sender);
+    let id = connection.id;
+    let mut reader = BufReader::new(stream);
+    loop {
+        match read_message(&mut reader) {
+            Ok(Some(message)) => lock(lobby).handle(id, message),
+            Ok(None) | Err(ProtocolError::Io(_)) => break,
+            Err(error @ ProtocolError::Invalid(_)) => lock(lobby).send(
+                id,
+                &ServerMessage::Error {
+                    message: error.to_string(),
+                },
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "websocket")]
+fn serve_browser(lobby: &Mutex<Lobby>, stream: TcpStream) {
+    use tungstenite::{Error, Message};
+
+    if stream
+        .set_write_timeout(Some(WRITE_TIMEOUT))
+        .and_then(|_| stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)))
+        .is_err()
+    {
+        return;
+    }
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+    // Reads give up now and then, so the messages for the browser can be passed on in between
+    if socket
+        .get_ref()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .is_err()
+    {
+        return;
+    }
+    let (sender, outbox) = mpsc::channel();
+    let connection = Connection::new(lobby, sender);
+    let id = connection.id;
+    'serve: loop {
+        for message in outbox.try_iter() {
+            let text = serde_json::to_string(&message).expect("messages serialize");
+            if socket.send(Message::Text(text)).is_err() {
+                break 'serve;
+            }
+        }
+        match socket.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str(&text) {
+                Ok(message) => lock(lobby).handle(id, message),
+                Err(error) => lock(lobby).send(
+                    id,
+                    &ServerMessage::Error {
+                        message: ProtocolError::Invalid(error).to_string(),
+                    },
+                ),
+            },
+            // Pings are answered and a close is acknowledged by tungstenite itself
+            Ok(_) => {}
+            Err(Error::Io(error))
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(_) => break,
+        }
+    }
+}
+
+/// A client in the lobby, which is disconnected once this is dropped, also when serving it
+/// panicked.
+struct Connection<'a> {
+    lobby: &'a Mutex<Lobby>,
+    id: usize,
+}
+
+impl<'a> Connection<'a> {
+    fn new(lobby: &'a Mutex<Lobby>, outbox: Sender<ServerMessage>) -> Self {
+        let id = lock(lobby).connect(outbox);
+        Self { lobby, id }
+    }
+}
+
+impl Drop for Connection<'_> {
+    fn drop(&mut self) {
+        lock(self.lobby).disconnect(self.id);
+    }
+}
+
+/// A panic while serving one client must not take the whole lobby down with it.
+fn lock(lobby: &Mutex<Lobby>) -> MutexGuard<'_, Lobby> {
+    lobby.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::dice::ScriptedRoller;
+    use crate::game::GameEvent;
+    use crate::score::Category;
+    use std::io::Write;
+
+    /// A raw protocol client for tests.
+    pub(crate) struct TestClient {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl TestClient {
+        pub fn connect(addr: SocketAddr) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            Self {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            }
+        }
+
+        pub fn send(&mut self, message: &ClientMessage) {
+            write_message(&mut self.writer, message).unwrap();
+        }
+
+        pub fn send_raw(&mut self, line: &str) {
+            writeln!(self.writer, "{}", line).unwrap();
+        }
+
+        pub fn receive(&mut self) -> ServerMessage {
+            read_message(&mut self.reader).unwrap().unwrap()
+        }
+
+        /// Skips messages until one matches.
+        pub fn receive_until<F: Fn(&ServerMessage) -> bool>(&mut self, f: F) -> ServerMessage {
+            loop {
+                let message = self.receive();
+                if f(&message) {
+                    return message;
+                }
+            }
+        }
+    }
+
+    /// Starts a server whose tables are for two players by default, all rolling `faces`.
+    pub(crate) fn start_server(faces: Vec<u32>) -> SocketAddr {
+        let server = Server::bind("127.0.0.1:0", DieSpec::default(), 2)
+            .unwrap()
+            .with_new_game(move |die_spec, seats| {
+                Game::new(die_spec, seats).with_roller(Box::new(ScriptedRoller::new(faces.clone())))
+            });
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        addr
+    }
+
+    pub(crate) fn welcome(client: &mut TestClient) -> (usize, usize, String) {
+        match client.receive() {
+            ServerMessage::Welcome {
+                table, seat, token, ..
+            } => (table, seat, token),
+            other => panic!("expected a welcome, got {:?}", other),
+        }
+    }
+
+    /// Connects a client and seats it at any open table.
+    pub(crate) fn join(addr: SocketAddr, name: &str) -> (TestClient, usize) {
+        let mut client = TestClient::connect(addr);
+        client.send(&ClientMessage::Join {
+            name: name.to_string(),
+            table: None,
+        });
+        let (_, seat, _) = welcome(&mut client);
+        (client, seat)
+    }
+
+    fn event(event: GameEvent) -> ServerMessage {
+        ServerMessage::Event(event)
+    }
+
+    #[test]
+    fn test_two_players_on_localhost() {
+        let addr = start_server(vec![2, 2, 2, 5, 6, 2, 2]);
+        let (mut ada, ada_seat) = join(addr, "ada");
+        assert_eq!(ada_seat, 0);
+        let (mut bob, bob_seat) = join(addr, "bob");
+        assert_eq!(bob_seat, 1);
+        assert_eq!(
+            ada.receive(),
+            event(GameEvent::PlayerJoined {
+                seat: 1,
+                name: "bob".to_string()
+            })
+        );
+
+        ada.send(&ClientMessage::Roll);
+        assert_eq!(
+            ada.receive(),
+            ServerMessage::Error {
+                message: "waiting for every seat to be taken and everyone to be ready".to_string()
+            }
+        );
+        ada.send(&ClientMessage::Ready);
+        bob.send(&ClientMessage::Ready);
+        for client in [&mut ada, &mut bob] {
+            client.receive_until(|message| message == &event(GameEvent::TurnStarted { seat: 0 }));
+        }
+
+        bob.send(&ClientMessage::Roll);
+        assert_eq!(
+            bob.receive(),
+            ServerMessage::Error {
+                message: "it's not your turn".to_string()
+            }
+        );
+
+        ada.send(&ClientMessage::Roll);
+        ada.send(&ClientMessage::Hold {
+            held: vec![true, true, true, false, false],
+        });
+        ada.send(&ClientMessage::Roll);
+        ada.send(&ClientMessage::Score {
+            category: Category::Yahtzee,
+        });
+        let scored = event(GameEvent::Scored {
+            seat: 0,
+            category: Category::Yahtzee,
+            points: 50,
+            total: 50,
+        });
+        // Both players see every step
+        for client in [&mut ada, &mut bob] {
+            client.receive_until(|message| message == &scored);
+            assert_eq!(client.receive(), event(GameEvent::TurnStarted { seat: 1 }));
+        }
+
+        ada.send(&ClientMessage::Chat {
+            text: "lucky".to_string(),
+        });
+        assert_eq!(
+            bob.receive(),
+            ServerMessage::Chat {
+                from: "ada".to_string(),
+                text: "lucky".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_lobby_lists_and_fills_tables() {
+        let addr = start_server(vec![]);
+        let mut ada = TestClient::connect(addr);
+        ada.send(&ClientMessage::Create {
+            name: "ada".to_string(),
+            die: "d8".to_string(),
+            seats: 3,
+        });
+        let (table, _, _) = welcome(&mut ada);
+        let (_bob, bob_seat) = join(addr, "bob");
+        assert_eq!(bob_seat, 1);
+
+        let mut cy = TestClient::connect(addr);
+        cy.send(&ClientMessage::ListGames);
+        assert_eq!(
+            cy.receive(),
+            ServerMessage::Games {
+                games: vec![TableSummary {
+                    table,
+                    die_spec: DieSpec::new(8).unwrap(),
+                    number_of_seats: 3,
+                    players: vec!["ada".to_string(), "bob".to_string()],
+                    started: false,
+                    over: false,
+                }]
+            }
+        );
+        cy.send(&ClientMessage::Join {
+            name: "cy".to_string(),
+            table: Some(table + 1),
+        });
+        assert_eq!(
+            cy.receive(),
+            ServerMessage::Error {
+                message: format!("there is no table {}", table + 1)
+            }
+        );
+        cy.send(&ClientMessage::Create {
+            name: "cy".to_string(),
+            die: "d6".to_string(),
+            seats: MAX_SEATS + 1,
+        });
+        assert!(matches!(cy.receive(), ServerMessage::Error { .. }));
+        cy.send_raw(r#"{"type":"create","name":"cy","die":"d4294967295","seats":2}"#);
+        assert_eq!(
+            cy.receive(),
+            ServerMessage::Error {
+                message: "invalid die: a die can have at most 1000 faces".to_string()
+            }
+        );
+
+        cy.send(&ClientMessage::Join {
+            name: "cy".to_string(),
+            table: None,
+        });
+        assert_eq!(welcome(&mut cy).0, table);
+        // A full table is no longer open, so the next player gets a new one
+        let (mut dee, _) = join(addr, "dee");
+        dee.send(&ClientMessage::ListGames);
+        let games =
+            match dee.receive_until(|message| matches!(message, ServerMessage::Games { .. })) {
+                ServerMessage::Games { games } => games,
+                _ => unreachable!(),
+            };
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].die_spec, DieSpec::default());
+        assert_eq!(games[1].players, ["dee"]);
+    }
+
+    #[test]
+    fn test_bots_fill_the_free_seats() {
+        let addr = start_server(vec![2; 5 * 4]);
+        let mut ada = TestClient::connect(addr);
+        ada.send(&ClientMessage::Create {
+            name: "ada".to_string(),
+            die: "d6".to_string(),
+            seats: 3,
+        });
+        welcome(&mut ada);
+        ada.send(&ClientMessage::AddBots);
+        ada.send(&ClientMessage::Ready);
+        ada.receive_until(|message| message == &event(GameEvent::TurnStarted { seat: 0 }));
+
+        ada.send(&ClientMessage::Roll);
+        ada.send(&ClientMessage::Score {
+            category: Category::Twos,
+        });
+        // The bots take their turns right away
+        for seat in 1..3 {
+            ada.receive_until(|message| {
+                message
+                    == &event(GameEvent::Scored {
+                        seat,
+                        category: Category::Yahtzee,
+                        points: 50,
+                        total: 50,
+                    })
+            });
+        }
+        assert_eq!(ada.receive(), event(GameEvent::TurnStarted { seat: 0 }));
+    }
+
+    #[test]
+    fn test_reconnecting_with_the_session_token() {
+        let addr = start_server(vec![]);
+        let mut ada = TestClient::connect(addr);
+        ada.send(&ClientMessage::Join {
+            name: "ada".to_string(),
+            table: None,
+        });
+        let (table, _, token) = welcome(&mut ada);
+        let (mut bob, _) = join(addr, "bob");
+        ada.send(&ClientMessage::Ready);
+        bob.send(&ClientMessage::Ready);
+        bob.receive_until(|message| message == &event(GameEvent::TurnStarted { seat: 0 }));
+
+        drop(ada);
+        assert_eq!(bob.receive(), event(GameEvent::PlayerLeft { seat: 0 }));
+        assert_eq!(bob.receive(), event(GameEvent::TurnStarted { seat: 1 }));
+
+        // The seat is kept for Ada rather than given to the next player
+        let mut cy = TestClient::connect(addr);
+        cy.send(&ClientMessage::Join {
+            name: "cy".to_string(),
+            table: None,
+        });
+        assert_ne!(welcome(&mut cy).0, table);
+        let mut ada = TestClient::connect(addr);
+        ada.send(&ClientMessage::Rejoin {
+            token: "nonsense".to_string(),
+        });
+        assert_eq!(
+            ada.receive(),
+            ServerMessage::Error {
+                message: "unknown session, it may have ended".to_string()
+            }
+        );
+        ada.send(&ClientMessage::Rejoin { token });
+        let (rejoined_table, seat, _) = welcome(&mut ada);
+        assert_eq!((rejoined_table, seat), (table, 0));
+        assert_eq!(
+            bob.receive(),
+            event(GameEvent::PlayerJoined {
+                seat: 0,
+                name: "ada".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_clients_are_disconnected_when_serving_them_panics() {
+        let server = Server::bind("127.0.0.1:0", DieSpec::default(), 2).unwrap();
+        let lobby = Arc::clone(&server.lobby);
+        let served = thread::spawn(move || {
+            let (sender, _outbox) = mpsc::channel();
+            let connection = Connection::new(&lobby, sender);
+            lock(&lobby).handle(
+                connection.id,
+                ClientMessage::Join {
+                    name: "ada".to_string(),
+                    table: None,
+                },
+            );
+            panic!("serving ada failed");
+        });
+        assert!(served.join().is_err());
+        let lobby = lock(&server.lobby);
+        assert!(lobby.clients.is_empty());
+        // Ada's seat was given up, which leaves nobody at the table
+        assert!(lobby.tables.is_empty());
+    }
+
+    #[test]
+    fn test_clients_that_stop_reading_hold_up_nobody() {
+        let addr = start_server(vec![1, 2, 3, 4, 5]);
+        let (mut stuck, _) = join(addr, "stuck");
+        // Far more than fits into the socket buffers
+        let text = "x".repeat(1000);
+        for _ in 0..20_000 {
+            stuck.send(&ClientMessage::Chat { text: text.clone() });
+        }
+
+        let mut cy = TestClient::connect(addr);
+        let started = std::time::Instant::now();
+        cy.send(&ClientMessage::ListGames);
+        assert!(matches!(cy.receive(), ServerMessage::Games { .. }));
+        assert!(started.elapsed() < WRITE_TIMEOUT / 2);
+    }
+
+    #[test]
+    fn test_spectators_join_mid_game() {
+        let addr = start_server(vec![2, 2, 2, 5, 6, 3]);
+        let mut spectator = TestClient::connect(addr);
+        spectator.send(&ClientMessage::Watch { table: None });
+        assert_eq!(
+            spectator.receive(),
+            ServerMessage::Error {
+                message: "no game is being played".to_string()
+            }
+        );
+
+        let (mut ada, _) = join(addr, "ada");
+        let (mut bob, _) = join(addr, "bob");
+        ada.send(&ClientMessage::Ready);
+        bob.send(&ClientMessage::Ready);
+        ada.receive_until(|message| message == &event(GameEvent::TurnStarted { seat: 0 }));
+        ada.send(&ClientMessage::Roll);
+        ada.receive_until(|message| {
+            matches!(message, ServerMessage::Event(GameEvent::Rolled { .. }))
+        });
+
+        spectator.send(&ClientMessage::Watch { table: None });
+        match spectator.receive() {
+            ServerMessage::Watching { table, game } => {
+                assert_eq!(table, 1);
+                assert_eq!(game.current, Some(0));
+                assert_eq!(game.dice, [2, 2, 2, 5, 6]);
+            }
+            message => panic!("expected a snapshot, got {:?}", message),
+        }
+        spectator.send(&ClientMessage::Roll);
+        assert_eq!(
+            spectator.receive(),
+            ServerMessage::Error {
+                message: "spectators can't play".to_string()
+            }
+        );
+
+        ada.send(&ClientMessage::Score {
+            category: Category::ThreeOfAKind,
+        });
+        assert_eq!(
+            spectator.receive(),
+            event(GameEvent::Scored {
+                seat: 0,
+                category: Category::ThreeOfAKind,
+                points: 17,
+                total: 17,
+            })
+        );
+        assert_eq!(
+            spectator.receive(),
+            event(GameEvent::TurnStarted { seat: 1 })
+        );
+    }
+
+    #[test]
+    fn test_invalid_messages_are_answered_with_errors() {
+        let addr = start_server(vec![]);
+        let mut client = TestClient::connect(addr);
+        client.send(&ClientMessage::Roll);
+        assert_eq!(
+            client.receive(),
+            ServerMessage::Error {
+                message: "join a game first".to_string()
+            }
+        );
+        client.send_raw("{\"type\":\"cheat\"}");
+        assert!(matches!(client.receive(), ServerMessage::Error { .. }));
+        // The connection stays open
+        client.send(&ClientMessage::Join {
+            name: "ada".to_string(),
+            table: None,
+        });
+        assert!(matches!(client.receive(), ServerMessage::Welcome { .. }));
+    }
+
+    #[cfg(feature = "websocket")]
+    #[test]
+    fn test_silent_browsers_are_dropped_during_the_handshake() {
+        use std::io::Read;
+
+        let server = Server::bind("127.0.0.1:0", DieSpec::default(), 2).unwrap();
+        let ws_addr = server.listen_websocket("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(ws_addr).unwrap();
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT * 2))
+            .unwrap();
+        assert_eq!(stream.read(&mut [0; 16]).unwrap(), 0);
+    }
+
+    #[cfg(feature = "websocket")]
+    #[test]
+    fn test_browsers_play_over_websocket() {
+        use tungstenite::Message;
+
+        let server = Server::bind("127.0.0.1:0", DieSpec::default(), 2)
+            .unwrap()
+            .with_new_game(|die_spec, seats| {
+                Game::new(die_spec, seats)
+                    .with_roller(Box::new(ScriptedRoller::new(vec![2, 2, 2, 5, 6])))
+            });
+        let addr = server.local_addr().unwrap();
+        let ws_addr = server.listen_websocket("127.0.0.1:0").unwrap();
+        thread::spawn(move || server.run());
+
+        let stream = TcpStream::connect(ws_addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let (mut browser, _) = tungstenite::client(format!("ws://{}/", ws_addr), stream).unwrap();
+        let mut send = |message: &ClientMessage| {
+            let text = serde_json::to_string(message).unwrap();
+            browser.send(Message::Text(text)).unwrap();
+        };
+        send(&ClientMessage::Join {
+            name: "ada".to_string(),
+            table: None,
+        });
+        let (mut bob, _) = join(addr, "bob");
+        send(&ClientMessage::Ready);
+        bob.send(&ClientMessage::Ready);
+        bob.receive_until(|message| message == &event(GameEvent::TurnStarted { seat: 0 }));
+        send(&ClientMessage::Roll);
+        assert_eq!(
+            bob.receive(),
+            event(GameEvent::Rolled {
+                seat: 0,
+                dice: vec![2, 2, 2, 5, 6],
+                rolled: vec![0, 1, 2, 3, 4],
+                rolls_left: 2,
+            })
+        );
+        browser
+            .send(Message::Text("{\"type\":\"shout\"}".to_string()))
+            .unwrap();
+
+        let mut receive = || loop {
+            if let Message::Text(text) = browser.read().unwrap() {
+                return serde_json::from_str::<ServerMessage>(&text).unwrap();
+            }
+        };
+        assert!(matches!(receive(), ServerMessage::Welcome { seat: 0, .. }));
+        loop {
+            match receive() {
+                ServerMessage::Event(GameEvent::Rolled { seat, dice, .. }) => {
+                    assert_eq!((seat, dice), (0, vec![2, 2, 2, 5, 6]));
+                    break;
+                }
+                ServerMessage::Event(_) => {}
+                message => panic!("expected an event, got {:?}", message),
+            }
+        }
+        assert!(
+            matches!(receive(), ServerMessage::Error { message } if message.starts_with("invalid message"))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Directory holding everything the game persists between sessions. `YAHTZEE_DATA_DIR` takes
+/// precedence, otherwise the XDG data directory is used.
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("YAHTZEE_DATA_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME") {
+        return Some(PathBuf::from(dir).join("yahtzee"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/yahtzee"))
+}
+
+/// Directory holding the user's configuration. `YAHTZEE_CONFIG_DIR` takes precedence, otherwise
+/// the XDG config directory is used.
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("YAHTZEE_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join("yahtzee"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/yahtzee"))
+}
+
+pub(crate) fn data_file(file_name: &str) -> io::Result<PathBuf> {
+    data_dir().map(|dir| dir.join(file_name)).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "no data directory, set YAHTZEE_DATA_DIR or HOME",
+        )
+    })
+}
+
+fn create_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn append_json_line<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    create_parent_dir(path)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())
+}
+
+/// Reads a JSON file, returning `None` if it doesn't exist.
+pub(crate) fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+pub(crate) fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    create_parent_dir(path)?;
+    fs::write(path, serde_json::to_string_pretty(value)?)
+}
+
+/// The values read from a JSON lines file.
+#[derive(Debug, PartialEq)]
+pub(crate) struct JsonLines<T> {
+    pub values: Vec<T>,
+    /// Lines that couldn't be read, like one cut short by a crash.
+    pub skipped: usize,
+}
+
+/// Reads a JSON lines file, treating a missing file as empty. A broken line only loses itself.
+pub(crate) fn read_json_lines<T: DeserializeOwned>(path: &Path) -> io::Result<JsonLines<T>> {
+    let mut lines = JsonLines {
+        values: vec![],
+        skipped: 0,
+    };
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(lines),
+        Err(error) => return Err(error),
+    };
+    for line in BufReader::new(file).split(b'\n') {
+        let line = line?;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        match serde_json::from_slice(&line) {
+            Ok(value) => lines.values.push(value),
+            Err(_) => lines.skipped += 1,
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "yahtzee-test-{}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst),
+        name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let path = temp_path("dir").join("value.json");
+        assert_eq!(read_json::<Vec<u32>>(&path).unwrap(), None);
+
+        write_json(&path, &vec![1, 2]).unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&path).unwrap(), Some(vec![1, 2]));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_json_lines_round_trip() {
+        let path = temp_path("lines.jsonl");
+        assert_eq!(
+            read_json_lines::<Vec<u32>>(&path).unwrap().values,
+            Vec::<Vec<u32>>::new()
+        );
+
+        append_json_line(&path, &vec![1, 2]).unwrap();
+        append_json_line(&path, &vec![3]).unwrap();
+        assert_eq!(
+            read_json_lines::<Vec<u32>>(&path).unwrap(),
+            JsonLines {
+                values: vec![vec![1, 2], vec![3]],
+                skipped: 0
+            }
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_broken_json_lines_are_skipped() {
+        let path = temp_path("broken.jsonl");
+        append_json_line(&path, &vec![1, 2]).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"[3,\n\xff\xfe\n").unwrap();
+        append_json_line(&path, &vec![4]).unwrap();
+        assert_eq!(
+            read_json_lines::<Vec<u32>>(&path).unwrap(),
+            JsonLines {
+                values: vec![vec![1, 2], vec![4]],
+                skipped: 2
+            }
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use crossterm::cursor::Show;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use std::io::{self, Stdout};
+use std::ops::{Deref, DerefMut};
+use std::panic;
+use std::sync::Once;
+use tui::backend::CrosstermBackend;
+use tui::Terminal;
+
+type Backend = CrosstermBackend<Stdout>;
+
+/// Owns the terminal while the game runs: raw mode, the alternate screen and mouse capture are
+/// switched on when it's created and always switched off again when it's dropped, on a panic too.
+pub(crate) struct TerminalGuard {
+    terminal: Terminal<Backend>,
+}
+
+impl TerminalGuard {
+    pub fn new() -> io::Result<Self> {
+        install_panic_hook();
+        enter()?;
+        match Terminal::new(CrosstermBackend::new(io::stdout())) {
+            Ok(terminal) => Ok(Self { terminal }),
+            // There is no guard to drop yet
+            Err(error) => {
+                restore();
+                Err(error)
+            }
+        }
+    }
+
+    /// Hands the terminal back to the shell and stops the process, like Ctrl-Z does outside of
+    /// raw mode. Takes the terminal over again once the shell resumes the game.
+    pub fn suspend(&mut self) -> io::Result<()> {
+        restore();
+        stop_process();
+        enter()?;
+        // The screen was changed behind our back, so everything has to be drawn again
+        self.terminal.clear()
+    }
+}
+
+impl Deref for TerminalGuard {
+    type Target = Terminal<Backend>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.terminal
+    }
+}
+
+impl DerefMut for TerminalGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.terminal
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Ctrl-C and Ctrl-Z arrive as key presses in raw mode and have to be handled by the game.
+pub(crate) fn is_interrupt(event: &KeyEvent) -> bool {
+    is_control_key(event, 'c')
+}
+
+pub(crate) fn is_suspend(event: &KeyEvent) -> bool {
+    is_control_key(event, 'z')
+}
+
+fn is_control_key(event: &KeyEvent, key: char) -> bool {
+    event.modifiers.contains(KeyModifiers::CONTROL) && event.code == KeyCode::Char(key)
+}
+
+fn enter() -> io::Result<()> {
+    enable_raw_mode()?;
+    let result = execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture);
+    if result.is_err() {
+        restore();
+    }
+    result
+}
+
+/// Undoes everything `enter` did. Every step is attempted even if an earlier one fails, so as much
+/// of the terminal as possible is usable again.
+fn restore() {
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+        Show
+    );
+}
+
+/// Restores the terminal before the panic message is printed, which would otherwise end up on
+/// the alternate screen and vanish.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            default_hook(info);
+        }));
+    });
+}
+
+#[cfg(unix)]
+fn stop_process() {
+    // SAFETY: raising a signal for the current process has no memory safety requirements
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_process() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_keys_are_recognised() {
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert!(is_interrupt(&ctrl_c));
+        assert!(!is_suspend(&ctrl_c));
+        assert!(is_suspend(&KeyEvent::new(
+            KeyCode::Char('z'),
+            KeyModifiers::CONTROL
+        )));
+        assert!(!is_interrupt(&KeyEvent::new(
+            KeyCode::Char('c'),
+            KeyModifiers::NONE
+        )));
+    }
+}
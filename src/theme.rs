@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use tui::style::{Color, Modifier, Style};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ThemeName {
+    Dark,
+    Light,
+    HighContrast,
+    NoColor,
+}
+
+impl ThemeName {
+    pub const ALL: [ThemeName; 4] = [
+        ThemeName::Dark,
+        ThemeName::Light,
+        ThemeName::HighContrast,
+        ThemeName::NoColor,
+    ];
+
+    /// Picks the theme from the command line, then the config file, and only falls back to
+    /// `no-color` if `NO_COLOR` is set and nothing was chosen explicitly.
+    pub fn choose(flag: Option<Self>, config: Option<Self>, no_color: bool) -> Self {
+        flag.or(config).unwrap_or(if no_color {
+            ThemeName::NoColor
+        } else {
+            ThemeName::Dark
+        })
+    }
+}
+
+impl fmt::Display for ThemeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ThemeName::Dark => "dark",
+            ThemeName::Light => "light",
+            ThemeName::HighContrast => "high-contrast",
+            ThemeName::NoColor => "no-color",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("unknown theme '{0}', expected one of dark, light, high-contrast or no-color")]
+pub(crate) struct UnknownTheme(String);
+
+impl FromStr for ThemeName {
+    type Err = UnknownTheme;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ThemeName::ALL
+            .iter()
+            .find(|theme| theme.to_string() == s)
+            .copied()
+            .ok_or_else(|| UnknownTheme(s.to_string()))
+    }
+}
+
+/// The styles the UI is drawn with.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Theme {
+    /// Regular text and borders.
+    pub text: Style,
+    /// Key hints, headings, held dice, the selection input and charts.
+    pub accent: Style,
+    /// Dice that are still tumbling.
+    pub muted: Style,
+    /// Scorecard rows that have been filled already.
+    pub filled: Style,
+    /// The scorecard cursor and chart values.
+    pub highlight: Style,
+    /// Popups asking the player for confirmation.
+    pub popup: Style,
+    /// Status bar messages by severity.
+    pub info: Style,
+    pub warning: Style,
+    pub error: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(ThemeName::Dark)
+    }
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        let fg = |color| Style::default().fg(color);
+        match name {
+            ThemeName::Dark => Self {
+                text: fg(Color::White),
+                accent: fg(Color::Yellow),
+                muted: fg(Color::DarkGray),
+                filled: Style::default().add_modifier(Modifier::DIM),
+                highlight: Style::default()
+                    .bg(Color::Yellow)
+                    .fg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+                popup: fg(Color::Yellow),
+                info: fg(Color::Green),
+                warning: fg(Color::Yellow),
+                error: fg(Color::Red),
+            },
+            ThemeName::Light => Self {
+                text: fg(Color::Black),
+                accent: fg(Color::Blue),
+                muted: fg(Color::Gray),
+                filled: fg(Color::DarkGray),
+                highlight: Style::default()
+                    .bg(Color::Blue)
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+                popup: fg(Color::Blue),
+                info: fg(Color::Green),
+                warning: fg(Color::Magenta),
+                error: fg(Color::Red),
+            },
+            ThemeName::HighContrast => Self {
+                text: fg(Color::White),
+                accent: fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                muted: fg(Color::Gray),
+                filled: fg(Color::Gray),
+                highlight: Style::default()
+                    .bg(Color::White)
+                    .fg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+                popup: Style::default()
+                    .bg(Color::Black)
+                    .fg(Color::LightYellow)
+                    .add_modifier(Modifier::BOLD),
+                info: fg(Color::LightGreen).add_modifier(Modifier::BOLD),
+                warning: fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                error: fg(Color::LightRed).add_modifier(Modifier::BOLD),
+            },
+            ThemeName::NoColor => Self {
+                text: Style::default(),
+                accent: Style::default().add_modifier(Modifier::BOLD),
+                muted: Style::default().add_modifier(Modifier::DIM),
+                filled: Style::default().add_modifier(Modifier::DIM),
+                highlight: Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+                popup: Style::default().add_modifier(Modifier::BOLD),
+                info: Style::default(),
+                warning: Style::default().add_modifier(Modifier::BOLD),
+                error: Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(None, None, false, ThemeName::Dark)]
+    #[case(None, None, true, ThemeName::NoColor)]
+    #[case(None, Some(ThemeName::Light), true, ThemeName::Light)]
+    #[case(
+        Some(ThemeName::HighContrast),
+        Some(ThemeName::Light),
+        true,
+        ThemeName::HighContrast
+    )]
+    fn test_choose_theme(
+        #[case] flag: Option<ThemeName>,
+        #[case] config: Option<ThemeName>,
+        #[case] no_color: bool,
+        #[case] expected: ThemeName,
+    ) {
+        assert_eq!(ThemeName::choose(flag, config, no_color), expected);
+    }
+
+    #[test]
+    fn test_theme_names_round_trip() {
+        for theme in ThemeName::ALL {
+            assert_eq!(theme.to_string().parse(), Ok(theme));
+        }
+        assert_eq!(
+            "solarized".parse::<ThemeName>(),
+            Err(UnknownTheme("solarized".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_no_color_theme_uses_no_colors() {
+        let theme = Theme::new(ThemeName::NoColor);
+        for style in [
+            theme.text,
+            theme.accent,
+            theme.muted,
+            theme.filled,
+            theme.highlight,
+            theme.popup,
+            theme.info,
+            theme.warning,
+            theme.error,
+        ] {
+            assert_eq!(style.fg, None);
+            assert_eq!(style.bg, None);
+        }
+    }
+}
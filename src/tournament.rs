@@ -0,0 +1,471 @@
+//! Bots playing each other over many games. Every combination of bots gets its own table, and
+//! everyone at a table rolls the same dice, so the results come down to the choices made with
+//! them rather than luck.
+
+use crate::bot::{play_step, Bot};
+use crate::dice::{DieSpec, SeededRoller};
+use crate::game::{Game, GameError};
+use serde::Serialize;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Where everyone's rating starts.
+const INITIAL_ELO: f64 = 1500.0;
+/// How far a rating moves after a game against a single opponent.
+const ELO_K: f64 = 16.0;
+/// For 95% confidence intervals.
+const Z_95: f64 = 1.96;
+
+/// How one bot did over a tournament.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Standing {
+    pub name: String,
+    pub scores: Vec<u32>,
+    /// Games won, a tie for the best score shared between the bots in it.
+    pub wins: f64,
+    pub elo: f64,
+    pub faults: usize,
+}
+
+impl Standing {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            scores: vec![],
+            wins: 0.0,
+            elo: INITIAL_ELO,
+            faults: 0,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        match self.games() {
+            0 => 0.0,
+            games => self.wins / games as f64,
+        }
+    }
+
+    /// Half the width of the 95% confidence interval around the win rate.
+    pub fn win_rate_margin(&self) -> f64 {
+        match self.games() {
+            0 => 0.0,
+            games => {
+                let rate = self.win_rate();
+                Z_95 * (rate * (1.0 - rate) / games as f64).sqrt()
+            }
+        }
+    }
+
+    pub fn mean_score(&self) -> f64 {
+        match self.games() {
+            0 => 0.0,
+            games => {
+                self.scores
+                    .iter()
+                    .map(|score| f64::from(*score))
+                    .sum::<f64>()
+                    / games as f64
+            }
+        }
+    }
+
+    /// Half the width of the 95% confidence interval around the mean score.
+    pub fn mean_score_margin(&self) -> f64 {
+        let games = self.games();
+        if games < 2 {
+            return 0.0;
+        }
+        let mean = self.mean_score();
+        let variance = self
+            .scores
+            .iter()
+            .map(|score| (f64::from(*score) - mean).powi(2))
+            .sum::<f64>()
+            / (games - 1) as f64;
+        Z_95 * (variance / games as f64).sqrt()
+    }
+}
+
+/// Plays every combination of `seats` bots a number of times. Game `n` at every table rolls the
+/// dice seeded with the tournament's seed plus `n`, so the same tournament can be played again.
+pub(crate) struct Tournament {
+    die_spec: DieSpec,
+    games: usize,
+    seed: u64,
+    seats: usize,
+}
+
+impl Tournament {
+    pub fn new(die_spec: DieSpec, games: usize, seed: u64) -> Self {
+        Self {
+            die_spec,
+            games,
+            seed,
+            seats: 2,
+        }
+    }
+
+    /// How many bots play each game, all of them if there are fewer.
+    pub fn with_seats(mut self, seats: usize) -> Self {
+        self.seats = seats;
+        self
+    }
+
+    /// The standings come back best first.
+    pub fn run(&self, entrants: &mut [(String, Box<dyn Bot>)]) -> Result<Vec<Standing>, GameError> {
+        let mut standings: Vec<_> = entrants
+            .iter()
+            .map(|(name, _)| Standing::new(name))
+            .collect();
+        let tables = combinations(entrants.len(), self.seats.clamp(1, entrants.len().max(1)));
+        for round in 0..self.games {
+            let seed = self.seed.wrapping_add(round as u64);
+            for table in &tables {
+                // The seats move on by one every game, so nobody always gets to go first
+                let first = round % table.len();
+                let mut seats = table.clone();
+                seats.rotate_left(first);
+                let mut bots: Vec<&mut dyn Bot> = entrants
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(entrant, _)| table.contains(entrant))
+                    .map(|(_, (_, bot))| bot.as_mut() as &mut dyn Bot)
+                    .collect();
+                bots.rotate_left(first);
+
+                let game = Game::new(self.die_spec.clone(), seats.len())
+                    .with_roller_per_seat(|_| Box::new(SeededRoller::new(seed)));
+                let scores = play_game(game, &mut bots)?;
+                record(&mut standings, &seats, &scores);
+            }
+        }
+        for (standing, (_, bot)) in standings.iter_mut().zip(entrants.iter()) {
+            standing.faults = bot.faults();
+        }
+        standings.sort_by(|a, b| {
+            b.win_rate()
+                .total_cmp(&a.win_rate())
+                .then(b.mean_score().total_cmp(&a.mean_score()))
+        });
+        Ok(standings)
+    }
+}
+
+/// Adds a game's scores to the standings of the bots that were at `seats`. Ratings change as if
+/// every bot at the table had played each of the others.
+fn record(standings: &mut [Standing], seats: &[usize], scores: &[u32]) {
+    let best = scores.iter().copied().max().unwrap_or(0);
+    let winners = scores.iter().filter(|score| **score == best).count();
+    let k = ELO_K / (seats.len().max(2) - 1) as f64;
+    let changes: Vec<f64> = seats
+        .iter()
+        .zip(scores)
+        .map(|(seat, score)| {
+            let rating = standings[*seat].elo;
+            seats
+                .iter()
+                .zip(scores)
+                .filter(|(other, _)| *other != seat)
+                .map(|(other, other_score)| {
+                    let expected =
+                        1.0 / (1.0 + 10f64.powf((standings[*other].elo - rating) / 400.0));
+                    let actual = match score.cmp(other_score) {
+                        std::cmp::Ordering::Greater => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Less => 0.0,
+                    };
+                    k * (actual - expected)
+                })
+                .sum()
+        })
+        .collect();
+    for ((seat, score), change) in seats.iter().zip(scores).zip(changes) {
+        let standing = &mut standings[*seat];
+        standing.scores.push(*score);
+        standing.elo += change;
+        if *score == best {
+            standing.wins += 1.0 / winners as f64;
+        }
+    }
+}
+
+/// Every way to pick `k` of `n` things, each in ascending order.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    (k - 1..n)
+        .flat_map(|last| {
+            combinations(last, k - 1)
+                .into_iter()
+                .map(move |mut picked| {
+                    picked.push(last);
+                    picked
+                })
+        })
+        .collect()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for ReportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A line of the standings as written to JSON.
+#[derive(Serialize)]
+struct ReportRow<'a> {
+    rank: usize,
+    name: &'a str,
+    games: usize,
+    wins: f64,
+    win_rate: f64,
+    win_rate_margin: f64,
+    mean_score: f64,
+    mean_score_margin: f64,
+    elo: f64,
+    faults: usize,
+}
+
+pub(crate) fn report(standings: &[Standing], format: ReportFormat) -> String {
+    let rows: Vec<_> = standings
+        .iter()
+        .enumerate()
+        .map(|(index, standing)| ReportRow {
+            rank: index + 1,
+            name: &standing.name,
+            games: standing.games(),
+            wins: standing.wins,
+            win_rate: standing.win_rate(),
+            win_rate_margin: standing.win_rate_margin(),
+            mean_score: standing.mean_score(),
+            mean_score_margin: standing.mean_score_margin(),
+            elo: standing.elo,
+            faults: standing.faults,
+        })
+        .collect();
+    let mut report = String::new();
+    match format {
+        ReportFormat::Json => {
+            report = serde_json::to_string_pretty(&rows).unwrap_or_default();
+            report.push('\n');
+        }
+        ReportFormat::Csv => {
+            report.push_str(
+                "rank,name,games,wins,win_rate,win_rate_margin,mean_score,mean_score_margin,elo,faults\n",
+            );
+            for row in rows {
+                let _ = writeln!(
+                    report,
+                    "{},{},{},{},{:.4},{:.4},{:.2},{:.2},{:.1},{}",
+                    row.rank,
+                    csv_field(row.name),
+                    row.games,
+                    row.wins,
+                    row.win_rate,
+                    row.win_rate_margin,
+                    row.mean_score,
+                    row.mean_score_margin,
+                    row.elo,
+                    row.faults
+                );
+            }
+        }
+        ReportFormat::Text => {
+            let width = rows
+                .iter()
+                .map(|row| row.name.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max("Bot".len());
+            let _ = writeln!(
+                report,
+                "{:>4}  {:<width$}  {:>5}  {:>6}  {:>15}  {:>14}  {:>6}  {:>6}",
+                "Rank",
+                "Bot",
+                "Games",
+                "Wins",
+                "Win rate",
+                "Mean score",
+                "Elo",
+                "Faults",
+                width = width
+            );
+            for row in rows {
+                let _ = writeln!(
+                    report,
+                    "{:>4}  {:<width$}  {:>5}  {:>6.1}  {:>15}  {:>14}  {:>6.0}  {:>6}",
+                    row.rank,
+                    row.name,
+                    row.games,
+                    row.wins,
+                    format!(
+                        "{:.1}% ± {:.1}%",
+                        row.win_rate * 100.0,
+                        row.win_rate_margin * 100.0
+                    ),
+                    format!("{:.1} ± {:.1}", row.mean_score, row.mean_score_margin),
+                    row.elo,
+                    row.faults,
+                    width = width
+                );
+            }
+        }
+    }
+    report
+}
+
+/// Quotes a CSV field if it needs it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Plays a whole game between `bots`, seated in order, and returns their final scores.
+pub(crate) fn play_game(mut game: Game, bots: &mut [&mut dyn Bot]) -> Result<Vec<u32>, GameError> {
+    let die_spec = game.snapshot().die_spec;
+    let players = bots.len();
+    for (seat, bot) in bots.iter_mut().enumerate() {
+        game.join(&format!("bot {}", seat + 1))?;
+        bot.new_game(seat, players, &die_spec);
+    }
+    for seat in 0..bots.len() {
+        game.ready(seat)?;
+    }
+    while let Some(seat) = game.snapshot().current {
+        play_step(&mut game, seat, &mut *bots[seat])?;
+    }
+    let scores: Vec<u32> = game
+        .players()
+        .iter()
+        .map(|player| player.score.total_score())
+        .collect();
+    for bot in bots.iter_mut() {
+        bot.game_over(&scores);
+    }
+    Ok(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{GreedyBot, Strategy};
+
+    fn greedy_bots(names: &[&str]) -> Vec<(String, Box<dyn Bot>)> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), Box::new(GreedyBot) as Box<dyn Bot>))
+            .collect()
+    }
+
+    #[test]
+    fn test_bots_play_whole_games() {
+        let game = Game::new(DieSpec::default(), 2).with_roller(Box::new(SeededRoller::new(3)));
+        let scores = play_game(game, &mut [&mut GreedyBot, &mut GreedyBot]).unwrap();
+        assert_eq!(scores.len(), 2);
+        assert!(scores.iter().all(|score| *score > 0));
+    }
+
+    #[test]
+    fn test_tournaments_can_be_replayed() {
+        let tournament = Tournament::new(DieSpec::default(), 6, 42);
+        let standings = tournament
+            .run(&mut greedy_bots(&["ada", "bob", "cy"]))
+            .unwrap();
+        assert_eq!(standings.len(), 3);
+        // Everyone plays both others six times
+        assert!(standings.iter().all(|standing| standing.games() == 12));
+        let wins: f64 = standings.iter().map(|standing| standing.wins).sum();
+        assert!((wins - 18.0).abs() < 1e-9);
+        assert!(standings[0].win_rate() >= standings[1].win_rate());
+        assert_eq!(
+            standings,
+            tournament
+                .run(&mut greedy_bots(&["ada", "bob", "cy"]))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_dice_tie_equal_bots() {
+        let tournament = Tournament::new(DieSpec::default(), 5, 9);
+        let standings = tournament.run(&mut greedy_bots(&["ada", "bob"])).unwrap();
+        for standing in standings {
+            assert_eq!(standing.wins, 2.5);
+            assert_eq!(standing.elo, INITIAL_ELO);
+        }
+    }
+
+    #[test]
+    fn test_stronger_bots_rank_higher() {
+        let mut entrants: Vec<(String, Box<dyn Bot>)> = vec![
+            ("random".to_string(), Strategy::Random.bot(1)),
+            ("greedy".to_string(), Strategy::Greedy.bot(1)),
+        ];
+        let standings = Tournament::new(DieSpec::default(), 20, 3)
+            .run(&mut entrants)
+            .unwrap();
+        assert_eq!(standings[0].name, "greedy");
+        assert!(standings[0].elo > INITIAL_ELO);
+        let lowest_mean = standings[0].mean_score() - standings[0].mean_score_margin();
+        assert!(lowest_mean > standings[1].mean_score());
+    }
+
+    #[test]
+    fn test_tables_are_every_combination() {
+        assert_eq!(combinations(3, 2), vec![vec![0, 1], vec![0, 2], vec![1, 2]]);
+        assert_eq!(combinations(4, 3).len(), 4);
+        assert_eq!(combinations(2, 2), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_elo_moves_towards_the_result() {
+        let mut standings = vec![Standing::new("ada"), Standing::new("bob")];
+        record(&mut standings, &[1, 0], &[180, 240]);
+        assert_eq!(standings[0].elo, INITIAL_ELO + ELO_K / 2.0);
+        assert_eq!(standings[1].elo, INITIAL_ELO - ELO_K / 2.0);
+        assert_eq!(standings[0].wins, 1.0);
+        assert_eq!(standings[1].scores, [180]);
+    }
+
+    #[test]
+    fn test_reports() {
+        let mut standings = vec![Standing::new("greedy, v2"), Standing::new("upper")];
+        record(&mut standings, &[0, 1], &[200, 150]);
+        record(&mut standings, &[1, 0], &[210, 210]);
+
+        let csv = report(&standings, ReportFormat::Csv);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("1,\"greedy, v2\",2,1.5,0.7500,"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&report(&standings, ReportFormat::Json)).unwrap();
+        assert_eq!(json[1]["name"], "upper");
+        assert_eq!(json[1]["wins"], 0.5);
+
+        let text = report(&standings, ReportFormat::Text);
+        assert!(text.lines().nth(1).unwrap().contains("75.0% ± 60.0%"));
+    }
+}
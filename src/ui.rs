@@ -1,210 +1,1167 @@
+use crate::app::{Severity, View};
+use crate::client::RemoteGame;
+use crate::help::{help_lines, HelpLine};
+use crate::keymap::{Action, Keymap};
+use crate::probability::MAX_FACES;
+use crate::score::Category;
+use crate::theme::Theme;
 use crate::{AppState, InputMode, Score};
 use tui::backend::Backend;
-use tui::layout::{Constraint, Direction, Layout};
-use tui::style::{Color, Modifier, Style};
+use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use tui::style::{Modifier, Style};
 use tui::text::{Span, Spans};
-use tui::widgets::{Block, BorderType, Borders, List, ListItem, Paragraph, Tabs};
+use tui::widgets::{
+    BarChart, Block, BorderType, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row,
+    Sparkline, Table, Tabs, Wrap,
+};
 use tui::Frame;
 
-pub(crate) fn render_app<B: Backend>(app_state: &AppState, rect: &mut Frame<B>) {
-    let menu_titles = vec!["New game", "Roll dice", "Quit"];
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum MenuItem {
+    NewGame,
+    RollDice,
+    DiceStats,
+    PlayerStats,
+    Help,
+    Quit,
+}
+
+impl MenuItem {
+    const ALL: [MenuItem; 6] = [
+        MenuItem::NewGame,
+        MenuItem::RollDice,
+        MenuItem::DiceStats,
+        MenuItem::PlayerStats,
+        MenuItem::Help,
+        MenuItem::Quit,
+    ];
+
+    fn title(&self, compact: bool) -> &'static str {
+        match (self, compact) {
+            (MenuItem::NewGame, false) => "New game",
+            (MenuItem::NewGame, true) => "New",
+            (MenuItem::RollDice, false) => "Roll dice",
+            (MenuItem::RollDice, true) => "Roll",
+            (MenuItem::DiceStats, false) => "Dice stats",
+            (MenuItem::DiceStats, true) => "Dice",
+            (MenuItem::PlayerStats, _) => "Profile",
+            (MenuItem::Help, _) => "Help",
+            (MenuItem::Quit, _) => "Quit",
+        }
+    }
+
+    fn action(&self) -> Action {
+        match self {
+            MenuItem::NewGame => Action::NewGame,
+            MenuItem::RollDice => Action::RollDice,
+            MenuItem::DiceStats => Action::DiceStats,
+            MenuItem::PlayerStats => Action::PlayerStats,
+            MenuItem::Help => Action::Help,
+            MenuItem::Quit => Action::Quit,
+        }
+    }
+
+    /// The first key bound to the item's action, shown in front of its title.
+    fn key_hint(&self, keymap: &Keymap) -> Option<String> {
+        keymap
+            .keys_for(self.action())
+            .first()
+            .map(|key| key.to_string())
+    }
+
+    fn width(&self, keymap: &Keymap, compact: bool) -> u16 {
+        let hint = self
+            .key_hint(keymap)
+            .map_or(0, |hint| hint.chars().count() + 1);
+        (hint + self.title(compact).chars().count()) as u16
+    }
+}
+
+/// Something the player can click on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum UiTarget {
+    Menu(MenuItem),
+    Die(usize),
+    Category(Category),
+}
+
+/// Where the clickable elements ended up in the last rendered frame.
+#[derive(Debug, Default)]
+pub(crate) struct UiRegions {
+    targets: Vec<(Rect, UiTarget)>,
+}
+
+impl UiRegions {
+    fn add(&mut self, area: Rect, target: UiTarget) {
+        self.targets.push((area, target));
+    }
+
+    pub fn target_at(&self, column: u16, row: u16) -> Option<UiTarget> {
+        self.targets
+            .iter()
+            .find(|(area, _)| {
+                area.left() <= column
+                    && column < area.right()
+                    && area.top() <= row
+                    && row < area.bottom()
+            })
+            .map(|(_, target)| *target)
+    }
+}
+
+const MARGIN: u16 = 2;
+/// Menu, dice, selection and status bar, without the scorecard.
+const FIXED_HEIGHT: u16 = 3 + DIE_HEIGHT + 3 + 3 + 1;
+const MIN_WIDTH: u16 = 55;
+const MIN_HEIGHT: u16 = FIXED_HEIGHT + 5;
+/// Below this width the full menu titles don't fit.
+const REGULAR_WIDTH: u16 = 80;
+/// From this width on the upper and lower section are shown side by side.
+const TWO_COLUMN_WIDTH: u16 = 100;
+
+/// How the app is laid out for the current terminal size.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct LayoutMode {
+    /// Drops the margin and abbreviates menu titles and category names.
+    compact: bool,
+    two_columns: bool,
+}
+
+impl LayoutMode {
+    /// `None` if the terminal is too small to play.
+    fn for_size(size: Rect) -> Option<Self> {
+        if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+            return None;
+        }
+        let two_columns = size.width >= TWO_COLUMN_WIDTH;
+        let rows = score_rows(&Score::new(), false);
+        let score_rows = if two_columns {
+            rows.0.len().max(rows.1.len())
+        } else {
+            rows.0.len() + rows.1.len()
+        };
+        let regular_height = 2 * MARGIN + FIXED_HEIGHT + score_rows as u16 + 2;
+        Some(Self {
+            compact: size.width < REGULAR_WIDTH || size.height < regular_height,
+            two_columns,
+        })
+    }
+
+    fn margin(&self) -> u16 {
+        if self.compact {
+            0
+        } else {
+            MARGIN
+        }
+    }
+}
 
+/// Renders the whole app and returns the regions that can be clicked.
+pub(crate) fn render_app<B: Backend>(app_state: &AppState, rect: &mut Frame<B>) -> UiRegions {
+    let mut regions = UiRegions::default();
+
+    let theme = &app_state.theme;
     let size = rect.size();
+    let layout = match LayoutMode::for_size(size) {
+        Some(layout) => layout,
+        None => {
+            render_too_small(app_state, rect, size);
+            return regions;
+        }
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .margin(2)
+        .margin(layout.margin())
         .constraints(
             [
                 Constraint::Length(3),
-                Constraint::Length(3),
+                Constraint::Length(DIE_HEIGHT + 3),
                 Constraint::Length(3),
                 Constraint::Min(2),
+                Constraint::Length(1),
             ]
             .as_ref(),
         )
         .split(size);
 
-    let menu = menu_titles
+    let menu = MenuItem::ALL
         .iter()
-        .map(|t| {
-            let (first, rest) = t.split_at(1);
-            Spans::from(vec![
-                Span::styled(
-                    first,
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::UNDERLINED),
-                ),
-                Span::styled(rest, Style::default().fg(Color::White)),
-            ])
+        .map(|item| {
+            let mut spans = vec![];
+            if let Some(hint) = item.key_hint(&app_state.keymap) {
+                spans.push(Span::styled(
+                    hint,
+                    theme.accent.add_modifier(Modifier::UNDERLINED),
+                ));
+                spans.push(Span::raw(" "));
+            }
+            spans.push(Span::styled(item.title(layout.compact), theme.text));
+            Spans::from(spans)
         })
         .collect();
 
     let tabs = Tabs::new(menu)
         .block(Block::default().title("Menu").borders(Borders::ALL))
-        .style(Style::default().fg(Color::White))
-        .highlight_style(Style::default().fg(Color::Yellow))
+        .style(theme.text)
+        .highlight_style(theme.accent)
         .divider(Span::raw("|"));
+    add_menu_regions(&mut regions, chunks[0], &app_state.keymap, layout.compact);
 
-    let dice_paragraph = Paragraph::new(
-        app_state
-            .dice_state
-            .dice
-            .0
-            .iter()
-            .map(|v| v.to_string())
-            .collect::<Vec<String>>()
-            .join(" "),
-    )
-    .style(Style::default().add_modifier(Modifier::BOLD))
-    .block(Block::default().borders(Borders::ALL).title(format!(
-        "Dice - roll {:?} / {:?}",
-        app_state.dice_state.number_of_rolls, app_state.dice_state.max_number_of_rolls
-    )));
+    if app_state.view != View::Game && app_state.view != View::Help {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(layout.margin())
+            .constraints(
+                [
+                    Constraint::Length(3),
+                    Constraint::Min(2),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
+            .split(size);
+        rect.render_widget(tabs, chunks[0]);
+        render_status(app_state, rect, chunks[2]);
+        match app_state.view {
+            View::DiceStats => rect.render_widget(render_dice_stats(app_state), chunks[1]),
+            View::PlayerStats => render_player_stats(app_state, rect, chunks[1]),
+            View::Game | View::Help => {}
+        }
+        return regions;
+    }
 
-    let input = Paragraph::new(app_state.selection_input.as_ref())
+    let (text, title) = match app_state.input_mode {
+        InputMode::Chatting => (&app_state.chat_input, "Chat"),
+        _ => (&app_state.selection_input, "Selection"),
+    };
+    let input = Paragraph::new(text.as_ref())
         .style(match app_state.input_mode {
-            InputMode::Normal => Style::default(),
-            InputMode::Selecting => Style::default().fg(Color::Yellow),
+            InputMode::Normal | InputMode::Confirming => theme.text,
+            InputMode::Selecting | InputMode::Chatting => theme.accent,
         })
-        .block(Block::default().borders(Borders::ALL).title("Selection"));
+        .block(Block::default().borders(Borders::ALL).title(title));
 
     rect.render_widget(tabs, chunks[0]);
-    rect.render_widget(dice_paragraph, chunks[1]);
-    rect.render_widget(input, chunks[2]);
-    rect.render_widget(render_score(&app_state.score), chunks[3]);
+    render_dice_row(app_state, rect, chunks[1], layout.compact, &mut regions);
+    match &app_state.remote {
+        Some(remote) => {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+                .split(chunks[2]);
+            rect.render_widget(input, chunks[0]);
+            rect.render_widget(render_players(remote, theme), chunks[1]);
+        }
+        None => rect.render_widget(input, chunks[2]),
+    }
+    match &app_state.remote {
+        Some(remote) if remote.spectating => {
+            render_scorecards(remote, theme, layout, rect, chunks[3])
+        }
+        _ => render_score(app_state, rect, chunks[3], layout, &mut regions),
+    }
+    render_status(app_state, rect, chunks[4]);
+
+    if let Some((category, points)) = app_state.pending_confirmation() {
+        render_confirmation(category, points, theme, rect, size);
+    }
+    if app_state.view == View::Help {
+        // Only the menu stays clickable while the overlay covers the game
+        regions
+            .targets
+            .retain(|(_, target)| matches!(target, UiTarget::Menu(_)));
+        let area = Rect {
+            height: chunks[3].bottom() - chunks[1].y,
+            ..chunks[1]
+        };
+        render_help(app_state, rect, area);
+    }
+    regions
 }
 
-fn render_score<'a>(score: &Score) -> List<'a> {
-    let scores = Block::default()
-        .borders(Borders::ALL)
-        .style(Style::default().fg(Color::White))
-        .title("Score")
-        .border_type(BorderType::Plain);
+/// Everyone at the table with their total, the player whose turn it is marked with `>`. Before
+/// the game starts, players who are ready are marked with `+` instead.
+fn render_players<'a>(remote: &'a RemoteGame, theme: &Theme) -> Paragraph<'a> {
+    let waiting = remote.current.is_none() && !remote.over;
+    let mut spans = vec![];
+    for (seat, player) in remote.players.iter().enumerate() {
+        let marker = match remote.current == Some(seat) {
+            true => ">",
+            false if waiting && player.ready => "+",
+            false => " ",
+        };
+        let style = match (player.present, remote.seat == Some(seat)) {
+            (false, _) => theme.muted,
+            (true, true) => theme.accent,
+            (true, false) => theme.text,
+        };
+        spans.push(Span::styled(
+            format!("{}{} {} ", marker, player.name, player.score.total_score()),
+            style,
+        ));
+    }
+    Paragraph::new(Spans::from(spans))
+        .block(Block::default().borders(Borders::ALL).title("Players"))
+        .style(theme.text)
+}
 
-    let mut items: Vec<_> = vec![];
-    items.extend(vec![
-        list_item(format!(
-            "Aces: {}",
-            score
-                .upper_section
-                .aces
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Twos: {}",
-            score
-                .upper_section
-                .twos
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Threes: {}",
-            score
-                .upper_section
-                .threes
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Fours: {}",
-            score
-                .upper_section
-                .fours
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Fives: {}",
-            score
-                .upper_section
-                .fives
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Sixes: {}",
-            score
-                .upper_section
-                .sixes
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Total upper section without bonus: {}",
-            score.upper_section.score_without_bonus(),
-        )),
-        list_item(format!(
-            "Bonus: {}",
-            score
-                .upper_section
-                .bonus
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Total upper section: {}",
-            score.upper_section.total_score(),
-        )),
-        list_item(format!(
-            "Three of a kind: {}",
-            score
-                .lower_section
-                .three_of_a_kind
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Four of a kind: {}",
-            score
-                .lower_section
-                .four_of_a_kind
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Full house: {}",
-            score
-                .lower_section
-                .full_house
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Small straight: {}",
-            score
-                .lower_section
-                .small_straight
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Large straight: {}",
-            score
-                .lower_section
-                .large_straight
-                .map_or("-".to_string(), |v| v.to_string())
-        )),
-        list_item(format!(
-            "Yahtzee: {}",
-            score
-                .lower_section
-                .yahtzee
-                .map_or("-".to_string(), |v| v.to_string())
+/// Every player's scorecard side by side, for spectators.
+fn render_scorecards<B: Backend>(
+    remote: &RemoteGame,
+    theme: &Theme,
+    layout: LayoutMode,
+    rect: &mut Frame<B>,
+    area: Rect,
+) {
+    let header = Row::new(std::iter::once(Cell::from("")).chain(
+        remote.players.iter().enumerate().map(|(seat, player)| {
+            let style = match (player.present, remote.current == Some(seat)) {
+                (false, _) => theme.muted,
+                (true, true) => theme.accent,
+                (true, false) => theme.text,
+            };
+            Cell::from(Span::styled(player.name.as_str(), style))
+        }),
+    ));
+    let points = |value: Option<u32>| value.map_or("-".to_string(), |v| v.to_string());
+    let row = |label: &'static str, cells: Vec<String>| {
+        Row::new(std::iter::once(label.to_string()).chain(cells))
+    };
+    let mut rows: Vec<_> = Category::ALL
+        .iter()
+        .map(|category| {
+            let label = match layout.compact {
+                true => category.short_name(),
+                false => category.name(),
+            };
+            let cells = remote
+                .players
+                .iter()
+                .map(|player| box_points(&player.score, *category))
+                .collect();
+            row(label, cells)
+        })
+        .collect();
+    rows.push(row(
+        "Bonus",
+        remote
+            .players
+            .iter()
+            .map(|player| points(player.score.upper_section.bonus))
+            .collect(),
+    ));
+    rows.push(row(
+        "Total",
+        remote
+            .players
+            .iter()
+            .map(|player| player.score.total_score().to_string())
+            .collect(),
+    ));
+
+    let label_width = match layout.compact {
+        true => 12,
+        false => 18,
+    };
+    let widths: Vec<_> = std::iter::once(Constraint::Length(label_width))
+        .chain(remote.players.iter().map(|_| Constraint::Length(8)))
+        .collect();
+    let table = Table::new(rows)
+        .header(header)
+        .widths(&widths)
+        .block(Block::default().borders(Borders::ALL).title("Scorecards"))
+        .style(theme.text);
+    rect.render_widget(table, area);
+}
+
+/// Mirrors how `Tabs` lays out its titles: one space of padding on each side and a one character
+/// divider between them.
+fn add_menu_regions(regions: &mut UiRegions, area: Rect, keymap: &Keymap, compact: bool) {
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    let mut x = inner.x;
+    for item in MenuItem::ALL {
+        let width = item.width(keymap, compact);
+        x = x.saturating_add(1);
+        if x >= inner.right() {
+            break;
+        }
+        let width = width.min(inner.right() - x);
+        regions.add(Rect::new(x, inner.y, width, 1), UiTarget::Menu(item));
+        x = x.saturating_add(width + 2);
+    }
+}
+
+const DIE_WIDTH: u16 = 9;
+const DIE_HEIGHT: u16 = 5;
+
+/// Pip positions on a 3x3 grid for the faces of a regular six sided die.
+fn pips(value: u32) -> Option<[[bool; 3]; 3]> {
+    let (o, x) = (false, true);
+    Some(match value {
+        1 => [[o, o, o], [o, x, o], [o, o, o]],
+        2 => [[x, o, o], [o, o, o], [o, o, x]],
+        3 => [[x, o, o], [o, x, o], [o, o, x]],
+        4 => [[x, o, x], [o, o, o], [x, o, x]],
+        5 => [[x, o, x], [o, x, o], [x, o, x]],
+        6 => [[x, o, x], [x, o, x], [x, o, x]],
+        _ => return None,
+    })
+}
+
+/// The three inner lines of a die face. Faces without a pip pattern show their number instead.
+fn die_face_lines(value: u32) -> Vec<String> {
+    match pips(value) {
+        Some(rows) => rows
+            .iter()
+            .map(|row| {
+                let pips: Vec<&str> = row.iter().map(|pip| if *pip { "●" } else { " " }).collect();
+                format!(" {} ", pips.join(" "))
+            })
+            .collect(),
+        None => vec![String::new(), format!("{:^7}", value), String::new()],
+    }
+}
+
+/// Puts the odds panel to the right of the dice if it's switched on and there is room for it.
+fn render_dice_row<B: Backend>(
+    app_state: &AppState,
+    rect: &mut Frame<B>,
+    area: Rect,
+    compact: bool,
+    regions: &mut UiRegions,
+) {
+    if app_state.show_odds {
+        let lines = odds_lines(app_state, compact);
+        let panel_width = lines.iter().map(Spans::width).max().unwrap_or(0) as u16 + 2;
+        let number_of_dice = app_state.dice_state.number_of_dice as u16;
+        let dice_width = number_of_dice * (DIE_WIDTH + 1) - 1 + 2;
+        if area.width >= dice_width + panel_width {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(panel_width)].as_ref())
+                .split(area);
+            render_dice(app_state, rect, chunks[0], regions);
+            let panel = Paragraph::new(lines)
+                .block(Block::default().borders(Borders::ALL).title("Odds"))
+                .style(app_state.theme.text);
+            rect.render_widget(panel, chunks[1]);
+            return;
+        }
+    }
+    render_dice(app_state, rect, area, regions);
+}
+
+/// The chance of each pattern by the end of the turn, e.g. `Full house      8.3%`.
+fn odds_lines(app_state: &AppState, compact: bool) -> Vec<Spans<'_>> {
+    let odds = match &app_state.odds {
+        Some(odds) => odds,
+        None => {
+            return vec![
+                Spans::from("Only worked out for"),
+                Spans::from(format!("dice with {} faces", MAX_FACES)),
+                Spans::from("or fewer"),
+            ]
+        }
+    };
+    let name = |category: Category| {
+        if compact {
+            category.short_name()
+        } else {
+            category.name()
+        }
+    };
+    let label_width = odds
+        .iter()
+        .map(|odds| name(odds.category).len())
+        .max()
+        .unwrap_or(0);
+    odds.iter()
+        .map(|odds| {
+            Spans::from(vec![
+                Span::raw(format!(
+                    "{:<width$} ",
+                    name(odds.category),
+                    width = label_width
+                )),
+                Span::styled(
+                    format!("{:>6.1}%", odds.probability * 100.0),
+                    app_state.theme.accent,
+                ),
+            ])
+        })
+        .collect()
+}
+
+fn render_dice<B: Backend>(
+    app_state: &AppState,
+    rect: &mut Frame<B>,
+    area: Rect,
+    regions: &mut UiRegions,
+) {
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "Dice - roll {:?} / {:?}",
+        app_state.dice_state.number_of_rolls, app_state.dice_state.max_number_of_rolls
+    ));
+    let inner = block.inner(area);
+    rect.render_widget(block, area);
+
+    let dice = app_state.displayed_dice();
+    if dice.is_empty() {
+        let hint = match app_state.keymap.keys_for(Action::RollDice).first() {
+            Some(key) => format!("Press {} to roll the dice", key),
+            None => "Click Roll dice to roll the dice".to_string(),
+        };
+        rect.render_widget(Paragraph::new(hint).style(app_state.theme.text), inner);
+        return;
+    }
+
+    let theme = &app_state.theme;
+    let selected_for_reroll = app_state.dice_selected_for_reroll();
+    let is_selecting = matches!(app_state.input_mode, InputMode::Selecting);
+    let is_tumbling = |id: &usize| {
+        app_state
+            .animation
+            .as_ref()
+            .is_some_and(|animation| animation.is_rolling(*id))
+    };
+
+    for (id, value) in dice.iter().enumerate() {
+        let x = inner.x + id as u16 * (DIE_WIDTH + 1);
+        if x + DIE_WIDTH > inner.x + inner.width {
+            break;
+        }
+        let die_area = Rect::new(x, inner.y, DIE_WIDTH, DIE_HEIGHT.min(inner.height));
+        let label_area = Rect::new(x, inner.y + DIE_HEIGHT, DIE_WIDTH, 1);
+
+        let is_held = is_selecting && !selected_for_reroll.contains(&id);
+        let border_style = if is_tumbling(&id) {
+            theme.muted
+        } else if is_held {
+            theme.accent
+        } else {
+            theme.text
+        };
+        let face = Paragraph::new(
+            die_face_lines(*value)
+                .into_iter()
+                .map(Spans::from)
+                .collect::<Vec<_>>(),
+        )
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(border_style),
+        );
+
+        let label = match (is_selecting, is_held) {
+            (false, _) => format!("{}", id + 1),
+            (true, true) => format!("{} hold", id + 1),
+            (true, false) => format!("{} roll", id + 1),
+        };
+
+        rect.render_widget(face, die_area);
+        regions.add(die_area, UiTarget::Die(id));
+        if label_area.y < inner.y + inner.height {
+            rect.render_widget(
+                Paragraph::new(format!("{:^width$}", label, width = DIE_WIDTH as usize))
+                    .style(border_style),
+                label_area,
+            );
+        }
+    }
+}
+
+fn render_too_small<B: Backend>(app_state: &AppState, rect: &mut Frame<B>, area: Rect) {
+    let notice = Paragraph::new(vec![
+        Spans::from("Terminal too small"),
+        Spans::from(format!(
+            "{}x{}, need at least {}x{}",
+            area.width, area.height, MIN_WIDTH, MIN_HEIGHT
         )),
-        list_item(format!(
-            "Chance: {}",
-            score
-                .lower_section
-                .chance
-                .map_or("-".to_string(), |v| v.to_string())
+    ])
+    .style(app_state.theme.warning)
+    .alignment(Alignment::Center)
+    .wrap(Wrap { trim: true });
+    let height = 2.min(area.height);
+    rect.render_widget(
+        notice,
+        Rect::new(
+            area.x,
+            area.y + (area.height - height) / 2,
+            area.width,
+            height,
+        ),
+    );
+}
+
+fn render_status<B: Backend>(app_state: &AppState, rect: &mut Frame<B>, area: Rect) {
+    let theme = &app_state.theme;
+    let status = match &app_state.status {
+        Some(status) => status,
+        None => return,
+    };
+    let (prefix, style) = match status.severity {
+        Severity::Info => ("", theme.info),
+        Severity::Warning => ("Warning: ", theme.warning),
+        Severity::Error => ("Error: ", theme.error),
+    };
+    let message = Paragraph::new(Span::styled(format!("{}{}", prefix, status.text), style));
+    rect.render_widget(message, area);
+}
+
+fn render_confirmation<B: Backend>(
+    category: Category,
+    points: u32,
+    theme: &Theme,
+    rect: &mut Frame<B>,
+    area: Rect,
+) {
+    let question = if points == 0 {
+        format!("Scratch {} for 0? y/n", category.name())
+    } else {
+        format!("Score {} for {}? y/n", category.name(), points)
+    };
+    let popup_area = centered_rect(question.len() as u16 + 4, 3, area);
+    let popup = Paragraph::new(question)
+        .style(theme.popup)
+        .block(Block::default().borders(Borders::ALL).title("Confirm"));
+    rect.render_widget(Clear, popup_area);
+    rect.render_widget(popup, popup_area);
+}
+
+/// A rectangle of the given size centered in `area`, shrunk to fit if necessary.
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn render_dice_stats(app_state: &AppState) -> Paragraph<'_> {
+    Paragraph::new(app_state.dice_report.as_str()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(app_state.theme.text)
+            .title(format!(
+                "Dice statistics - {}",
+                app_state.dice_state.die_spec
+            )),
+    )
+}
+
+/// Draws the rules and key bindings over everything below the menu, starting at the line the
+/// player scrolled to.
+fn render_help<B: Backend>(app_state: &AppState, rect: &mut Frame<B>, area: Rect) {
+    let keymap = &app_state.keymap;
+    let theme = &app_state.theme;
+    let lines = help_lines(&app_state.dice_state, keymap);
+    let label_width = lines
+        .iter()
+        .map(|line| match line {
+            HelpLine::Entry(label, _) => label.chars().count(),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0);
+    let text: Vec<Spans> = lines
+        .into_iter()
+        .skip(app_state.help_scroll)
+        .map(|line| match line {
+            HelpLine::Heading(heading) => Spans::from(Span::styled(
+                heading,
+                theme.accent.add_modifier(Modifier::BOLD),
+            )),
+            HelpLine::Entry(label, explanation) => Spans::from(vec![
+                Span::styled(
+                    format!("{:<width$}  ", label, width = label_width),
+                    theme.accent,
+                ),
+                Span::raw(explanation),
+            ]),
+            HelpLine::Text(text) => Spans::from(text),
+            HelpLine::Blank => Spans::default(),
+        })
+        .collect();
+    let title = format!(
+        "Help - {}/{} to scroll, {} to close",
+        keymap.describe_keys(Action::HighlightPrevious),
+        keymap.describe_keys(Action::HighlightNext),
+        keymap.describe_keys(Action::Help)
+    );
+    let help = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(theme.text)
+            .title(title),
+    );
+    rect.render_widget(Clear, area);
+    rect.render_widget(help, area);
+}
+
+fn render_player_stats<B: Backend>(app_state: &AppState, rect: &mut Frame<B>, area: Rect) {
+    let theme = &app_state.theme;
+    let (player, stats) = match (&app_state.player, &app_state.player_stats) {
+        (Some(player), Some(stats)) => (player, stats),
+        _ => {
+            let paragraph = Paragraph::new("Player profiles are not available.")
+                .block(Block::default().borders(Borders::ALL).title("Player stats"));
+            rect.render_widget(paragraph, area);
+            return;
+        }
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(4),
+                Constraint::Min(6),
+                Constraint::Length(8),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    let bottom_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(chunks[2]);
+
+    let summary = Paragraph::new(vec![
+        Spans::from(format!(
+            "Games played: {}    Average score: {:.1}    Best score: {}",
+            stats.games_played, stats.average_score, stats.best_score
         )),
-        list_item(format!(
-            "Total lower section: {}",
-            score.lower_section.total_score(),
+        Spans::from(format!(
+            "Upper bonus: {:.0}%    Yahtzees: {}",
+            stats.upper_bonus_rate * 100.0,
+            stats.yahtzees
         )),
-        list_item(format!("TOTAL SCORE: {}", score.total_score(),)),
+    ])
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Player stats - {}", player.profile.name)),
+    );
+
+    let category_averages: Vec<(&str, u64)> = stats
+        .category_averages
+        .iter()
+        .map(|(category, average)| (category.short_name(), average.round() as u64))
+        .collect();
+    let categories = BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Average per category"),
+        )
+        .data(&category_averages)
+        .bar_width(4)
+        .bar_style(theme.accent)
+        .value_style(theme.highlight);
+
+    let histogram_labels: Vec<String> = stats
+        .histogram
+        .iter()
+        .map(|(bucket, _)| bucket.to_string())
+        .collect();
+    let histogram_data: Vec<(&str, u64)> = histogram_labels
+        .iter()
+        .zip(&stats.histogram)
+        .map(|(label, (_, count))| (label.as_str(), *count))
+        .collect();
+    let histogram = BarChart::default()
+        .block(Block::default().borders(Borders::ALL).title("Scores"))
+        .data(&histogram_data)
+        .bar_width(3)
+        .bar_style(theme.accent)
+        .value_style(theme.highlight);
+
+    let trend_data: Vec<u64> = stats
+        .trend
+        .iter()
+        .map(|(_, average)| average.round() as u64)
+        .collect();
+    let trend_title = match (stats.trend.first(), stats.trend.last()) {
+        (Some((first, _)), Some((last, _))) => format!("Daily average {} - {}", first, last),
+        _ => "Daily average".to_string(),
+    };
+    let trend = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(trend_title))
+        .data(&trend_data)
+        .style(theme.accent);
+
+    rect.render_widget(summary, chunks[0]);
+    rect.render_widget(categories, chunks[1]);
+    rect.render_widget(histogram, bottom_chunks[0]);
+    rect.render_widget(trend, bottom_chunks[1]);
+}
+
+type ScoreRow = (Option<Category>, String);
+
+/// The points in a box, with the Yahtzee bonus next to the Yahtzee box once there is one.
+fn box_points(score: &Score, category: Category) -> String {
+    let points = score
+        .get(category)
+        .map_or("-".to_string(), |v| v.to_string());
+    match score.lower_section.yahtzee_bonus {
+        bonus if category == Category::Yahtzee && bonus > 0 => format!("{} +{}", points, bonus),
+        _ => points,
+    }
+}
+
+/// The rows of the upper and lower section of the scorecard, with the category each row scores
+/// (totals have none).
+fn score_rows(score: &Score, compact: bool) -> (Vec<ScoreRow>, Vec<ScoreRow>) {
+    let points = |value: Option<u32>| value.map_or("-".to_string(), |v| v.to_string());
+    let category_row = |category: &Category| {
+        let name = if compact {
+            category.short_name()
+        } else {
+            category.name()
+        };
+        (
+            Some(*category),
+            format!("{}: {}", name, box_points(score, *category)),
+        )
+    };
+    let label = |regular: &'static str, short: &'static str| if compact { short } else { regular };
+
+    let mut upper: Vec<_> = Category::ALL[..6].iter().map(category_row).collect();
+    upper.extend(vec![
+        (
+            None,
+            format!(
+                "{}: {}",
+                label("Total upper section without bonus", "Upper"),
+                score.upper_section.score_without_bonus(),
+            ),
+        ),
+        (
+            None,
+            format!("Bonus: {}", points(score.upper_section.bonus)),
+        ),
+        (
+            None,
+            format!(
+                "{}: {}",
+                label("Total upper section", "Upper total"),
+                score.upper_section.total_score(),
+            ),
+        ),
     ]);
+    let mut lower: Vec<_> = Category::ALL[6..].iter().map(category_row).collect();
+    lower.extend(vec![
+        (
+            None,
+            format!(
+                "{}: {}",
+                label("Total lower section", "Lower total"),
+                score.lower_section.total_score(),
+            ),
+        ),
+        (
+            None,
+            format!("{}: {}", label("TOTAL SCORE", "TOTAL"), score.total_score()),
+        ),
+    ]);
+    (upper, lower)
+}
 
-    List::new(items).block(scores).highlight_style(
-        Style::default()
-            .bg(Color::Yellow)
-            .fg(Color::Black)
-            .add_modifier(Modifier::BOLD),
-    )
+fn render_score<B: Backend>(
+    app_state: &AppState,
+    rect: &mut Frame<B>,
+    area: Rect,
+    layout: LayoutMode,
+    regions: &mut UiRegions,
+) {
+    let (upper, mut lower) = score_rows(&app_state.score, layout.compact);
+    if layout.two_columns {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(area);
+        render_score_column(app_state, "Upper section", upper, rect, columns[0], regions);
+        render_score_column(app_state, "Lower section", lower, rect, columns[1], regions);
+    } else {
+        let mut rows = upper;
+        rows.append(&mut lower);
+        render_score_column(app_state, "Score", rows, rect, area, regions);
+    }
+}
+
+fn render_score_column<B: Backend>(
+    app_state: &AppState,
+    title: &str,
+    rows: Vec<ScoreRow>,
+    rect: &mut Frame<B>,
+    area: Rect,
+    regions: &mut UiRegions,
+) {
+    let scores = Block::default()
+        .borders(Borders::ALL)
+        .style(app_state.theme.text)
+        .title(title.to_string())
+        .border_type(BorderType::Plain);
+
+    let selected = rows.iter().position(|(category, _)| {
+        category.is_some() && *category == app_state.highlighted_category
+    });
+    let mut list_state = ListState::default();
+    list_state.select(selected);
+
+    // A fresh `ListState` scrolls just far enough to show the selected row
+    let inner = scores.inner(area);
+    let offset = selected.map_or(0, |selected| {
+        (selected + 1).saturating_sub(inner.height as usize)
+    });
+    for (row, (category, _)) in rows.iter().enumerate().skip(offset) {
+        let y = inner.y + (row - offset) as u16;
+        if y >= inner.bottom() {
+            break;
+        }
+        if let Some(category) = category {
+            regions.add(
+                Rect::new(inner.x, y, inner.width, 1),
+                UiTarget::Category(*category),
+            );
+        }
+    }
+
+    let items: Vec<_> = rows
+        .into_iter()
+        .map(|(category, content)| {
+            let is_filled =
+                category.is_some_and(|category| app_state.score.get(category).is_some());
+            if is_filled {
+                ListItem::new(Span::styled(content, app_state.theme.filled))
+            } else {
+                list_item(content)
+            }
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(scores)
+        .highlight_style(app_state.theme.highlight)
+        .highlight_symbol("> ");
+    rect.render_stateful_widget(list, area, &mut list_state);
 }
 
 fn list_item<'a>(content: String) -> ListItem<'a> {
     ListItem::new(Spans::from(vec![Span::styled(content, Style::default())]))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::{Dice, DieSpec};
+    use rstest::rstest;
+    use tui::backend::TestBackend;
+    use tui::Terminal;
+
+    fn render_regions(app_state: &AppState, width: u16, height: u16) -> UiRegions {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let mut regions = UiRegions::default();
+        terminal
+            .draw(|rect| regions = render_app(app_state, rect))
+            .unwrap();
+        regions
+    }
+
+    #[test]
+    fn test_regions_match_rendered_layout() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.dice_state.dice = Dice(vec![1, 2, 3, 4, 5]);
+        let regions = render_regions(&app_state, 80, 40);
+
+        // Menu: border at x = 2, then " n New game | r Roll dice | ..."
+        assert_eq!(
+            regions.target_at(4, 3),
+            Some(UiTarget::Menu(MenuItem::NewGame))
+        );
+        assert_eq!(regions.target_at(15, 3), None);
+        assert_eq!(
+            regions.target_at(17, 3),
+            Some(UiTarget::Menu(MenuItem::RollDice))
+        );
+        assert_eq!(
+            regions.target_at(70, 3),
+            Some(UiTarget::Menu(MenuItem::Quit))
+        );
+        // Dice start inside the dice block, each one 9 wide with a gap of 1
+        assert_eq!(regions.target_at(3, 6), Some(UiTarget::Die(0)));
+        assert_eq!(regions.target_at(15, 7), Some(UiTarget::Die(1)));
+        // First scorecard row sits right below the score block border
+        assert_eq!(
+            regions.target_at(10, 17),
+            Some(UiTarget::Category(Category::Aces))
+        );
+        assert_eq!(regions.target_at(10, 23), None);
+        assert_eq!(
+            regions.target_at(10, 26),
+            Some(UiTarget::Category(Category::ThreeOfAKind))
+        );
+    }
+
+    #[test]
+    fn test_regions_follow_scrolled_scorecard() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.highlighted_category = Some(Category::Chance);
+        let regions = render_regions(&app_state, 80, 24);
+        // The compact layout has room for seven rows, so the list scrolls to keep Chance visible
+        assert_eq!(
+            regions.target_at(10, 20),
+            Some(UiTarget::Category(Category::Yahtzee))
+        );
+        assert_eq!(
+            regions.target_at(10, 21),
+            Some(UiTarget::Category(Category::Chance))
+        );
+        assert_eq!(regions.target_at(10, 22), None);
+    }
+
+    #[test]
+    fn test_die_faces_show_pips() {
+        assert_eq!(die_face_lines(1), vec!["       ", "   ●   ", "       "]);
+        assert_eq!(die_face_lines(6), vec![" ●   ● ", " ●   ● ", " ●   ● "]);
+    }
+
+    #[test]
+    fn test_die_faces_without_pips_show_number() {
+        assert_eq!(die_face_lines(12), vec!["", "  12   ", ""]);
+    }
+
+    #[test]
+    fn test_help_overlay_covers_game() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.toggle_view(View::Help);
+        let mut terminal = Terminal::new(TestBackend::new(80, 40)).unwrap();
+        let mut regions = UiRegions::default();
+        terminal
+            .draw(|rect| regions = render_app(&app_state, rect))
+            .unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let row =
+            |y: u16| -> String { (0..80).map(|x| buffer.get(x, y).symbol.as_str()).collect() };
+        assert!(row(5).contains("Help - up/down to scroll, ? to close"));
+        assert!((7..38).any(|y| row(y).contains("Small straight")));
+        assert_eq!(
+            regions.target_at(4, 3),
+            Some(UiTarget::Menu(MenuItem::NewGame))
+        );
+        assert_eq!(regions.target_at(3, 7), None);
+        assert_eq!(regions.target_at(10, 17), None);
+    }
+
+    #[test]
+    fn test_no_color_theme_renders_without_colors() {
+        let mut app_state = AppState::new(DieSpec::default())
+            .with_theme(Theme::new(crate::theme::ThemeName::NoColor));
+        app_state.dice_state.dice = Dice(vec![1, 2, 3, 4, 5]);
+        app_state.select_dice_to_reroll().unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(80, 40)).unwrap();
+        terminal
+            .draw(|rect| {
+                render_app(&app_state, rect);
+            })
+            .unwrap();
+        assert!(
+            terminal
+                .backend()
+                .buffer()
+                .content()
+                .iter()
+                .all(|cell| cell.fg == tui::style::Color::Reset
+                    && cell.bg == tui::style::Color::Reset)
+        );
+    }
+
+    fn render_rows(app_state: &AppState, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|rect| {
+                render_app(app_state, rect);
+            })
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(80, 40, Some(LayoutMode { compact: false, two_columns: false }))]
+    #[case(80, 24, Some(LayoutMode { compact: true, two_columns: false }))]
+    #[case(60, 40, Some(LayoutMode { compact: true, two_columns: false }))]
+    #[case(120, 29, Some(LayoutMode { compact: true, two_columns: true }))]
+    #[case(120, 30, Some(LayoutMode { compact: false, two_columns: true }))]
+    #[case(54, 40, None)]
+    #[case(80, 19, None)]
+    fn test_layout_mode_follows_terminal_size(
+        #[case] width: u16,
+        #[case] height: u16,
+        #[case] expected: Option<LayoutMode>,
+    ) {
+        assert_eq!(
+            LayoutMode::for_size(Rect::new(0, 0, width, height)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_small_terminal_shows_notice() {
+        let app_state = AppState::new(DieSpec::default());
+        let rows = render_rows(&app_state, 40, 12);
+        assert!(rows.iter().any(|row| row.contains("Terminal too small")));
+        assert!(rows
+            .iter()
+            .any(|row| row.contains("40x12, need at least 55x20")));
+        assert_eq!(render_regions(&app_state, 40, 12).target_at(1, 1), None);
+    }
+
+    #[test]
+    fn test_compact_layout_abbreviates_categories() {
+        let app_state = AppState::new(DieSpec::default());
+        let rows = render_rows(&app_state, 60, 40);
+        assert!(rows[1].contains("n New | r Roll | d Dice"));
+        assert!(rows.iter().any(|row| row.contains("3K: -")));
+        assert!(!rows.iter().any(|row| row.contains("Three of a kind")));
+    }
+
+    #[test]
+    fn test_wide_layout_splits_scorecard() {
+        let app_state = AppState::new(DieSpec::default());
+        let rows = render_rows(&app_state, 120, 34);
+        let row = rows.iter().position(|row| row.contains("Aces: -")).unwrap();
+        assert!(rows[row].contains("Three of a kind: -"));
+        let regions = render_regions(&app_state, 120, 34);
+        assert_eq!(
+            regions.target_at(10, row as u16),
+            Some(UiTarget::Category(Category::Aces))
+        );
+        assert_eq!(
+            regions.target_at(70, row as u16),
+            Some(UiTarget::Category(Category::ThreeOfAKind))
+        );
+    }
+
+    #[test]
+    fn test_yahtzee_bonus_shows_next_to_yahtzee_box() {
+        let mut app_state = AppState::new(DieSpec::default());
+        app_state.score.lower_section.yahtzee = Some(50);
+        app_state.score.lower_section.yahtzee_bonus = 200;
+        let rows = render_rows(&app_state, 60, 40);
+        assert!(rows.iter().any(|row| row.contains("Y: 50 +200")));
+    }
+}